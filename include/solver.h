@@ -0,0 +1,22 @@
+#ifndef SOLVER_H
+#define SOLVER_H
+
+/* build the library with `cargo rustc --lib --release --features ffi --crate-type cdylib` */
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 0 on success with the value in *result, -1 on failure, -2 if the library panicked */
+int solver_evaluate(const char *expression, double *result);
+
+/* last error on this thread or NULL; free it with solver_string_free */
+char *solver_last_error_message(void);
+
+void solver_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
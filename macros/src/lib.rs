@@ -0,0 +1,94 @@
+use proc_macro::{Literal, TokenStream, TokenTree};
+
+// `solve!("2 * pi * r", r)` checks the expression while compiling and expands to plain f64
+// arithmetic on the listed variables; anything `as f64` accepts can be passed
+#[proc_macro]
+pub fn solve(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(expansion) => expansion,
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+// only the escapes an expression could need, \" and \\
+fn string_literal(literal: &Literal) -> Result<String, String> {
+    let literal = literal.to_string();
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let quotes = &raw[hashes..raw.len() - hashes];
+        if let Some(inner) = quotes
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+        {
+            return Ok(inner.to_string());
+        }
+    }
+    let Some(inner) = literal
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return Err(String::from(
+            "solve! takes the expression as a string literal",
+        ));
+    };
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some(escaped @ ('"' | '\\')) => result.push(escaped),
+            _ => return Err(String::from("only \\\" and \\\\ escapes are supported")),
+        }
+    }
+    Ok(result)
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let mut tokens = input.into_iter();
+    let expression = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => string_literal(&literal)?,
+        _ => {
+            return Err(String::from(
+                "solve! takes a string literal, then the variables it uses",
+            ))
+        }
+    };
+    let mut variables = vec![];
+    loop {
+        match tokens.next() {
+            None => break,
+            Some(TokenTree::Punct(punct)) if punct.as_char() == ',' => {}
+            Some(_) => return Err(String::from("variables are separated by commas")),
+        }
+        match tokens.next() {
+            // a trailing comma
+            None => break,
+            Some(TokenTree::Ident(ident)) => variables.push(ident.to_string()),
+            Some(_) => return Err(String::from("expected a variable name")),
+        }
+    }
+
+    let (source, parameters) =
+        solver::codegen::to_rust_fn_parts(&expression, "solve", &solver::Context::new())
+            .map_err(|error| error.to_string())?;
+    if let Some(missing) = parameters
+        .iter()
+        .find(|parameter| !variables.contains(parameter))
+    {
+        return Err(format!(
+            "{} is used in the expression but not listed after it",
+            missing
+        ));
+    }
+    let arguments: Vec<String> = parameters
+        .iter()
+        .map(|parameter| format!("{} as f64", parameter))
+        .collect();
+    format!("{{\n{}\nsolve({})\n}}", source, arguments.join(", "))
+        .parse()
+        .map_err(|_| String::from("generated code does not parse"))
+}
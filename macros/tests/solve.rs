@@ -0,0 +1,12 @@
+use solver_macros::solve;
+
+#[test]
+fn solve_expands() {
+    let r = 2.0;
+    assert_eq!(solve!("2 * pi * r", r), 2.0 * std::f64::consts::PI * 2.0);
+    let (x, y) = (3, 4.0_f32);
+    assert_eq!(solve!("sqrt(x^2 + y^2)", x, y,), 5.0);
+    assert_eq!(solve!(r"max(1, 2) + 10% >= 2.2"), 1.0);
+    let (a, b) = (1.0, 2.0);
+    assert_eq!(solve!("avg(a, b) - min(b, a)", b, a), 0.5);
+}
@@ -0,0 +1,70 @@
+use arrow_array::builder::Float64Builder;
+use arrow_array::{Array, Float64Array};
+
+use crate::Context;
+
+// evaluates once per row, binding each named column as a variable; a null in any column gives a null result
+pub fn evaluate_columns(
+    expression: &str,
+    columns: &[(&str, &Float64Array)],
+    context: &Context,
+) -> anyhow::Result<Float64Array> {
+    let rows = columns.first().map_or(0, |(_, column)| column.len());
+    if let Some((name, _)) = columns.iter().find(|(_, column)| column.len() != rows) {
+        return Err(anyhow::Error::msg(format!(
+            "column {} has a different length from the others",
+            name
+        )));
+    }
+
+    let expr = crate::parse_with(expression, context)?;
+    let mut context = context.clone();
+    let mut results = Float64Builder::with_capacity(rows);
+    for row in 0..rows {
+        if columns.iter().any(|(_, column)| column.is_null(row)) {
+            results.append_null();
+            continue;
+        }
+        for (name, column) in columns {
+            context.set(name, column.value(row));
+        }
+        let value = expr
+            .evaluate(&context)
+            .map_err(|error| error.context(format!("row {}", row)))?;
+        results.append_value(value);
+    }
+    Ok(results.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorKind};
+
+    #[test]
+    fn evaluate_columns_works() {
+        let price = Float64Array::from(vec![Some(2.0), None, Some(4.5)]);
+        let units = Float64Array::from(vec![3.0, 1.0, 2.0]);
+        let mut context = Context::new();
+        context.set("fee", 1.0);
+        let columns = [("price", &price), ("units", &units)];
+        let result = evaluate_columns("price units - fee", &columns, &context).unwrap();
+        assert_eq!(result, Float64Array::from(vec![Some(5.0), None, Some(8.0)]));
+
+        assert_eq!(evaluate_columns("1", &[], &context).unwrap().len(), 0);
+    }
+    #[test]
+    fn evaluate_columns_errors() {
+        let a = Float64Array::from(vec![1.0, 0.0]);
+        let b = Float64Array::from(vec![1.0]);
+        let context = Context::new();
+        assert!(evaluate_columns("a + b", &[("a", &a), ("b", &b)], &context).is_err());
+
+        let error = evaluate_columns("1 / a", &[("a", &a)], &context).unwrap_err();
+        assert_eq!(error.to_string(), "row 1");
+        assert_eq!(
+            error.downcast_ref::<Error>().map(|error| &error.kind),
+            Some(&ErrorKind::DivisionByZero)
+        );
+    }
+}
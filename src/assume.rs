@@ -0,0 +1,352 @@
+use std::fmt;
+
+use crate::ast::Expr;
+use crate::lexer::Operator;
+use crate::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Positive,
+    NonNegative,
+    Negative,
+    NonPositive,
+    NonZero,
+    Integer,
+    // neither infinite nor NaN
+    Finite,
+}
+
+impl Property {
+    fn implies(self, other: Property) -> bool {
+        self == other
+            || matches!(
+                (self, other),
+                (
+                    Property::Positive,
+                    Property::NonNegative | Property::NonZero
+                ) | (
+                    Property::Negative,
+                    Property::NonPositive | Property::NonZero
+                ) | (Property::Integer, Property::Finite)
+            )
+    }
+
+    // what the value has to be for its negation to have this property
+    fn negated(self) -> Property {
+        match self {
+            Property::Positive => Property::Negative,
+            Property::NonNegative => Property::NonPositive,
+            Property::Negative => Property::Positive,
+            Property::NonPositive => Property::NonNegative,
+            property => property,
+        }
+    }
+
+    fn holds(self, value: f64) -> bool {
+        match self {
+            Property::Positive => value > 0.0,
+            Property::NonNegative => value >= 0.0,
+            Property::Negative => value < 0.0,
+            Property::NonPositive => value <= 0.0,
+            Property::NonZero => value != 0.0 && !value.is_nan(),
+            Property::Integer => value.fract() == 0.0,
+            Property::Finite => value.is_finite(),
+        }
+    }
+}
+
+// `x > 0` or `n is integer`, about one variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assumption {
+    pub name: String,
+    pub property: Property,
+}
+
+impl fmt::Display for Assumption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let condition = match self.property {
+            Property::Positive => "> 0",
+            Property::NonNegative => ">= 0",
+            Property::Negative => "< 0",
+            Property::NonPositive => "<= 0",
+            Property::NonZero => "!= 0",
+            Property::Integer => "is integer",
+            Property::Finite => "is finite",
+        };
+        write!(f, "{} {}", self.name, condition)
+    }
+}
+
+impl Assumption {
+    pub fn parse(statement: &str) -> anyhow::Result<Assumption> {
+        let invalid = || {
+            anyhow::Error::msg(
+                "an assumption compares a variable with 0, like `x > 0`, or reads `n is integer` or `x is finite`",
+            )
+        };
+        if let [name, "is", kind] = statement.split_whitespace().collect::<Vec<_>>()[..] {
+            let property = match kind {
+                "integer" => Property::Integer,
+                "finite" => Property::Finite,
+                _ => return Err(invalid()),
+            };
+            return match crate::parse(name)? {
+                Expr::Variable { name, .. } => Ok(Assumption { name, property }),
+                _ => Err(invalid()),
+            };
+        }
+        let Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } = crate::parse(statement)?
+        else {
+            return Err(invalid());
+        };
+        // `0 < x` is x > 0
+        let (name, operator) = match (*left, *right) {
+            (Expr::Variable { name, .. }, Expr::Number(0.0)) => (name, operator),
+            (Expr::Number(0.0), Expr::Variable { name, .. }) => {
+                let flipped = match operator {
+                    Operator::Less => Operator::Greater,
+                    Operator::LessEqual => Operator::GreaterEqual,
+                    Operator::Greater => Operator::Less,
+                    Operator::GreaterEqual => Operator::LessEqual,
+                    operator => operator,
+                };
+                (name, flipped)
+            }
+            _ => return Err(invalid()),
+        };
+        let property = match operator {
+            Operator::Greater => Property::Positive,
+            Operator::GreaterEqual => Property::NonNegative,
+            Operator::Less => Property::Negative,
+            Operator::LessEqual => Property::NonPositive,
+            Operator::NotEqual => Property::NonZero,
+            _ => return Err(invalid()),
+        };
+        Ok(Assumption { name, property })
+    }
+}
+
+fn both(
+    first: Option<Vec<usize>>,
+    second: impl FnOnce() -> Option<Vec<usize>>,
+) -> Option<Vec<usize>> {
+    let mut used = first?;
+    used.extend(second()?);
+    Some(used)
+}
+
+// the assumptions on the context that show `expr` has the property, by index, or none when it can't be shown;
+// this only follows signs and integrality through arithmetic and a few functions, it is no theorem prover;
+// finiteness only holds of numbers and variables, since any arithmetic can overflow
+pub fn prove(expr: &Expr, property: Property, context: &Context) -> Option<Vec<usize>> {
+    let mut used = prove_directly(expr, property, context).or_else(|| match property {
+        Property::NonNegative => prove(expr, Property::Positive, context),
+        Property::NonPositive => prove(expr, Property::Negative, context),
+        Property::NonZero => prove(expr, Property::Positive, context)
+            .or_else(|| prove(expr, Property::Negative, context)),
+        _ => None,
+    })?;
+    used.sort_unstable();
+    used.dedup();
+    Some(used)
+}
+
+fn prove_directly(expr: &Expr, property: Property, context: &Context) -> Option<Vec<usize>> {
+    use Property::*;
+    let prove = |expr: &Expr, property: Property| prove(expr, property, context);
+    let sign = |property: Property| !matches!(property, Integer | NonZero | Finite);
+
+    match expr {
+        Expr::Number(number) => property.holds(*number).then(Vec::new),
+        Expr::Constant(constant) => property.holds(constant.value()).then(Vec::new),
+        Expr::Variable { name, .. } => context
+            .assumptions()
+            .iter()
+            .position(|assumption| {
+                assumption.name == *name && assumption.property.implies(property)
+            })
+            .map(|index| vec![index]),
+        Expr::Unary {
+            operator: Operator::Negative,
+            operand,
+            ..
+        } => prove(operand, property.negated()),
+        Expr::Unary {
+            operator: Operator::Percent,
+            operand,
+            ..
+        } if sign(property) || matches!(property, NonZero | Finite) => prove(operand, property),
+        // n! of a whole n >= 0 is a positive integer
+        Expr::Unary {
+            operator: Operator::Factorial,
+            operand,
+            ..
+        } if matches!(property, Positive | Integer) => {
+            both(prove(operand, Integer), || prove(operand, NonNegative))
+        }
+        Expr::Binary {
+            operator: operator @ (Operator::Plus | Operator::Minus),
+            left,
+            right,
+            ..
+        } => {
+            // a - b has the signs of a + (-b)
+            let right_property = |property: Property| match operator {
+                Operator::Minus => property.negated(),
+                _ => property,
+            };
+            let weak = match property {
+                Positive => NonNegative,
+                Negative => NonPositive,
+                property => property,
+            };
+            match property {
+                Positive | Negative => {
+                    both(prove(left, property), || prove(right, right_property(weak))).or_else(
+                        || both(prove(left, weak), || prove(right, right_property(property))),
+                    )
+                }
+                NonNegative | NonPositive | Integer => both(prove(left, property), || {
+                    prove(right, right_property(property))
+                }),
+                NonZero | Finite => None,
+            }
+        }
+        Expr::Binary {
+            operator: operator @ (Operator::Multiply | Operator::Divide),
+            left,
+            right,
+            ..
+        } => {
+            // the divisor is never zero, so it has to be strictly signed for the weak cases
+            let divisor = |property: Property| match (operator, property) {
+                (Operator::Divide, NonNegative) => Positive,
+                (Operator::Divide, NonPositive) => Negative,
+                (_, property) => property,
+            };
+            let pair = |a: Property, b: Property| both(prove(left, a), || prove(right, divisor(b)));
+            match property {
+                Positive => pair(Positive, Positive).or_else(|| pair(Negative, Negative)),
+                NonNegative => {
+                    pair(NonNegative, NonNegative).or_else(|| pair(NonPositive, NonPositive))
+                }
+                Negative => pair(Positive, Negative).or_else(|| pair(Negative, Positive)),
+                NonPositive => {
+                    pair(NonNegative, NonPositive).or_else(|| pair(NonPositive, NonNegative))
+                }
+                NonZero => pair(NonZero, NonZero),
+                Integer if *operator == Operator::Multiply => pair(Integer, Integer),
+                Integer | Finite => None,
+            }
+        }
+        Expr::Binary {
+            operator: Operator::Power,
+            left,
+            right,
+            ..
+        } => {
+            let exponent = match **right {
+                Expr::Number(exponent) if exponent.fract() == 0.0 => Some(exponent),
+                _ => None,
+            };
+            match (property, exponent) {
+                (Finite, _) => None,
+                (Integer, Some(exponent)) if exponent >= 0.0 => prove(left, Integer),
+                (Integer, _) => None,
+                // an even power can't be negative
+                (NonNegative, Some(exponent)) if exponent % 2.0 == 0.0 => Some(vec![]),
+                (Positive | NonZero, Some(exponent)) if exponent % 2.0 == 0.0 => {
+                    prove(left, NonZero)
+                }
+                (Negative | NonPositive, Some(exponent)) if exponent % 2.0 == 0.0 => None,
+                // only an odd power keeps the sign of its base, though 0^-1 is +infinity
+                (NonPositive, Some(exponent)) if exponent < 0.0 => prove(left, Negative),
+                (_, Some(_)) => prove(left, property),
+                (Positive | NonNegative | NonZero, None) => prove(left, Positive),
+                (_, None) => None,
+            }
+        }
+        Expr::Call {
+            name, arguments, ..
+        } if context.function(name).is_none() => match (name.as_str(), &arguments[..]) {
+            ("exp", [_]) if sign(property) => Positive.implies(property).then(Vec::new),
+            ("abs", [_]) if property == NonNegative => Some(vec![]),
+            ("abs", [argument]) if matches!(property, Positive | NonZero | Integer) => {
+                let needed = if property == Integer {
+                    Integer
+                } else {
+                    NonZero
+                };
+                prove(argument, needed)
+            }
+            ("sqrt", [argument]) if matches!(property, Positive | NonNegative) => {
+                prove(argument, property)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(statements: &[&str]) -> Context {
+        let mut context = Context::new();
+        for statement in statements {
+            context.assume(statement).unwrap();
+        }
+        context
+    }
+
+    #[test]
+    fn parse_works() {
+        let parsed = |statement| Assumption::parse(statement).unwrap().to_string();
+        assert_eq!(parsed("x > 0"), "x > 0");
+        assert_eq!(parsed("0 >= rate"), "rate <= 0");
+        assert_eq!(parsed("y != 0"), "y != 0");
+        assert_eq!(parsed("n  is integer"), "n is integer");
+        assert_eq!(parsed("x is finite"), "x is finite");
+        assert!(Assumption::parse("x is real").is_err());
+        assert!(Assumption::parse("x > 1").is_err());
+        assert!(Assumption::parse("x + 1 > 0").is_err());
+        assert!(Assumption::parse("2 is integer").is_err());
+        assert!(Assumption::parse("x").is_err());
+    }
+    #[test]
+    fn prove_works() {
+        let context = context(&["x > 0", "y <= 0", "n is integer"]);
+        let proof =
+            |expression, property| prove(&crate::parse(expression).unwrap(), property, &context);
+        assert_eq!(proof("2x + 1", Property::Positive), Some(vec![0]));
+        assert_eq!(proof("x - y", Property::Positive), Some(vec![0, 1]));
+        assert_eq!(proof("-x * y / x", Property::NonNegative), Some(vec![0, 1]));
+        assert_eq!(proof("y^2 + sqrt(x)", Property::NonNegative), Some(vec![0]));
+        assert_eq!(proof("abs(y) + exp(y)", Property::NonZero), Some(vec![]));
+        assert_eq!(proof("3n^2 - n", Property::Integer), Some(vec![2]));
+        assert_eq!(proof("x / y", Property::NonPositive), None);
+        assert_eq!(proof("x + y", Property::Positive), None);
+        assert_eq!(proof("n / 2", Property::Integer), None);
+        assert_eq!(proof("z^0.5", Property::NonNegative), None);
+        assert_eq!(proof("y^2", Property::NonPositive), None);
+        assert_eq!(proof("y^0", Property::Negative), None);
+        assert_eq!(proof("y^3", Property::NonPositive), Some(vec![1]));
+        // folded, so the exponent is the literal -1
+        let folded = |expression, property| {
+            let expr = crate::rewrite::Rewriter::new().rewrite(&crate::parse(expression).unwrap());
+            prove(&expr, property, &context)
+        };
+        assert_eq!(folded("y^-1", Property::NonPositive), None);
+        assert_eq!(folded("(-x)^-1", Property::Negative), Some(vec![0]));
+        assert_eq!(proof("-n", Property::Finite), Some(vec![2]));
+        assert_eq!(proof("2.5", Property::Finite), Some(vec![]));
+        assert_eq!(proof("x", Property::Finite), None);
+        assert_eq!(proof("n * n", Property::Finite), None);
+    }
+}
@@ -0,0 +1,512 @@
+use std::fmt;
+
+use crate::error::{Error, ErrorKind};
+use crate::lexer::{self, Constant, Operator, Token};
+use crate::parser::{self, CONDITIONAL, NEGATION, NOT, POSTFIX};
+use crate::{AngleMode, Associativity, Context};
+
+// `token` fields index into the token stream the node was parsed from, for error reporting
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Number(f64),
+    Constant(Constant),
+    Variable {
+        name: String,
+        token: usize,
+    },
+    Unary {
+        operator: Operator,
+        operand: Box<Expr>,
+        token: usize,
+    },
+    Binary {
+        operator: Operator,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        token: usize,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expr>,
+        token: usize,
+    },
+    // `condition ? then : otherwise`, `if(..)` and `piecewise(..)`; its own node so a host
+    // function named if can't take it over
+    Conditional {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+        token: usize,
+    },
+}
+
+// what an expression means, predicates give booleans even though they compute 1 or 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl From<Value> for f64 {
+    fn from(value: Value) -> f64 {
+        match value {
+            Value::Number(number) => number,
+            Value::Bool(true) => 1.0,
+            Value::Bool(false) => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Bool(boolean) => write!(f, "{}", boolean),
+        }
+    }
+}
+
+// only as many parentheses as it takes to parse back to the same tree;
+// custom operators have no precedence here, so they and their operands get them whenever nested
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0, "")
+    }
+}
+
+impl Expr {
+    // `next` is what gets written right after this expression, which decides whether a
+    // trailing `%` reads back as a percent or as a remainder
+    fn write(&self, f: &mut fmt::Formatter, minimum: u16, next: &str) -> fmt::Result {
+        let grouped =
+            |f: &mut fmt::Formatter,
+             precedence: u8,
+             inner: &dyn Fn(&mut fmt::Formatter, &str) -> fmt::Result| {
+                if u16::from(precedence) < minimum {
+                    write!(f, "(")?;
+                    inner(f, ")")?;
+                    write!(f, ")")
+                } else {
+                    inner(f, next)
+                }
+            };
+        match self {
+            // folding can leave a negative literal, which reads back as a negation
+            Expr::Number(number) if number.is_sign_negative() && *number != 0.0 => {
+                grouped(f, NEGATION, &|f, _| write!(f, "{}", number))
+            }
+            Expr::Number(number) => write!(f, "{}", number),
+            Expr::Constant(constant) => write!(f, "{}", Token::Constant(constant.clone())),
+            Expr::Variable { name, .. } => write!(f, "{}", name),
+            Expr::Unary {
+                operator: operator @ (Operator::Factorial | Operator::Percent),
+                operand,
+                ..
+            } => {
+                let symbol = Token::Unary(operator.clone()).to_string();
+                // a percent followed by something that would make it a remainder is kept whole
+                let precedence = if *operator == Operator::Percent && !lexer::percent_before(next) {
+                    0
+                } else {
+                    POSTFIX
+                };
+                grouped(f, precedence, &|f, next| {
+                    operand.write(f, POSTFIX.into(), &format!("{}{}", symbol, next))?;
+                    write!(f, "{}", symbol)
+                })
+            }
+            Expr::Unary {
+                operator, operand, ..
+            } => {
+                let (precedence, inner) = match operator {
+                    Operator::Not => (NOT, NOT),
+                    Operator::Custom(_) => (0, u8::MAX),
+                    _ => (NEGATION, NEGATION),
+                };
+                let symbol = Token::Unary(operator.clone()).to_string();
+                // a word needs a space before its operand
+                let space = if symbol.ends_with(|char: char| char.is_ascii_alphanumeric()) {
+                    " "
+                } else {
+                    ""
+                };
+                grouped(f, precedence, &|f, next| {
+                    write!(f, "{}{}", symbol, space)?;
+                    operand.write(f, inner.into(), next)
+                })
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let (precedence, left_minimum, right_minimum) =
+                    match parser::builtin_precedence(operator) {
+                        Some((precedence, Associativity::Left)) => {
+                            (precedence, precedence.into(), u16::from(precedence) + 1)
+                        }
+                        Some((precedence, Associativity::Right)) => {
+                            (precedence, u16::from(precedence) + 1, precedence.into())
+                        }
+                        None => (0, u8::MAX.into(), u8::MAX.into()),
+                    };
+                let symbol = Token::Binary(operator.clone());
+                let between = if *operator == Operator::Power {
+                    symbol.to_string()
+                } else {
+                    format!(" {} ", symbol)
+                };
+                grouped(f, precedence, &|f, next| {
+                    left.write(f, left_minimum, &between)?;
+                    write!(f, "{}", between)?;
+                    right.write(f, right_minimum, next)
+                })
+            }
+            Expr::Call {
+                name, arguments, ..
+            } => {
+                write!(f, "{}(", name)?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let next = if index + 1 < arguments.len() {
+                        ", "
+                    } else {
+                        ")"
+                    };
+                    argument.write(f, 0, next)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => grouped(f, CONDITIONAL, &|f, next| {
+                condition.write(f, u16::from(CONDITIONAL) + 1, " ? ")?;
+                write!(f, " ? ")?;
+                then.write(f, 0, " : ")?;
+                write!(f, " : ")?;
+                otherwise.write(f, CONDITIONAL.into(), next)
+            }),
+        }
+    }
+}
+
+fn error(kind: ErrorKind, token: usize) -> anyhow::Error {
+    Error::new(kind, Some(token)).into()
+}
+
+// past 2^53 an f64 no longer holds every integer, so its bits would be made up
+const MAX_BITWISE: f64 = 9_007_199_254_740_992.0;
+
+fn bits(value: f64, token: usize) -> anyhow::Result<i64> {
+    if value.fract() != 0.0 || value.abs() > MAX_BITWISE {
+        return Err(error(ErrorKind::NotAnInteger, token));
+    }
+    Ok(value as i64)
+}
+
+// predicates compute 1 or 0, and as an operand any nonzero number is true
+fn truth(holds: bool) -> f64 {
+    if holds {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Expr {
+    pub fn is_predicate(&self, context: &Context) -> bool {
+        match self {
+            Expr::Binary { operator, .. } => {
+                matches!(operator, Operator::Approx | Operator::And | Operator::Or)
+                    || operator.is_comparison()
+            }
+            Expr::Unary { operator, .. } => *operator == Operator::Not,
+            // a host function named approx is just a function
+            Expr::Call { name, .. } => name == "approx" && context.function(name).is_none(),
+            Expr::Conditional {
+                then, otherwise, ..
+            } => then.is_predicate(context) && otherwise.is_predicate(context),
+            _ => false,
+        }
+    }
+
+    pub fn evaluate_value(&self, context: &Context) -> anyhow::Result<Value> {
+        let value = self.evaluate(context)?;
+        if self.is_predicate(context) {
+            Ok(Value::Bool(value != 0.0))
+        } else {
+            Ok(Value::Number(value))
+        }
+    }
+
+    pub fn evaluate(&self, context: &Context) -> anyhow::Result<f64> {
+        match self {
+            Expr::Number(number) => Ok(*number),
+            Expr::Constant(constant) => Ok(constant.value()),
+            Expr::Variable { name, token } => context
+                .get(name)
+                .ok_or_else(|| error(ErrorKind::UnknownVariable(name.clone()), *token)),
+            Expr::Unary {
+                operator,
+                operand,
+                token,
+            } => {
+                let operand = operand.evaluate(context)?;
+                match operator {
+                    Operator::Negative => Ok(-operand),
+                    Operator::Percent => Ok(operand / 100.0),
+                    Operator::Not => Ok(truth(operand == 0.0)),
+                    Operator::BitNot => Ok(!bits(operand, *token)? as f64),
+                    Operator::Factorial => crate::factorial(operand, &context.options)
+                        .ok_or_else(|| error(ErrorKind::InvalidFactorial, *token)),
+                    Operator::Custom(symbol) => match context.unary_operator(symbol) {
+                        Some(registered) => Ok((registered.function)(operand)),
+                        None => Err(error(ErrorKind::UnexpectedToken, *token)),
+                    },
+                    _ => Err(error(ErrorKind::UnexpectedToken, *token)),
+                }
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                token,
+            } => {
+                let left = left.evaluate(context)?;
+                // short circuit, so `x != 0 and 1 / x > 2` never divides by zero
+                match operator {
+                    Operator::And if left == 0.0 => return Ok(0.0),
+                    Operator::Or if left != 0.0 => return Ok(1.0),
+                    _ => {}
+                }
+                let right = right.evaluate(context)?;
+                if matches!(operator, Operator::Divide | Operator::Remainder) && right == 0.0 {
+                    return Err(error(ErrorKind::DivisionByZero, *token));
+                }
+                match operator {
+                    Operator::And | Operator::Or => Ok(truth(right != 0.0)),
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                        let (left, right) = (bits(left, *token)?, bits(right, *token)?);
+                        Ok(operator.bitwise(left, right).unwrap_or_default() as f64)
+                    }
+                    Operator::Plus => Ok(left + right),
+                    Operator::Minus => Ok(left - right),
+                    Operator::Multiply => Ok(left * right),
+                    Operator::Divide => Ok(left / right),
+                    Operator::Remainder => Ok(left % right),
+                    Operator::Power => Ok(left.powf(right)),
+                    Operator::Approx => Ok(crate::approx(
+                        left,
+                        right,
+                        context.options.approx_absolute,
+                        context.options.approx_relative,
+                    )),
+                    Operator::Custom(symbol) => match context.binary_operator(symbol) {
+                        Some(registered) => Ok((registered.function)(left, right)),
+                        None => Err(error(ErrorKind::UnexpectedToken, *token)),
+                    },
+                    comparison => match comparison.compare(left, right) {
+                        Some(holds) => Ok(truth(holds)),
+                        None => Err(error(ErrorKind::UnexpectedToken, *token)),
+                    },
+                }
+            }
+            Expr::Call {
+                name,
+                arguments,
+                token,
+            } => evaluate_call(name, arguments, *token, context),
+            // only the branch taken is evaluated, so `x != 0 ? 1 / x : 0` never divides by zero
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                if condition.evaluate(context)? != 0.0 {
+                    return then.evaluate(context);
+                }
+                otherwise.evaluate(context)
+            }
+        }
+    }
+}
+
+fn evaluate_call(
+    name: &str,
+    arguments: &[Expr],
+    token: usize,
+    context: &Context,
+) -> anyhow::Result<f64> {
+    let registered = context.function(name);
+    let builtin = crate::lookup_function(name);
+    let multiple = crate::lookup_multiple(name);
+    if registered.is_none() && builtin.is_none() && multiple.is_none() {
+        return Err(error(ErrorKind::UnknownFunction(name.to_string()), token));
+    }
+
+    let arguments = arguments
+        .iter()
+        .map(|argument| argument.evaluate(context))
+        .collect::<anyhow::Result<Vec<f64>>>()?;
+    let expected = match (registered, multiple) {
+        (Some(registered), _) => Some(registered.arity),
+        (None, Some((arity, _))) => arity,
+        (None, None) => Some(1),
+    };
+    let Some(expected) = expected else {
+        if arguments.is_empty() {
+            let kind = ErrorKind::TooFewArguments {
+                name: name.to_string(),
+                minimum: 1,
+                found: 0,
+            };
+            return Err(error(kind, token));
+        }
+        let (_, function) = multiple.unwrap();
+        return Ok(function(&arguments, &context.options));
+    };
+    if arguments.len() != expected {
+        let kind = ErrorKind::WrongArgumentCount {
+            name: name.to_string(),
+            expected,
+            found: arguments.len(),
+        };
+        return Err(error(kind, token));
+    }
+
+    if let Some(registered) = registered {
+        return Ok((registered.function)(&arguments));
+    }
+    if let Some((_, function)) = multiple {
+        return Ok(function(&arguments, &context.options));
+    }
+    let mut argument = arguments[0];
+    if context.options.angle_mode == AngleMode::Degrees && crate::TRIGONOMETRIC.contains(&name) {
+        argument = argument.to_radians();
+    }
+    Ok(builtin.unwrap()(argument))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_builds_tree() {
+        let expr = crate::parse("-2^x!").unwrap();
+        let expected = Expr::Unary {
+            operator: Operator::Negative,
+            operand: Box::new(Expr::Binary {
+                operator: Operator::Power,
+                left: Box::new(Expr::Number(2.0)),
+                right: Box::new(Expr::Unary {
+                    operator: Operator::Factorial,
+                    operand: Box::new(Expr::Variable {
+                        name: String::from("x"),
+                        token: 3,
+                    }),
+                    token: 4,
+                }),
+                token: 2,
+            }),
+            token: 0,
+        };
+        assert_eq!(expr, expected);
+
+        let mut context = Context::new();
+        context.set("x", 3.0);
+        assert_eq!(expr.evaluate(&context).unwrap(), -64.0);
+        context.set("x", 0.0);
+        assert_eq!(expr.evaluate(&context).unwrap(), -2.0);
+    }
+    #[test]
+    fn evaluate_value_works() {
+        let context = Context::new();
+        let value = |expression| crate::parse(expression).unwrap().evaluate_value(&context);
+        assert_eq!(value("0.1 + 0.2 ~= 0.3").unwrap(), Value::Bool(true));
+        assert_eq!(value("approx(1, 2, 0.1)").unwrap(), Value::Bool(false));
+        assert_eq!(value("(1 ~= 1) + 1").unwrap(), Value::Number(2.0));
+        assert_eq!(value("2 + 2 == 4").unwrap(), Value::Bool(true));
+        assert_eq!(value("3! != 6").unwrap(), Value::Bool(false));
+        assert_eq!(value("1 < 2 == 1").unwrap(), Value::Bool(true));
+        assert_eq!(value("-1 >= 0").unwrap(), Value::Bool(false));
+        assert_eq!(value("1 and 2").unwrap(), Value::Bool(true));
+        assert_eq!(value("not 0").unwrap(), Value::Bool(true));
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(f64::from(Value::Bool(true)), 1.0);
+
+        let mut context = Context::new();
+        context.register_fn("approx", 1, |args| args[0]);
+        let expr = crate::parse("approx(5)").unwrap();
+        assert_eq!(expr.evaluate_value(&context).unwrap(), Value::Number(5.0));
+    }
+    #[test]
+    fn display_works() {
+        let display = |expression| crate::parse(expression).unwrap().to_string();
+        assert_eq!(display("2x^2 + (3 - y) / 4"), "2 * x^2 + (3 - y) / 4");
+        assert_eq!(
+            display("(2^3)^2 - (1 - (2 - 3))"),
+            "(2^3)^2 - (1 - (2 - 3))"
+        );
+        assert_eq!(
+            display("-(2^x)! + (-2)^2 + 2^-x"),
+            "-(2^x)! + (-2)^2 + 2^(-x)"
+        );
+        assert_eq!(
+            display("not (x > 1 and y) or ~(a | b)"),
+            "not (x > 1 and y) or ~(a | b)"
+        );
+        let converted = crate::parse_tokens("max(1, pi) in mi", &Context::new(), true).unwrap();
+        assert_eq!(converted.to_string(), "max(1, pi) in mi");
+        assert_eq!(display("(50%)!"), "(50%)!");
+        assert_eq!(display("(5%) % 3"), "(5%) % 3");
+        assert_eq!(display("(5%) - 3"), "5% - 3");
+        assert_eq!(
+            display("(a ? b : c) + (d ? e ? 1 : 2 : (f ? 3 : 4))"),
+            "(a ? b : c) + (d ? e ? 1 : 2 : f ? 3 : 4)"
+        );
+        assert_eq!(display("if(x, 1, 2) * 3"), "(x ? 1 : 2) * 3");
+
+        let folded = Expr::Binary {
+            operator: Operator::Power,
+            left: Box::new(Expr::Number(-2.0)),
+            right: Box::new(Expr::Number(2.0)),
+            token: 0,
+        };
+        assert_eq!(folded.to_string(), "(-2)^2");
+        assert_eq!(crate::evaluate(&folded.to_string()).unwrap(), 4.0);
+    }
+    #[test]
+    fn display_parses_back() {
+        for expression in [
+            "(5%) % 3 + 0",
+            "(50%)!",
+            "2 * (5%) % 3",
+            "-(5%) in mi",
+            "max(5%, 1%) + 10% of 20",
+            "x ? 5% : 1% xor 2",
+            "(5%)^2 - -3% * 4",
+        ] {
+            let expr = crate::parse_tokens(expression, &Context::new(), true).unwrap();
+            let displayed = expr.to_string();
+            let parsed = crate::parse_tokens(&displayed, &Context::new(), true).unwrap();
+            assert_eq!(parsed.to_string(), displayed, "{}", expression);
+            assert!(crate::rewrite::same(&parsed, &expr), "{}", expression);
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn expr_round_trips() {
+        let expr = crate::parse("max(2x, -pi) + 3!").unwrap();
+        let json = serde_json::to_string(&expr).unwrap();
+        assert_eq!(serde_json::from_str::<Expr>(&json).unwrap(), expr);
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+use std::iter::Peekable;
+
+use crate::units::{self, Quantity};
+
+// standard atomic weights in g/mol, mass number of the longest-lived isotope for elements without one
+const ELEMENTS: [(&str, f64); 118] = [
+    ("H", 1.008),
+    ("He", 4.0026),
+    ("Li", 6.94),
+    ("Be", 9.0122),
+    ("B", 10.81),
+    ("C", 12.011),
+    ("N", 14.007),
+    ("O", 15.999),
+    ("F", 18.998),
+    ("Ne", 20.180),
+    ("Na", 22.990),
+    ("Mg", 24.305),
+    ("Al", 26.982),
+    ("Si", 28.085),
+    ("P", 30.974),
+    ("S", 32.06),
+    ("Cl", 35.45),
+    ("Ar", 39.948),
+    ("K", 39.098),
+    ("Ca", 40.078),
+    ("Sc", 44.956),
+    ("Ti", 47.867),
+    ("V", 50.942),
+    ("Cr", 51.996),
+    ("Mn", 54.938),
+    ("Fe", 55.845),
+    ("Co", 58.933),
+    ("Ni", 58.693),
+    ("Cu", 63.546),
+    ("Zn", 65.38),
+    ("Ga", 69.723),
+    ("Ge", 72.630),
+    ("As", 74.922),
+    ("Se", 78.971),
+    ("Br", 79.904),
+    ("Kr", 83.798),
+    ("Rb", 85.468),
+    ("Sr", 87.62),
+    ("Y", 88.906),
+    ("Zr", 91.224),
+    ("Nb", 92.906),
+    ("Mo", 95.95),
+    ("Tc", 98.0),
+    ("Ru", 101.07),
+    ("Rh", 102.91),
+    ("Pd", 106.42),
+    ("Ag", 107.87),
+    ("Cd", 112.41),
+    ("In", 114.82),
+    ("Sn", 118.71),
+    ("Sb", 121.76),
+    ("Te", 127.60),
+    ("I", 126.90),
+    ("Xe", 131.29),
+    ("Cs", 132.91),
+    ("Ba", 137.33),
+    ("La", 138.91),
+    ("Ce", 140.12),
+    ("Pr", 140.91),
+    ("Nd", 144.24),
+    ("Pm", 145.0),
+    ("Sm", 150.36),
+    ("Eu", 151.96),
+    ("Gd", 157.25),
+    ("Tb", 158.93),
+    ("Dy", 162.50),
+    ("Ho", 164.93),
+    ("Er", 167.26),
+    ("Tm", 168.93),
+    ("Yb", 173.05),
+    ("Lu", 174.97),
+    ("Hf", 178.49),
+    ("Ta", 180.95),
+    ("W", 183.84),
+    ("Re", 186.21),
+    ("Os", 190.23),
+    ("Ir", 192.22),
+    ("Pt", 195.08),
+    ("Au", 196.97),
+    ("Hg", 200.59),
+    ("Tl", 204.38),
+    ("Pb", 207.2),
+    ("Bi", 208.98),
+    ("Po", 209.0),
+    ("At", 210.0),
+    ("Rn", 222.0),
+    ("Fr", 223.0),
+    ("Ra", 226.0),
+    ("Ac", 227.0),
+    ("Th", 232.04),
+    ("Pa", 231.04),
+    ("U", 238.03),
+    ("Np", 237.0),
+    ("Pu", 244.0),
+    ("Am", 243.0),
+    ("Cm", 247.0),
+    ("Bk", 247.0),
+    ("Cf", 251.0),
+    ("Es", 252.0),
+    ("Fm", 257.0),
+    ("Md", 258.0),
+    ("No", 259.0),
+    ("Lr", 266.0),
+    ("Rf", 267.0),
+    ("Db", 268.0),
+    ("Sg", 269.0),
+    ("Bh", 270.0),
+    ("Hs", 269.0),
+    ("Mt", 278.0),
+    ("Ds", 281.0),
+    ("Rg", 282.0),
+    ("Cn", 285.0),
+    ("Nh", 286.0),
+    ("Fl", 289.0),
+    ("Mc", 290.0),
+    ("Lv", 293.0),
+    ("Ts", 294.0),
+    ("Og", 294.0),
+];
+
+pub fn atomic_mass(symbol: &str) -> Option<f64> {
+    ELEMENTS
+        .iter()
+        .find(|(element, _)| *element == symbol)
+        .map(|(_, mass)| *mass)
+}
+
+// no digits at all is a count of one
+fn parse_count<Iter: Iterator<Item = char>>(iterator: &mut Peekable<Iter>) -> anyhow::Result<u32> {
+    let mut buffer = String::new();
+    while let Some(&char) = iterator.peek() {
+        match char {
+            '0'..='9' => buffer.push(char),
+            _ => break,
+        }
+        iterator.next();
+    }
+    if buffer.is_empty() {
+        return Ok(1);
+    }
+    match buffer.parse::<u32>() {
+        Ok(0) => Err(anyhow::Error::msg("a count of zero in formula")),
+        Ok(count) => Ok(count),
+        Err(_) => Err(anyhow::Error::msg(format!(
+            "count too large in formula: {}",
+            buffer
+        ))),
+    }
+}
+
+fn parse_formula<Iter: Iterator<Item = char>>(
+    iterator: &mut Peekable<Iter>,
+) -> anyhow::Result<f64> {
+    // one running total per open group with the bracket that closes it, the outermost formula first
+    let mut groups = vec![(0.0, None)];
+
+    while let Some(&char) = iterator.peek() {
+        match char {
+            'A'..='Z' => {
+                let mut symbol = char.to_string();
+                iterator.next();
+                if let Some(&lower @ 'a'..='z') = iterator.peek() {
+                    symbol.push(lower);
+                    iterator.next();
+                }
+                let mass = atomic_mass(&symbol)
+                    .ok_or_else(|| anyhow::Error::msg(format!("unknown element {}", symbol)))?;
+                let count = parse_count(iterator)?;
+                groups.last_mut().unwrap().0 += mass * count as f64;
+            }
+            '(' => {
+                groups.push((0.0, Some(')')));
+                iterator.next();
+            }
+            '[' => {
+                groups.push((0.0, Some(']')));
+                iterator.next();
+            }
+            ')' | ']' => {
+                iterator.next();
+                match groups.last() {
+                    Some((_, Some(closing))) if *closing == char => {}
+                    Some((_, Some(closing))) => {
+                        return Err(anyhow::Error::msg(format!(
+                            "expected {} but found {} in formula",
+                            closing, char
+                        )))
+                    }
+                    _ => return Err(anyhow::Error::msg("unmatched closing group in formula")),
+                }
+                let (group, _) = groups.pop().unwrap();
+                let count = parse_count(iterator)?;
+                groups.last_mut().unwrap().0 += group * count as f64;
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "unknown character in formula: {}",
+                    char
+                )))
+            }
+        }
+    }
+
+    if groups.len() != 1 {
+        return Err(anyhow::Error::msg("unclosed group in formula"));
+    }
+    Ok(groups[0].0)
+}
+
+// in g/mol, the way the tables give it, so water comes out as 18.015 g/mol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MolarMass {
+    pub grams_per_mole: f64,
+}
+
+impl MolarMass {
+    // in base units, kg/mol, to go on with unit arithmetic
+    pub fn quantity(&self) -> Quantity {
+        let grams = units::unit("g").unwrap().value;
+        Quantity {
+            value: self.grams_per_mole * grams,
+            dimension: units::MOLAR_MASS,
+        }
+    }
+}
+
+impl fmt::Display for MolarMass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} g/mol", self.grams_per_mole)
+    }
+}
+
+pub fn molar_mass(formula: &str) -> anyhow::Result<MolarMass> {
+    let mut total = 0.0;
+
+    // hydrates and adducts like CuSO4·5H2O are written as dot-separated parts
+    for part in formula.split(['·', '.', '*']) {
+        let mut iterator = part.trim().chars().peekable();
+        let coefficient = parse_count(&mut iterator)?;
+        if iterator.peek().is_none() {
+            return Err(anyhow::Error::msg("empty chemical formula"));
+        }
+        total += coefficient as f64 * parse_formula(&mut iterator)?;
+    }
+
+    Ok(MolarMass {
+        grams_per_mole: total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn grams(formula: &str) -> f64 {
+        molar_mass(formula).unwrap().grams_per_mole
+    }
+
+    #[test]
+    fn molar_mass_works() {
+        let water = molar_mass("H2O").unwrap();
+        assert!(close(water.grams_per_mole, 18.015));
+        assert!(water.to_string().ends_with(" g/mol"));
+        let quantity = water.quantity();
+        assert_eq!(quantity.dimension.to_string(), "kg/mol");
+        assert!(close(quantity.value * 1000.0, 18.015));
+        assert!(close(grams("H2SO4"), 98.072));
+        assert!(close(grams("NaCl"), 58.44));
+    }
+    #[test]
+    fn molar_mass_groups() {
+        assert!(close(grams("Ca(OH)2"), 74.092));
+        assert!(close(grams("K4[Fe(CN)6]"), 368.345));
+        assert!(close(grams("CuSO4·5H2O"), 249.677));
+    }
+    #[test]
+    fn molar_mass_errors() {
+        assert!(molar_mass("Xx2").is_err());
+        assert!(molar_mass("Ca(OH2").is_err());
+        assert!(molar_mass("H2O)").is_err());
+        assert!(molar_mass("K4[Fe(CN)6)").is_err());
+        assert!(molar_mass("Ca(OH]2").is_err());
+        assert!(molar_mass("").is_err());
+        assert!(molar_mass("h2o").is_err());
+        assert!(molar_mass("C99999999999").is_err());
+        assert!(molar_mass("(OH)99999999999").is_err());
+        assert!(molar_mass("H0").is_err());
+        assert!(molar_mass("CuSO4·0H2O").is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::{Constant, Operator, Token};
+use crate::{AngleMode, Context};
+
+// strict and reserved keywords, which need `r#` to be parameter names
+const KEYWORDS: [&str; 48] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+// on every helper, so no parameter can shadow one
+const HELPER_PREFIX: &str = "__solver_";
+
+fn unsupported(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("{} has no Rust translation", what))
+}
+
+fn identifier(name: &str) -> anyhow::Result<String> {
+    match name {
+        // these can't be raw identifiers either
+        "self" | "Self" | "super" | "crate" | "_" => Err(anyhow::Error::msg(format!(
+            "{} can't be a Rust identifier",
+            name
+        ))),
+        _ if name.starts_with(HELPER_PREFIX) => Err(anyhow::Error::msg(format!(
+            "{} is kept for the generated helpers",
+            name
+        ))),
+        _ if KEYWORDS.contains(&name) => Ok(format!("r#{}", name)),
+        _ => Ok(name.to_string()),
+    }
+}
+
+fn literal(value: f64) -> String {
+    if value.is_nan() {
+        String::from("f64::NAN")
+    } else if value.is_infinite() {
+        String::from("f64::INFINITY")
+    } else {
+        format!("{:?}_f64", value)
+    }
+}
+
+struct Translator<'a> {
+    context: &'a Context,
+    // parameters in order of first use
+    parameters: Vec<String>,
+    // helper name to its source, emitted at the top of the function body
+    helpers: BTreeMap<&'static str, String>,
+}
+
+impl Translator<'_> {
+    fn aggregate(&mut self, name: &'static str, arguments: &[String]) -> String {
+        // the same NaN rule as the interpreter's max, min and avg
+        let guard = if self.context.options.ignore_nan {
+            "if values.iter().all(|value| value.is_nan()) {\n        return f64::NAN;\n    }"
+        } else {
+            "if values.iter().any(|value| value.is_nan()) {\n        return f64::NAN;\n    }"
+        };
+        let reduce = match name {
+            "max" => "numbers.fold(f64::NEG_INFINITY, f64::max)",
+            "min" => "numbers.fold(f64::INFINITY, f64::min)",
+            _ => {
+                "let (sum, count) = numbers.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));\n    sum / count as f64"
+            }
+        };
+        let source = format!(
+            "fn {}{}(values: &[f64]) -> f64 {{\n    {}\n    let numbers = values.iter().copied().filter(|value| !value.is_nan());\n    {}\n}}",
+            HELPER_PREFIX, name, guard, reduce
+        );
+        self.helpers.insert(name, source);
+        format!("{}{}(&[{}])", HELPER_PREFIX, name, arguments.join(", "))
+    }
+
+    fn approx(&mut self, arguments: [&str; 4]) -> String {
+        let source = format!("fn {}approx(a: f64, b: f64, absolute: f64, relative: f64) -> f64 {{\n    let bound = absolute.max(relative * a.abs().max(b.abs()));\n    if a == b || (a - b).abs() <= bound {{\n        1.0\n    }} else {{\n        0.0\n    }}\n}}", HELPER_PREFIX);
+        self.helpers.insert("approx", source);
+        format!("{}approx({})", HELPER_PREFIX, arguments.join(", "))
+    }
+
+    fn call(&mut self, name: &str, arguments: &[String], token: usize) -> anyhow::Result<String> {
+        if self.context.function(name).is_some() {
+            return Err(unsupported(&format!("host function {}()", name)));
+        }
+        let arity = match name {
+            "approx" => Some(3),
+            "max" | "min" | "avg" => None,
+            _ => Some(1),
+        };
+        match arity {
+            Some(expected) if arguments.len() != expected => {
+                let kind = ErrorKind::WrongArgumentCount {
+                    name: name.to_string(),
+                    expected,
+                    found: arguments.len(),
+                };
+                return Err(Error::new(kind, Some(token)).into());
+            }
+            None if arguments.is_empty() => {
+                let kind = ErrorKind::TooFewArguments {
+                    name: name.to_string(),
+                    minimum: 1,
+                    found: 0,
+                };
+                return Err(Error::new(kind, Some(token)).into());
+            }
+            _ => {}
+        }
+
+        match name {
+            "sin" | "cos" | "tan" => match self.context.options.angle_mode {
+                AngleMode::Radians => Ok(format!("{}.{}()", arguments[0], name)),
+                AngleMode::Degrees => Ok(format!("{}.to_radians().{}()", arguments[0], name)),
+            },
+            "sqrt" | "ln" | "exp" | "abs" => Ok(format!("{}.{}()", arguments[0], name)),
+            "log" => Ok(format!("{}.log10()", arguments[0])),
+            "approx" => {
+                Ok(self.approx([&arguments[0], &arguments[1], &arguments[2], &arguments[2]]))
+            }
+            "max" => Ok(self.aggregate("max", arguments)),
+            "min" => Ok(self.aggregate("min", arguments)),
+            "avg" => Ok(self.aggregate("avg", arguments)),
+            _ => Err(Error::new(ErrorKind::UnknownFunction(name.to_string()), Some(token)).into()),
+        }
+    }
+
+    // every compound form comes out parenthesized, so method calls on it bind correctly
+    fn translate(&mut self, expr: &Expr) -> anyhow::Result<String> {
+        match expr {
+            Expr::Number(number) => Ok(literal(*number)),
+            Expr::Constant(constant) => Ok(String::from(match constant {
+                Constant::Pi => "std::f64::consts::PI",
+                Constant::E => "std::f64::consts::E",
+                Constant::Tau => "std::f64::consts::TAU",
+            })),
+            Expr::Variable { name, .. } => {
+                let name = identifier(name)?;
+                if !self.parameters.contains(&name) {
+                    self.parameters.push(name.clone());
+                }
+                Ok(name)
+            }
+            Expr::Unary {
+                operator, operand, ..
+            } => {
+                let operand = self.translate(operand)?;
+                match operator {
+                    Operator::Negative => Ok(format!("(-{})", operand)),
+                    Operator::Percent => Ok(format!("({} / 100.0)", operand)),
+                    Operator::Not => {
+                        Ok(format!("(if {} == 0.0 {{ 1.0 }} else {{ 0.0 }})", operand))
+                    }
+                    Operator::Factorial => Err(unsupported("factorial")),
+                    // whether the operands are integers is only known when the function runs
+                    Operator::BitNot => Err(unsupported("a bitwise operator")),
+                    _ => Err(unsupported("a custom operator")),
+                }
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let left = self.translate(left)?;
+                let right = self.translate(right)?;
+                match operator {
+                    Operator::Plus
+                    | Operator::Minus
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Remainder => Ok(format!(
+                        "({} {} {})",
+                        left,
+                        Token::Binary(operator.clone()),
+                        right
+                    )),
+                    Operator::Power => Ok(format!("{}.powf({})", left, right)),
+                    Operator::Approx => {
+                        let absolute = literal(self.context.options.approx_absolute);
+                        let relative = literal(self.context.options.approx_relative);
+                        Ok(self.approx([&left, &right, &absolute, &relative]))
+                    }
+                    comparison if comparison.is_comparison() => Ok(format!(
+                        "(if {} {} {} {{ 1.0 }} else {{ 0.0 }})",
+                        left,
+                        Token::Binary(comparison.clone()),
+                        right
+                    )),
+                    Operator::And | Operator::Or => Ok(format!(
+                        "(if {} != 0.0 {} {} != 0.0 {{ 1.0 }} else {{ 0.0 }})",
+                        left,
+                        if *operator == Operator::And {
+                            "&&"
+                        } else {
+                            "||"
+                        },
+                        right
+                    )),
+                    Operator::Convert => Err(unsupported("unit conversion")),
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                        Err(unsupported("a bitwise operator"))
+                    }
+                    _ => Err(unsupported("a custom operator")),
+                }
+            }
+            Expr::Call {
+                name,
+                arguments,
+                token,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.translate(argument))
+                    .collect::<anyhow::Result<Vec<String>>>()?;
+                self.call(name, &arguments, *token)
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => Ok(format!(
+                "(if {} != 0.0 {{ {} }} else {{ {} }})",
+                self.translate(condition)?,
+                self.translate(then)?,
+                self.translate(otherwise)?
+            )),
+        }
+    }
+}
+
+pub fn to_rust_fn(expression: &str, fn_name: &str) -> anyhow::Result<String> {
+    to_rust_fn_with(expression, fn_name, &Context::new())
+}
+
+// a standalone `pub fn name(x: f64, ...) -> f64`, parameters in order of first use; the context
+// decides the angle mode and the tolerances. division by zero gives inf or NaN instead of an error
+pub fn to_rust_fn_with(
+    expression: &str,
+    fn_name: &str,
+    context: &Context,
+) -> anyhow::Result<String> {
+    to_rust_fn_parts(expression, fn_name, context).map(|(source, _)| source)
+}
+
+// the source along with its parameter names, for callers that generate the call too
+pub fn to_rust_fn_parts(
+    expression: &str,
+    fn_name: &str,
+    context: &Context,
+) -> anyhow::Result<(String, Vec<String>)> {
+    let fn_name = identifier(fn_name)?;
+    let expr = crate::parse_with(expression, context)?;
+    let mut translator = Translator {
+        context,
+        parameters: vec![],
+        helpers: BTreeMap::new(),
+    };
+    let body = translator.translate(&expr)?;
+
+    let parameters: Vec<String> = translator
+        .parameters
+        .iter()
+        .map(|parameter| format!("{}: f64", parameter))
+        .collect();
+    // the translation parenthesizes every compound form, which is noisy but never wrong
+    let mut source = String::from("#[allow(unused_parens, clippy::all)]\n");
+    source += &format!("pub fn {}({}) -> f64 {{\n", fn_name, parameters.join(", "));
+    for helper in translator.helpers.values() {
+        for line in helper.lines() {
+            source += &format!("    {}\n", line);
+        }
+        source += "\n";
+    }
+    source += &format!("    {}\n}}\n", body);
+    Ok((source, translator.parameters))
+}
+
+// for build scripts: one function per (name, expression), written to a file that `include!` can pull in
+pub fn write_rust_fns<P: AsRef<Path>>(
+    path: P,
+    functions: &[(&str, &str)],
+    context: &Context,
+) -> anyhow::Result<()> {
+    let mut source = String::from("// generated by solver, do not edit\n");
+    for (name, expression) in functions {
+        source += "\n";
+        source += &to_rust_fn_with(expression, name, context)?;
+    }
+    std::fs::write(path, source)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_rust_fn_works() {
+        assert_eq!(
+            to_rust_fn("2 pi r^2 + h / 2", "area").unwrap(),
+            "#[allow(unused_parens, clippy::all)]\npub fn area(r: f64, h: f64) -> f64 {\n    (((2.0_f64 * std::f64::consts::PI) * r.powf(2.0_f64)) + (h / 2.0_f64))\n}\n"
+        );
+        assert_eq!(
+            to_rust_fn("x ? 1 : y", "f").unwrap(),
+            "#[allow(unused_parens, clippy::all)]\npub fn f(x: f64, y: f64) -> f64 {\n    (if x != 0.0 { 1.0_f64 } else { y })\n}\n"
+        );
+        assert_eq!(
+            to_rust_fn("x and not y", "f").unwrap(),
+            "#[allow(unused_parens, clippy::all)]\npub fn f(x: f64, y: f64) -> f64 {\n    (if x != 0.0 && (if y == 0.0 { 1.0 } else { 0.0 }) != 0.0 { 1.0 } else { 0.0 })\n}\n"
+        );
+        assert_eq!(
+            to_rust_fn("-sin(x) >= 0", "f").unwrap(),
+            "#[allow(unused_parens, clippy::all)]\npub fn f(x: f64) -> f64 {\n    (if (-x.sin()) >= 0.0_f64 { 1.0 } else { 0.0 })\n}\n"
+        );
+
+        let source = to_rust_fn("max(a, b, 1) + avg(a, 2) ~= type", "mixed").unwrap();
+        assert!(source
+            .starts_with("#[allow(unused_parens, clippy::all)]\npub fn mixed(a: f64, b: f64, r#type: f64) -> f64 {\n    fn __solver_approx("));
+        assert!(source.contains("    fn __solver_avg(values: &[f64]) -> f64 {\n"));
+        assert!(source.contains("    fn __solver_max(values: &[f64]) -> f64 {\n"));
+        assert!(source.ends_with(
+            "    __solver_approx((__solver_max(&[a, b, 1.0_f64]) + __solver_avg(&[a, 2.0_f64])), r#type, 1e-12_f64, 1e-9_f64)\n}\n"
+        ));
+        // a parameter named like a helper no longer hides it
+        let source = to_rust_fn("max(max, min) + min", "f").unwrap();
+        assert!(source.ends_with("    (__solver_max(&[max, min]) + min)\n}\n"));
+
+        let (_, parameters) = to_rust_fn_parts("y - x * y", "f", &Context::new()).unwrap();
+        assert_eq!(parameters, vec!["y", "x"]);
+
+        let mut degrees = Context::new();
+        degrees.options.angle_mode = AngleMode::Degrees;
+        let source = to_rust_fn_with("cos(angle)", "f", &degrees).unwrap();
+        assert!(source.contains("angle.to_radians().cos()"));
+    }
+    #[test]
+    fn to_rust_fn_errors() {
+        assert!(to_rust_fn("3!", "f").is_err());
+        assert!(to_rust_fn("1 +", "f").is_err());
+        assert!(to_rust_fn("x", "self").is_err());
+        let error = to_rust_fn("__solver_max + 1", "f").unwrap_err();
+        assert!(error.to_string().contains("kept for the generated helpers"));
+        let error = to_rust_fn("foo(1)", "f").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Error>().map(|error| &error.kind),
+            Some(&ErrorKind::UnknownFunction(String::from("foo")))
+        );
+    }
+    #[test]
+    fn write_rust_fns_works() {
+        let path = std::env::temp_dir().join(format!("solver-codegen-{}.rs", std::process::id()));
+        let functions = [("double", "2x"), ("half", "x / 2")];
+        write_rust_fns(&path, &functions, &Context::new()).unwrap();
+        let source = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(source.starts_with("// generated by solver, do not edit\n\n#[allow(unused_parens, clippy::all)]\npub fn double(x: f64)"));
+        assert!(source.contains("\n#[allow(unused_parens, clippy::all)]\npub fn half(x: f64) -> f64 {\n    (x / 2.0_f64)\n}\n"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use crate::ast::Expr;
+use crate::lexer::{Operator, Token};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComplexityScore {
+    pub nodes: usize,
+    // a lone number or variable is depth 1
+    pub depth: usize,
+    // how often each operator and function appears, by symbol or name
+    pub operators: BTreeMap<String, usize>,
+    // rough relative price of one evaluation, an addition costs 1
+    pub cost: f64,
+}
+
+fn operator_cost(operator: &Operator) -> f64 {
+    match operator {
+        Operator::Plus | Operator::Minus | Operator::Negative | Operator::Percent => 1.0,
+        Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+        | Operator::Equal
+        | Operator::NotEqual => 1.0,
+        Operator::And | Operator::Or | Operator::Not => 1.0,
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor | Operator::BitNot => 1.0,
+        Operator::Multiply | Operator::Approx | Operator::Convert => 2.0,
+        Operator::Divide | Operator::Remainder => 4.0,
+        Operator::Power | Operator::Factorial | Operator::Custom(_) => 10.0,
+    }
+}
+
+const CALL_COST: f64 = 20.0;
+
+impl ComplexityScore {
+    pub fn of(expr: &Expr) -> ComplexityScore {
+        let mut score = ComplexityScore::default();
+        score.depth = score.visit(expr);
+        score
+    }
+
+    // returns the depth of the subtree
+    fn visit(&mut self, expr: &Expr) -> usize {
+        self.nodes += 1;
+        let children: Vec<&Expr> = match expr {
+            Expr::Number(_) | Expr::Constant(_) => vec![],
+            Expr::Variable { .. } => {
+                self.cost += 1.0;
+                vec![]
+            }
+            Expr::Unary {
+                operator, operand, ..
+            } => {
+                // negation and subtraction share a symbol but not a cost
+                let key = match operator {
+                    Operator::Negative => "neg".to_string(),
+                    _ => Token::Unary(operator.clone()).to_string(),
+                };
+                self.count(key, operator_cost(operator));
+                vec![operand]
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                self.count(
+                    Token::Binary(operator.clone()).to_string(),
+                    operator_cost(operator),
+                );
+                vec![left, right]
+            }
+            Expr::Call {
+                name, arguments, ..
+            } => {
+                self.count(format!("{}()", name), CALL_COST);
+                arguments.iter().collect()
+            }
+            // a branch costs what the if() call it stands for did
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.count(String::from("?:"), CALL_COST);
+                vec![condition, then, otherwise]
+            }
+        };
+        1 + children
+            .into_iter()
+            .map(|child| self.visit(child))
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn count(&mut self, key: String, cost: f64) {
+        *self.operators.entry(key).or_default() += 1;
+        self.cost += cost;
+    }
+}
+
+pub fn complexity(expression: &str) -> anyhow::Result<ComplexityScore> {
+    Ok(ComplexityScore::of(&crate::parse(expression)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complexity_works() {
+        let score = complexity("2x^2 + sqrt(x) / 4 - 1").unwrap();
+        assert_eq!(score.nodes, 12);
+        assert_eq!(score.depth, 5);
+        let operators: Vec<(&str, usize)> = score
+            .operators
+            .iter()
+            .map(|(key, count)| (key.as_str(), *count))
+            .collect();
+        assert_eq!(
+            operators,
+            vec![
+                ("*", 1),
+                ("+", 1),
+                ("-", 1),
+                ("/", 1),
+                ("^", 1),
+                ("sqrt()", 1)
+            ]
+        );
+        assert_eq!(score.cost, 40.0);
+
+        let lone = complexity("7").unwrap();
+        assert_eq!((lone.nodes, lone.depth, lone.cost), (1, 1, 0.0));
+        let negated = complexity("-(3 - 2)!").unwrap();
+        assert_eq!(negated.operators.get("neg"), Some(&1));
+        assert_eq!(negated.operators.get("-"), Some(&1));
+        assert_eq!(negated.cost, 12.0);
+        assert!(complexity("2 +").is_err());
+    }
+}
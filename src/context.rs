@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::assume::Assumption;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+// how integer arithmetic treats a division with a remainder
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IntegerDivision {
+    #[default]
+    Truncate,
+    Exact,
+}
+
+#[derive(Debug, Clone)]
+pub struct EvalOptions {
+    // reject `2.5!` instead of extending factorial through the gamma function
+    pub integer_factorial: bool,
+    // unit of the arguments to sin, cos and tan
+    pub angle_mode: AngleMode,
+    // `a ~= b` holds when they differ by at most the larger of these two bounds
+    pub approx_absolute: f64,
+    pub approx_relative: f64,
+    // let max, min and avg skip NaN arguments instead of returning NaN
+    pub ignore_nan: bool,
+    // longer number literals are rejected as invalid rather than parsed
+    pub max_literal_length: usize,
+    pub integer_division: IntegerDivision,
+}
+
+impl Default for EvalOptions {
+    fn default() -> EvalOptions {
+        EvalOptions {
+            integer_factorial: false,
+            angle_mode: AngleMode::default(),
+            approx_absolute: 1e-12,
+            approx_relative: 1e-9,
+            ignore_nan: false,
+            max_literal_length: crate::lexer::MAX_LITERAL_LENGTH,
+            integer_division: IntegerDivision::default(),
+        }
+    }
+}
+
+pub type CustomFunction = Arc<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Registered {
+    pub(crate) arity: usize,
+    pub(crate) function: CustomFunction,
+}
+
+impl fmt::Debug for Registered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fn/{}", self.arity)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+pub(crate) struct RegisteredOperator<F: ?Sized> {
+    pub(crate) precedence: u8,
+    pub(crate) associativity: Associativity,
+    pub(crate) function: Arc<F>,
+}
+
+impl<F: ?Sized> Clone for RegisteredOperator<F> {
+    fn clone(&self) -> Self {
+        RegisteredOperator {
+            precedence: self.precedence,
+            associativity: self.associativity,
+            function: self.function.clone(),
+        }
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for RegisteredOperator<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "op/{}/{:?}", self.precedence, self.associativity)
+    }
+}
+
+type UnaryFunction = dyn Fn(f64) -> f64 + Send + Sync;
+type BinaryFunction = dyn Fn(f64, f64) -> f64 + Send + Sync;
+
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    variables: HashMap<String, f64>,
+    functions: HashMap<String, Registered>,
+    unary_operators: HashMap<String, RegisteredOperator<UnaryFunction>>,
+    binary_operators: HashMap<String, RegisteredOperator<BinaryFunction>>,
+    assumptions: Vec<Assumption>,
+    pub options: EvalOptions,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.variables.get(name).copied()
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    // `x > 0`, `x != 0`, `n is integer` and the like, for simplification to rely on; evaluation ignores them
+    pub fn assume(&mut self, statement: &str) -> anyhow::Result<()> {
+        let assumption = Assumption::parse(statement)?;
+        if !self.assumptions.contains(&assumption) {
+            self.assumptions.push(assumption);
+        }
+        Ok(())
+    }
+
+    pub fn assumptions(&self) -> &[Assumption] {
+        &self.assumptions
+    }
+
+    // host functions shadow the built-in ones of the same name
+    pub fn register_fn<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        let function = Arc::new(function);
+        self.functions
+            .insert(name.to_string(), Registered { arity, function });
+    }
+
+    pub(crate) fn function(&self, name: &str) -> Option<&Registered> {
+        self.functions.get(name)
+    }
+
+    // built-in precedences are 2 for `? :`, 3 for or, 4 for and, 5 for comparisons, 6 to 8 for | xor &, 10 for + and -, 20 for * / %, 30 for negation and 40 for ^
+    pub fn register_binary_op<F>(
+        &mut self,
+        symbol: &str,
+        precedence: u8,
+        associativity: Associativity,
+        function: F,
+    ) where
+        F: Fn(f64, f64) -> f64 + Send + Sync + 'static,
+    {
+        let operator = RegisteredOperator {
+            precedence,
+            associativity,
+            function: Arc::new(function) as Arc<BinaryFunction>,
+        };
+        self.binary_operators.insert(symbol.to_string(), operator);
+    }
+
+    // a prefix operator; its operand takes every operator binding tighter than `precedence`
+    pub fn register_unary_op<F>(&mut self, symbol: &str, precedence: u8, function: F)
+    where
+        F: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        let operator = RegisteredOperator {
+            precedence,
+            associativity: Associativity::Right,
+            function: Arc::new(function) as Arc<UnaryFunction>,
+        };
+        self.unary_operators.insert(symbol.to_string(), operator);
+    }
+
+    pub(crate) fn unary_operator(
+        &self,
+        symbol: &str,
+    ) -> Option<&RegisteredOperator<UnaryFunction>> {
+        self.unary_operators.get(symbol)
+    }
+
+    pub(crate) fn binary_operator(
+        &self,
+        symbol: &str,
+    ) -> Option<&RegisteredOperator<BinaryFunction>> {
+        self.binary_operators.get(symbol)
+    }
+
+    pub(crate) fn operator_symbols(&self) -> Vec<&str> {
+        let mut symbols: Vec<&str> = self.unary_operators.keys().map(String::as_str).collect();
+        for symbol in self.binary_operators.keys() {
+            if !symbols.contains(&symbol.as_str()) {
+                symbols.push(symbol);
+            }
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_works() {
+        let mut context = Context::new();
+        assert_eq!(context.get("x"), None);
+        context.set("x", 2.5);
+        context.set("x", 4.0);
+        assert_eq!(context.get("x"), Some(4.0));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::{Constant, Operator, Token};
+use crate::rewrite::Rewriter;
+use crate::{AngleMode, Context};
+
+fn unsupported(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("{} has no derivative", what))
+}
+
+fn binary(operator: Operator, left: Expr, right: Expr, token: usize) -> Expr {
+    Expr::Binary {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+        token,
+    }
+}
+
+fn call(name: &str, arguments: Vec<Expr>, token: usize) -> Expr {
+    Expr::Call {
+        name: name.to_string(),
+        arguments,
+        token,
+    }
+}
+
+fn negate(operand: Expr, token: usize) -> Expr {
+    Expr::Unary {
+        operator: Operator::Negative,
+        operand: Box::new(operand),
+        token,
+    }
+}
+
+pub(crate) fn depends(expr: &Expr, variable: &str) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Constant(_) => false,
+        Expr::Variable { name, .. } => name == variable,
+        Expr::Unary { operand, .. } => depends(operand, variable),
+        Expr::Binary { left, right, .. } => depends(left, variable) || depends(right, variable),
+        Expr::Call { arguments, .. } => {
+            arguments.iter().any(|argument| depends(argument, variable))
+        }
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => [condition, then, otherwise]
+            .iter()
+            .any(|part| depends(part, variable)),
+    }
+}
+
+// d/du of the built-in f at u, before the chain rule multiplies in du
+fn outer(name: &str, u: &Expr, token: usize, context: &Context) -> anyhow::Result<Expr> {
+    let argument = || vec![u.clone()];
+    let derivative = match name {
+        "sin" => call("cos", argument(), token),
+        "cos" => negate(call("sin", argument(), token), token),
+        "tan" => binary(
+            Operator::Divide,
+            Expr::Number(1.0),
+            binary(
+                Operator::Power,
+                call("cos", argument(), token),
+                Expr::Number(2.0),
+                token,
+            ),
+            token,
+        ),
+        "sqrt" => binary(
+            Operator::Divide,
+            Expr::Number(1.0),
+            binary(
+                Operator::Multiply,
+                Expr::Number(2.0),
+                call("sqrt", argument(), token),
+                token,
+            ),
+            token,
+        ),
+        "ln" => binary(Operator::Divide, Expr::Number(1.0), u.clone(), token),
+        "log" => binary(
+            Operator::Divide,
+            Expr::Number(1.0),
+            binary(
+                Operator::Multiply,
+                u.clone(),
+                call("ln", vec![Expr::Number(10.0)], token),
+                token,
+            ),
+            token,
+        ),
+        "exp" => call("exp", argument(), token),
+        // the sign of u, undefined at 0 like the derivative itself
+        "abs" => binary(
+            Operator::Divide,
+            u.clone(),
+            call("abs", argument(), token),
+            token,
+        ),
+        _ => return Err(unsupported(&format!("{}()", name))),
+    };
+    // in degrees sin(u) is sin(u * pi / 180), so the chain rule brings out pi / 180
+    if matches!(name, "sin" | "cos" | "tan") && context.options.angle_mode == AngleMode::Degrees {
+        let radians = binary(
+            Operator::Divide,
+            Expr::Constant(Constant::Pi),
+            Expr::Number(180.0),
+            token,
+        );
+        return Ok(binary(Operator::Multiply, derivative, radians, token));
+    }
+    Ok(derivative)
+}
+
+fn derivative(expr: &Expr, variable: &str, context: &Context) -> anyhow::Result<Expr> {
+    // anything without the variable is a constant, whatever it is made of
+    if !depends(expr, variable) {
+        return Ok(Expr::Number(0.0));
+    }
+    let d = |expr: &Expr| derivative(expr, variable, context);
+    match expr {
+        Expr::Number(_) | Expr::Constant(_) => Ok(Expr::Number(0.0)),
+        Expr::Variable { .. } => Ok(Expr::Number(1.0)),
+        Expr::Unary {
+            operator: Operator::Negative,
+            operand,
+            token,
+        } => Ok(negate(d(operand)?, *token)),
+        Expr::Unary {
+            operator: Operator::Percent,
+            operand,
+            token,
+        } => Ok(binary(
+            Operator::Divide,
+            d(operand)?,
+            Expr::Number(100.0),
+            *token,
+        )),
+        Expr::Unary { operator, .. } => Err(unsupported(&format!(
+            "the {} operator",
+            Token::Unary(operator.clone())
+        ))),
+        Expr::Binary {
+            operator,
+            left: u,
+            right: v,
+            token,
+        } => {
+            let token = *token;
+            // a zero factor is the derivative of a constant, so the product is zero whatever the other
+            let product = |a: Expr, b: Expr| match (&a, &b) {
+                (Expr::Number(0.0), _) | (_, Expr::Number(0.0)) => Expr::Number(0.0),
+                _ => binary(Operator::Multiply, a, b, token),
+            };
+            match operator {
+                Operator::Plus | Operator::Minus => {
+                    Ok(binary(operator.clone(), d(u)?, d(v)?, token))
+                }
+                Operator::Multiply => Ok(binary(
+                    Operator::Plus,
+                    product(d(u)?, (**v).clone()),
+                    product((**u).clone(), d(v)?),
+                    token,
+                )),
+                Operator::Divide => Ok(binary(
+                    Operator::Divide,
+                    binary(
+                        Operator::Minus,
+                        product(d(u)?, (**v).clone()),
+                        product((**u).clone(), d(v)?),
+                        token,
+                    ),
+                    binary(Operator::Power, (**v).clone(), Expr::Number(2.0), token),
+                    token,
+                )),
+                // u^n is n * u^(n - 1) * du
+                Operator::Power if !depends(v, variable) => {
+                    let lowered = binary(
+                        Operator::Power,
+                        (**u).clone(),
+                        binary(Operator::Minus, (**v).clone(), Expr::Number(1.0), token),
+                        token,
+                    );
+                    Ok(product(product((**v).clone(), lowered), d(u)?))
+                }
+                // a^v is a^v * ln(a) * dv
+                Operator::Power if !depends(u, variable) => Ok(product(
+                    product(expr.clone(), call("ln", vec![(**u).clone()], token)),
+                    d(v)?,
+                )),
+                // u^v in general is exp(v * ln(u)), so its derivative is u^v * (dv * ln(u) + v * du / u)
+                Operator::Power => {
+                    let ln = call("ln", vec![(**u).clone()], token);
+                    let inner = binary(
+                        Operator::Plus,
+                        product(d(v)?, ln),
+                        binary(
+                            Operator::Divide,
+                            product((**v).clone(), d(u)?),
+                            (**u).clone(),
+                            token,
+                        ),
+                        token,
+                    );
+                    Ok(product(expr.clone(), inner))
+                }
+                _ => Err(unsupported(&format!(
+                    "the {} operator",
+                    Token::Binary(operator.clone())
+                ))),
+            }
+        }
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } => {
+            let token = *token;
+            if context.function(name).is_some() {
+                return Err(unsupported(&format!("host function {}()", name)));
+            }
+            if name == "avg" {
+                let sum = arguments
+                    .iter()
+                    .map(d)
+                    .reduce(|sum, term| Ok(binary(Operator::Plus, sum?, term?, token)))
+                    .unwrap_or(Ok(Expr::Number(0.0)))?;
+                let count = Expr::Number(arguments.len() as f64);
+                return Ok(binary(Operator::Divide, sum, count, token));
+            }
+            let [u] = &arguments[..] else {
+                if crate::lookup_function(name).is_none() {
+                    return Err(unsupported(&format!("{}()", name)));
+                }
+                let kind = ErrorKind::WrongArgumentCount {
+                    name: name.clone(),
+                    expected: 1,
+                    found: arguments.len(),
+                };
+                return Err(Error::new(kind, Some(token)).into());
+            };
+            Ok(binary(
+                Operator::Multiply,
+                outer(name, u, token, context)?,
+                d(u)?,
+                token,
+            ))
+        }
+        // only the branches change, the jump where the condition flips is ignored
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            token,
+        } => Ok(Expr::Conditional {
+            condition: condition.clone(),
+            then: Box::new(d(then)?),
+            otherwise: Box::new(d(otherwise)?),
+            token: *token,
+        }),
+    }
+}
+
+impl Expr {
+    // with respect to `variable`, every other variable held constant; radians are assumed
+    pub fn differentiate(&self, variable: &str) -> anyhow::Result<Expr> {
+        self.differentiate_with(variable, &Context::new())
+    }
+
+    // the context says which functions are the host's and whether angles are in degrees
+    pub fn differentiate_with(&self, variable: &str, context: &Context) -> anyhow::Result<Expr> {
+        let derivative = derivative(self, variable, context)?;
+        Ok(Rewriter::default().rewrite(&derivative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derivative(expression: &str) -> String {
+        let expr = crate::parse(expression).unwrap();
+        expr.differentiate("x").unwrap().to_string()
+    }
+
+    #[test]
+    fn differentiate_works() {
+        assert_eq!(derivative("x^3 + 2x - 7"), "3 * x^2 + 2");
+        assert_eq!(derivative("a * x^2 + b * x + c"), "a * (2 * x) + b");
+        assert_eq!(derivative("sin(x^2)"), "cos(x^2) * (2 * x)");
+        assert_eq!(derivative("exp(3x)"), "exp(3 * x) * 3");
+        assert_eq!(derivative("ln(x) + y^2"), "1 / x");
+        assert_eq!(derivative("2^x"), "2^x * ln(2)");
+        assert_eq!(derivative("3! * x"), "3!");
+        assert_eq!(derivative("x > 0 ? x^2 : -x"), "x > 0 ? 2 * x : -1");
+    }
+    #[test]
+    fn differentiate_matches_numeric() {
+        let mut context = Context::new();
+        let at = 1.7;
+        let step = 1e-6;
+        for expression in [
+            "x^x",
+            "ln(x) / x",
+            "sqrt(1 + x^2)",
+            "tan(x) - log(x)",
+            "2^x * exp(-x)",
+            "abs(x - 3) * cos(x)",
+            "avg(x, x^2, 1) + x * 50%",
+            "piecewise((x < 1, 0), (x < 2, x^3), x)",
+        ] {
+            let expr = crate::parse(expression).unwrap();
+            let derivative = expr.differentiate("x").unwrap();
+            let mut value = |x: f64| {
+                context.set("x", x);
+                expr.evaluate(&context).unwrap()
+            };
+            let numeric = (value(at + step) - value(at - step)) / (2.0 * step);
+            context.set("x", at);
+            let exact = derivative.evaluate(&context).unwrap();
+            assert!(
+                (exact - numeric).abs() < 1e-5,
+                "{}: {}",
+                expression,
+                derivative
+            );
+        }
+
+        let mut degrees = Context::new();
+        degrees.options.angle_mode = AngleMode::Degrees;
+        degrees.set("x", 60.0);
+        let derivative = crate::parse("sin(x)")
+            .unwrap()
+            .differentiate_with("x", &degrees)
+            .unwrap();
+        let expected = 0.5 * std::f64::consts::PI / 180.0;
+        assert!((derivative.evaluate(&degrees).unwrap() - expected).abs() < 1e-12);
+    }
+    #[test]
+    fn differentiate_errors() {
+        let differentiate = |expression| crate::parse(expression).unwrap().differentiate("x");
+        assert!(differentiate("x!").is_err());
+        assert!(differentiate("max(x, 1)").is_err());
+        assert!(differentiate("x % 2").is_err());
+        assert!(differentiate("x == 1").is_err());
+        assert!(differentiate("sin(x, 2)").is_err());
+        let mut context = Context::new();
+        context.register_fn("double", 1, |args| args[0] * 2.0);
+        let expr = crate::parse_with("double(x)", &context).unwrap();
+        assert!(expr.differentiate_with("x", &context).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::iter::Peekable;
+
+// the exact distribution is a vector over every possible total, so keep it bounded
+const MAX_OUTCOMES: i64 = 1_000_000;
+// each die convolved costs its sides times the outcomes so far, 200d1000 would take seconds
+const MAX_WORK: i64 = 100_000_000;
+
+fn overflow() -> anyhow::Error {
+    anyhow::Error::msg("dice total does not fit in 64 bits")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Dice { count: u32, sides: u32 },
+    Constant(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiceExpr {
+    // each term is added or subtracted according to its sign
+    terms: Vec<(i64, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub min: i64,
+    // probabilities[i] is the chance of rolling a total of min + i
+    pub probabilities: Vec<f64>,
+}
+
+impl Distribution {
+    pub fn max(&self) -> i64 {
+        self.min + self.probabilities.len() as i64 - 1
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.outcomes().map(|(total, chance)| total * chance).sum()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.outcomes()
+            .map(|(total, chance)| (total - mean).powi(2) * chance)
+            .sum()
+    }
+
+    fn outcomes(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.probabilities
+            .iter()
+            .enumerate()
+            .map(|(index, &chance)| ((self.min + index as i64) as f64, chance))
+    }
+}
+
+// splitmix64, small and seedable so rolls can be reproduced
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+// None when there are no digits, an error when there are too many for a u32
+fn parse_integer<Iter: Iterator<Item = char>>(
+    iterator: &mut Peekable<Iter>,
+) -> anyhow::Result<Option<u32>> {
+    let mut buffer = String::new();
+    while let Some(&char) = iterator.peek() {
+        match char {
+            '0'..='9' => buffer.push(char),
+            _ => break,
+        }
+        iterator.next();
+    }
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+    buffer
+        .parse::<u32>()
+        .map(Some)
+        .map_err(|_| anyhow::Error::msg(format!("number too large: {}", buffer)))
+}
+
+fn skip_spaces<Iter: Iterator<Item = char>>(iterator: &mut Peekable<Iter>) {
+    while iterator.peek() == Some(&' ') {
+        iterator.next();
+    }
+}
+
+pub fn parse_dice(expression: &str) -> anyhow::Result<DiceExpr> {
+    let mut terms = vec![];
+    let mut iterator = expression.chars().peekable();
+    let mut sign = 1;
+
+    loop {
+        skip_spaces(&mut iterator);
+        let number = parse_integer(&mut iterator)?;
+        let term = match (number, iterator.peek()) {
+            (count, Some('d' | 'D')) => {
+                iterator.next();
+                let sides = parse_integer(&mut iterator)?
+                    .ok_or_else(|| anyhow::Error::msg("dice need a number of sides"))?;
+                if sides == 0 {
+                    return Err(anyhow::Error::msg("dice need at least one side"));
+                }
+                Term::Dice {
+                    count: count.unwrap_or(1),
+                    sides,
+                }
+            }
+            (Some(constant), _) => Term::Constant(constant as i64),
+            (None, _) => return Err(anyhow::Error::msg("expected a die or a number")),
+        };
+        terms.push((sign, term));
+
+        skip_spaces(&mut iterator);
+        sign = match iterator.next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            None => break,
+            Some(char) => {
+                return Err(anyhow::Error::msg(format!(
+                    "unknown character in dice expression: {}",
+                    char
+                )))
+            }
+        };
+    }
+
+    Ok(DiceExpr { terms })
+}
+
+// the same seed always rolls the same total, which is what makes a roll repeatable
+pub fn roll(expression: &str, seed: u64) -> anyhow::Result<i64> {
+    parse_dice(expression)?.sample(&mut Rng::new(seed))
+}
+
+impl DiceExpr {
+    // the largest total in either direction, so no roll can overflow once this fits
+    fn reach(&self) -> anyhow::Result<i64> {
+        let mut reach: i64 = 0;
+        for (_, term) in &self.terms {
+            let largest = match term {
+                Term::Dice { count, sides } => (*count as i64)
+                    .checked_mul(*sides as i64)
+                    .ok_or_else(overflow)?,
+                Term::Constant(constant) => constant.abs(),
+            };
+            reach = reach.checked_add(largest).ok_or_else(overflow)?;
+        }
+        Ok(reach)
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> anyhow::Result<i64> {
+        self.reach()?;
+        // one draw per die, so a server rolling 4000000000d6 doesn't stall
+        let count: i64 = self
+            .terms
+            .iter()
+            .map(|(_, term)| match term {
+                Term::Dice { count, .. } => *count as i64,
+                Term::Constant(_) => 0,
+            })
+            .sum();
+        if count > MAX_WORK {
+            return Err(anyhow::Error::msg("too many dice to roll"));
+        }
+        let mut total = 0;
+        for (sign, term) in &self.terms {
+            total += sign
+                * match term {
+                    Term::Dice { count, sides } => (0..*count)
+                        .map(|_| rng.below(*sides as u64) as i64 + 1)
+                        .sum(),
+                    Term::Constant(constant) => *constant,
+                };
+        }
+        Ok(total)
+    }
+
+    pub fn distribution(&self) -> anyhow::Result<Distribution> {
+        let too_many = || anyhow::Error::msg("too many outcomes for an exact distribution");
+        let mut span: i64 = 0;
+        let mut rolled: i64 = 0;
+        for (_, term) in &self.terms {
+            if let Term::Dice { count, sides } = term {
+                let width = (*count as i64)
+                    .checked_mul(*sides as i64 - 1)
+                    .ok_or_else(too_many)?;
+                span = span.checked_add(width).ok_or_else(too_many)?;
+                rolled = (*count as i64)
+                    .checked_mul(*sides as i64)
+                    .and_then(|sides| rolled.checked_add(sides))
+                    .ok_or_else(too_many)?;
+            }
+        }
+        if span >= MAX_OUTCOMES {
+            return Err(too_many());
+        }
+        // every die is convolved into at most span + 1 outcomes
+        if rolled.saturating_mul(span + 1) > MAX_WORK {
+            return Err(anyhow::Error::msg(
+                "too many dice for an exact distribution, sample them instead",
+            ));
+        }
+
+        let mut result = Distribution {
+            min: 0,
+            probabilities: vec![1.0],
+        };
+        for (sign, term) in &self.terms {
+            match term {
+                Term::Constant(constant) => result.min += sign * constant,
+                Term::Dice { count, sides } => {
+                    for _ in 0..*count {
+                        result = convolve(&result, *sign, *sides);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+// adds (or subtracts) one fair die to the distribution
+fn convolve(distribution: &Distribution, sign: i64, sides: u32) -> Distribution {
+    let sides = sides as usize;
+    let chance = 1.0 / sides as f64;
+    let mut probabilities = vec![0.0; distribution.probabilities.len() + sides - 1];
+    for (index, probability) in distribution.probabilities.iter().enumerate() {
+        for face in 0..sides {
+            // subtracting a die mirrors its faces onto the low end
+            let offset = if sign > 0 { face } else { sides - 1 - face };
+            probabilities[index + offset] += probability * chance;
+        }
+    }
+    let min = if sign > 0 {
+        distribution.min + 1
+    } else {
+        distribution.min - sides as i64
+    };
+    Distribution { min, probabilities }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn parse_dice_works() {
+        assert!(parse_dice("3d6 + 2").is_ok());
+        assert!(parse_dice("d20").is_ok());
+        assert!(parse_dice("2d8 - 1d4 - 1").is_ok());
+        assert!(parse_dice("3d").is_err());
+        assert!(parse_dice("2d0").is_err());
+        assert!(parse_dice("3d6 *").is_err());
+        for too_large in ["2d6 + 4294967296", "4294967296d6", "d99999999999"] {
+            let error = parse_dice(too_large).unwrap_err().to_string();
+            assert!(error.starts_with("number too large"), "{}", error);
+        }
+        assert_eq!(
+            parse_dice("+").unwrap_err().to_string(),
+            "expected a die or a number"
+        );
+    }
+    #[test]
+    fn distribution_works() {
+        let distribution = parse_dice("3d6 + 2").unwrap().distribution().unwrap();
+        assert_eq!((distribution.min, distribution.max()), (5, 20));
+        assert!(close(distribution.mean(), 12.5));
+        assert!(close(distribution.variance(), 8.75));
+        assert!(close(distribution.probabilities.iter().sum(), 1.0));
+
+        let difference = parse_dice("d6 - d6").unwrap().distribution().unwrap();
+        assert_eq!((difference.min, difference.max()), (-5, 5));
+        assert!(close(difference.mean(), 0.0));
+    }
+    #[test]
+    fn sample_works() {
+        let dice = parse_dice("2d6 + 1").unwrap();
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let roll = dice.sample(&mut rng).unwrap();
+            assert!((3..=13).contains(&roll));
+        }
+        let (mut first, mut second) = (Rng::new(42), Rng::new(42));
+        assert_eq!(
+            dice.sample(&mut first).unwrap(),
+            dice.sample(&mut second).unwrap()
+        );
+        let huge = parse_dice("4294967295d4294967295 + 4294967295d4294967295").unwrap();
+        assert!(huge.sample(&mut rng).is_err());
+        assert!(parse_dice("4000000000d6")
+            .unwrap()
+            .sample(&mut rng)
+            .is_err());
+    }
+    #[test]
+    fn roll_works() {
+        assert_eq!(roll("3d8 - 2", 9).unwrap(), roll("3d8 - 2", 9).unwrap());
+        assert!((1..=22).contains(&roll("3d8 - 2", 9).unwrap()));
+        assert!(roll("2d0", 9).is_err());
+    }
+    #[test]
+    fn distribution_limits() {
+        let huge = parse_dice("4294967295d4294967295").unwrap();
+        assert!(huge.distribution().is_err());
+        // a narrow span, but far too much convolution
+        let slow = parse_dice("200d1000").unwrap();
+        assert!(slow.distribution().is_err());
+        assert!(parse_dice("100d100").unwrap().distribution().is_ok());
+    }
+}
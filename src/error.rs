@@ -0,0 +1,264 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::lexer::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    UnexpectedToken,
+    UnexpectedEnd,
+    UnclosedGroup,
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    TooFewArguments {
+        name: String,
+        minimum: usize,
+        found: usize,
+    },
+    InvalidFactorial,
+    DivisionByZero,
+    InvalidNumber,
+    UnknownCharacter(char),
+    Overflow,
+    InexactDivision,
+    NotAnInteger,
+    TooDeep,
+    UnitMismatch {
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnexpectedToken => write!(f, "unexpected token"),
+            ErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ErrorKind::UnclosedGroup => write!(f, "missing closing parenthesis"),
+            ErrorKind::UnknownVariable(name) => write!(f, "unknown variable {}", name),
+            ErrorKind::UnknownFunction(name) => write!(f, "unknown function {}", name),
+            ErrorKind::WrongArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} argument{}, found {}",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            ErrorKind::TooFewArguments {
+                name,
+                minimum,
+                found,
+            } => write!(
+                f,
+                "{} takes at least {} argument{}, found {}",
+                name,
+                minimum,
+                if *minimum == 1 { "" } else { "s" },
+                found
+            ),
+            ErrorKind::InvalidFactorial => {
+                write!(f, "factorial is not defined for this value")
+            }
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::InvalidNumber => write!(f, "invalid number literal"),
+            ErrorKind::UnknownCharacter(char) => write!(f, "unknown character {:?}", char),
+            ErrorKind::Overflow => write!(f, "integer overflow"),
+            ErrorKind::InexactDivision => write!(f, "division has a remainder"),
+            ErrorKind::NotAnInteger => write!(f, "expected an integer"),
+            ErrorKind::TooDeep => write!(f, "expression is nested too deeply"),
+            ErrorKind::UnitMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+// listed from the evaluator's own tables, so a new built-in can't be left out
+fn functions_hint() -> &'static str {
+    static HINT: OnceLock<String> = OnceLock::new();
+    HINT.get_or_init(|| {
+        let mut names: Vec<&str> = crate::builtin_functions().collect();
+        let last = names.pop().unwrap_or_default();
+        format!("built-in functions are {} and {}", names.join(", "), last)
+    })
+}
+
+impl ErrorKind {
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ErrorKind::UnclosedGroup => Some("every ( needs a matching )"),
+            ErrorKind::UnknownVariable(_) => {
+                Some("assign it first with `name = value`, or set it on the context")
+            }
+            ErrorKind::UnknownFunction(_) => Some(functions_hint()),
+            ErrorKind::InvalidFactorial => {
+                Some("factorial takes a non-negative whole number, or a fraction in floating point")
+            }
+            ErrorKind::InvalidNumber => {
+                Some("a number has at most one decimal point and a limited number of digits")
+            }
+            ErrorKind::Overflow => Some("integer results must fit in 64 bits"),
+            ErrorKind::TooDeep => Some("split it up, or nest fewer groups and operators"),
+            ErrorKind::InexactDivision => {
+                Some("set options.integer_division to Truncate to round towards zero instead")
+            }
+            ErrorKind::NotAnInteger => {
+                Some("bitwise operators take whole numbers of at most 2^53 in size")
+            }
+            ErrorKind::UnitMismatch { .. } => {
+                Some("only quantities of the same dimension can be added, subtracted or compared")
+            }
+            _ => None,
+        }
+    }
+}
+
+// the user's expression, with the columns of the offending token
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    pub source: String,
+    pub start: usize,
+    pub width: usize,
+}
+
+impl Frame {
+    pub fn new(source: &str, spans: &[Span], token: Option<usize>) -> Frame {
+        let (start, width) = match token.and_then(|token| spans.get(token)) {
+            // a token inserted by implicit multiplication has no width of its own
+            Some(span) => (span.start, span.len().max(1)),
+            // no token means the expression ended early, so point just past the end
+            None => (source.trim_end().chars().count() + 1, 1),
+        };
+        Frame {
+            source: source.to_string(),
+            start,
+            width,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Error {
+    pub kind: ErrorKind,
+    // index of the offending token, none when the expression ended early
+    pub token: Option<usize>,
+    pub frame: Option<Frame>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, token: Option<usize>) -> Error {
+        Error {
+            kind,
+            token,
+            frame: None,
+        }
+    }
+
+    pub fn with_frame(mut self, source: &str, spans: &[Span]) -> Error {
+        self.frame = Some(Frame::new(source, spans, self.token));
+        self
+    }
+}
+
+// the frame is only presentation, two errors are the same when kind and position are
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        self.kind == other.kind && self.token == other.token
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(frame) = &self.frame else {
+            return match self.token {
+                Some(token) => write!(f, "{} at token {}", self.kind, token),
+                None => write!(f, "{}", self.kind),
+            };
+        };
+
+        write!(f, "{}", self.kind)?;
+        write!(f, "\n  | {}", frame.source)?;
+        write!(
+            f,
+            "\n  | {}{}",
+            " ".repeat(frame.start),
+            "^".repeat(frame.width)
+        )?;
+        if let Some(hint) = self.kind.hint() {
+            write!(f, "\n  = hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(expression: &str) -> String {
+        crate::evaluate(expression).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn display_frame() {
+        assert_eq!(render("10 / 0"), "division by zero\n  | 10 / 0\n  |    ^");
+        assert_eq!(
+            render("2 + foo(1)"),
+            "unknown function foo\n  | 2 + foo(1)\n  |     ^^^\n  = hint: built-in functions are sin, cos, tan, sqrt, ln, log, exp, abs, approx, max, min, avg, if and piecewise"
+        );
+        assert_eq!(
+            render("(-3)!"),
+            "factorial is not defined for this value\n  | (-3)!\n  |     ^\n  = hint: factorial takes a non-negative whole number, or a fraction in floating point"
+        );
+        assert_eq!(
+            render("(1 + 2"),
+            "missing closing parenthesis\n  | (1 + 2\n  |        ^\n  = hint: every ( needs a matching )"
+        );
+        assert_eq!(
+            render("1 + 1.2.3 * 2"),
+            "invalid number literal\n  | 1 + 1.2.3 * 2\n  |     ^^^^^\n  = hint: a number has at most one decimal point and a limited number of digits"
+        );
+        assert_eq!(
+            Error::new(ErrorKind::UnexpectedToken, Some(3)).to_string(),
+            "unexpected token at token 3"
+        );
+    }
+    #[test]
+    fn hint_lists_every_function() {
+        let hint = ErrorKind::UnknownFunction(String::from("foo"))
+            .hint()
+            .unwrap();
+        for name in crate::builtin_functions() {
+            assert!(hint.contains(name), "{}", name);
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn error_round_trips() {
+        let error = crate::evaluate("1 + foo(2)").unwrap_err();
+        let error = error.downcast_ref::<Error>().unwrap();
+        let json = serde_json::to_value(error).unwrap();
+        assert_eq!(json["kind"]["UnknownFunction"], "foo");
+        assert_eq!(json["token"], 2);
+        assert_eq!(json["frame"]["source"], "1 + foo(2)");
+
+        let back: Error = serde_json::from_value(json).unwrap();
+        assert_eq!(&back, error);
+        assert_eq!(back.to_string(), error.to_string());
+    }
+}
@@ -0,0 +1,260 @@
+use std::str::FromStr;
+
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::{BigDecimal, One, ToPrimitive, Zero};
+
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::Operator;
+use crate::Context;
+
+// past these, exact results stop being worth the memory: 10000! already has 35660 digits
+pub(crate) const MAX_EXPONENT: i64 = 10_000;
+const MAX_FACTORIAL: u64 = 10_000;
+// the exponent alone can't bound a power, `(9^999)^999` keeps both under it
+pub(crate) const MAX_DIGITS: u64 = 100_000;
+
+pub(crate) fn error(kind: ErrorKind, token: usize) -> anyhow::Error {
+    Error::new(kind, Some(token)).into()
+}
+
+pub(crate) fn unsupported(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("{} is not available in exact arithmetic", what))
+}
+
+// variables and the numbers the parser makes up are f64, so they are read back through their shortest decimal form
+pub(crate) fn decimal(value: f64) -> anyhow::Result<BigDecimal> {
+    if !value.is_finite() {
+        return Err(unsupported("a non-finite number"));
+    }
+    Ok(BigDecimal::from_str(&value.to_string())?)
+}
+
+// a literal keeps every digit it was written with, unlike the f64 the lexer makes of it;
+// `crate::parse_literals` names it after its source text
+pub(crate) fn literal(name: &str) -> anyhow::Result<BigDecimal> {
+    let text = name.trim_start_matches('#');
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => return Ok(BigDecimal::from_str(text)?),
+    };
+    BigInt::parse_bytes(&text.as_bytes()[2..], radix)
+        .map(BigDecimal::from)
+        .ok_or_else(|| anyhow::Error::msg(format!("invalid literal {}", text)))
+}
+
+fn power(base: &BigDecimal, exponent: &BigDecimal, token: usize) -> anyhow::Result<BigDecimal> {
+    let exponent = match exponent.to_i64() {
+        Some(whole) if exponent.is_integer() && whole.abs() <= MAX_EXPONENT => whole,
+        _ => return Err(unsupported("a non-integer or very large exponent")),
+    };
+    // a power has about as many digits as the base times the exponent
+    if base.digits().saturating_mul(exponent.unsigned_abs()) > MAX_DIGITS {
+        return Err(unsupported("a power this large"));
+    }
+    let mut result = BigDecimal::one();
+    let mut square = base.clone();
+    let mut remaining = exponent.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result *= &square;
+        }
+        remaining >>= 1;
+        if remaining > 0 {
+            square = &square * &square;
+        }
+    }
+    if exponent >= 0 {
+        return Ok(result);
+    }
+    if result.is_zero() {
+        return Err(error(ErrorKind::DivisionByZero, token));
+    }
+    Ok(BigDecimal::one() / result)
+}
+
+// two's complement, as for i64, but without a width
+fn bits(value: &BigDecimal, token: usize) -> anyhow::Result<BigInt> {
+    if !value.is_integer() {
+        return Err(error(ErrorKind::NotAnInteger, token));
+    }
+    Ok(value.with_scale(0).into_bigint_and_exponent().0)
+}
+
+pub(crate) fn factorial(value: &BigDecimal, token: usize) -> anyhow::Result<BigDecimal> {
+    // gamma has no exact decimal values, so only whole numbers are accepted
+    if !value.is_integer() || value.sign() == Sign::Minus {
+        return Err(error(ErrorKind::InvalidFactorial, token));
+    }
+    let value = match value.to_u64() {
+        Some(value) if value <= MAX_FACTORIAL => value,
+        _ => return Err(unsupported("a factorial this large")),
+    };
+    let product = (1..=value).fold(BigInt::one(), |product, n| product * n);
+    Ok(BigDecimal::from(product))
+}
+
+pub(crate) fn evaluate_expr(expr: &Expr, context: &Context) -> anyhow::Result<BigDecimal> {
+    match expr {
+        Expr::Number(number) => decimal(*number),
+        Expr::Constant(constant) => Err(unsupported(&constant_name(constant))),
+        Expr::Variable { name, .. } if name.starts_with('#') => literal(name),
+        Expr::Variable { name, token } => match context.get(name) {
+            Some(value) => decimal(value),
+            None => Err(error(ErrorKind::UnknownVariable(name.clone()), *token)),
+        },
+        Expr::Unary {
+            operator,
+            operand,
+            token,
+        } => {
+            let operand = evaluate_expr(operand, context)?;
+            match operator {
+                Operator::Negative => Ok(-operand),
+                Operator::Percent => Ok(operand / BigDecimal::from(100)),
+                Operator::Not => Ok(BigDecimal::from(operand.is_zero() as u8)),
+                Operator::BitNot => Ok(BigDecimal::from(!bits(&operand, *token)?)),
+                Operator::Factorial => factorial(&operand, *token),
+                _ => Err(unsupported(&format!(
+                    "the {} operator",
+                    operator_name(operator)
+                ))),
+            }
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            token,
+        } => {
+            let left = evaluate_expr(left, context)?;
+            match operator {
+                Operator::And if left.is_zero() => return Ok(BigDecimal::from(0)),
+                Operator::Or if !left.is_zero() => return Ok(BigDecimal::from(1)),
+                _ => {}
+            }
+            let right = evaluate_expr(right, context)?;
+            if matches!(operator, Operator::Divide | Operator::Remainder) && right.is_zero() {
+                return Err(error(ErrorKind::DivisionByZero, *token));
+            }
+            match operator {
+                Operator::Plus => Ok(left + right),
+                Operator::Minus => Ok(left - right),
+                Operator::Multiply => Ok(left * right),
+                // rounded to bigdecimal's default of 100 significant digits when inexact
+                Operator::Divide => Ok(left / right),
+                Operator::Remainder => Ok(left % right),
+                Operator::Power => power(&left, &right, *token),
+                Operator::And | Operator::Or => Ok(BigDecimal::from(!right.is_zero() as u8)),
+                Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                    let (left, right) = (bits(&left, *token)?, bits(&right, *token)?);
+                    Ok(BigDecimal::from(
+                        operator.bitwise(left, right).unwrap_or_default(),
+                    ))
+                }
+                comparison if comparison.is_comparison() => {
+                    let holds = comparison.compare(&left, &right).unwrap_or_default();
+                    Ok(BigDecimal::from(holds as u8))
+                }
+                _ => Err(unsupported(&format!(
+                    "the {} operator",
+                    operator_name(operator)
+                ))),
+            }
+        }
+        Expr::Call { name, .. } => Err(unsupported(&format!("{}()", name))),
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            if evaluate_expr(condition, context)?.is_zero() {
+                evaluate_expr(otherwise, context)
+            } else {
+                evaluate_expr(then, context)
+            }
+        }
+    }
+}
+
+fn constant_name(constant: &crate::lexer::Constant) -> String {
+    crate::lexer::Token::Constant(constant.clone()).to_string()
+}
+
+fn operator_name(operator: &Operator) -> String {
+    crate::lexer::Token::Binary(operator.clone()).to_string()
+}
+
+pub fn evaluate_bigdecimal(expression: &str) -> anyhow::Result<BigDecimal> {
+    evaluate_bigdecimal_with(expression, &Context::new())
+}
+
+// exact decimal arithmetic for + - * % and integer powers and factorials; functions and irrational constants are rejected
+pub fn evaluate_bigdecimal_with(expression: &str, context: &Context) -> anyhow::Result<BigDecimal> {
+    let (expr, spans) = crate::parse_literals(expression, context)?;
+    evaluate_expr(&expr, context)
+        .map(|value| value.normalized())
+        .map_err(|error| crate::framed(error, expression, &spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exact(expression: &str) -> String {
+        evaluate_bigdecimal(expression).unwrap().to_string()
+    }
+
+    #[test]
+    fn evaluate_bigdecimal_works() {
+        assert_eq!(exact("0.1 + 0.2"), "0.3");
+        assert_eq!(exact("12345678901234567891 + 1"), "12345678901234567892");
+        assert_eq!(
+            exact("0.1000000000000000000001 * 10"),
+            "1.000000000000000000001"
+        );
+        assert_eq!(exact("0xFFFFFFFFFFFFFFFFF + 1"), "295147905179352825856");
+        assert_eq!(exact(".5 + 5. + 1e3 + 2(3)"), "1011.5");
+        assert_eq!(exact("25!"), "15511210043330985984000000");
+        assert_eq!(
+            exact("50!"),
+            "30414093201713378043612608166064768844377641568960512000000000000"
+        );
+        assert_eq!(exact("2^100"), "1267650600228229401496703205376");
+        assert_eq!(exact("2^-3"), "0.125");
+        assert_eq!(exact("(1 - 0.9) * 3"), "0.3");
+        assert_eq!(exact("7.5 % 2"), "1.5");
+        assert_eq!(exact("0.1 + 0.2 == 0.3"), "1");
+        assert_eq!(exact("0.1 + 0.2 != 0.3 or not 0.3"), "0");
+        assert_eq!(exact("(2^70 | 1) xor ~0"), "-1180591620717411303426");
+        assert_eq!(exact("0.1 + 0.2 == 0.3 ? 1 / 4 : 1 / 0"), "0.25");
+        assert!(exact("1 / 3").starts_with("0.3333333333"));
+
+        let mut context = Context::new();
+        context.set("price", 19.99);
+        let total = evaluate_bigdecimal_with("price * 3", &context).unwrap();
+        assert_eq!(total.to_string(), "59.97");
+    }
+    #[test]
+    fn evaluate_bigdecimal_errors() {
+        assert!(evaluate_bigdecimal("sqrt(2)").is_err());
+        assert!(evaluate_bigdecimal("2pi").is_err());
+        assert!(evaluate_bigdecimal("2^0.5").is_err());
+        assert!(evaluate_bigdecimal("2.5!").is_err());
+        assert!(evaluate_bigdecimal("(9^999)^999").is_err());
+        assert!(evaluate_bigdecimal("(9^9999)^9999").is_err());
+        assert_eq!(exact("(2^100)^100").len(), 3011);
+        let error = evaluate_bigdecimal("1 / (2 - 2)").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Error>().map(|error| &error.kind),
+            Some(&ErrorKind::DivisionByZero)
+        );
+        assert_eq!(
+            error.to_string().lines().take(3).collect::<Vec<_>>(),
+            ["division by zero", "  | 1 / (2 - 2)", "  |   ^"]
+        );
+    }
+}
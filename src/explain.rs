@@ -0,0 +1,151 @@
+use crate::ast::Expr;
+use crate::derivative::depends;
+use crate::Context;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub before: f64,
+    pub after: f64,
+    // first-order share of the change per changed variable, largest first
+    pub contributions: Vec<(String, f64)>,
+    // what the linear terms do not account for
+    pub residual: f64,
+}
+
+// host functions are opaque to differentiation, so only they need a numeric slope
+fn calls_host(expr: &Expr, context: &Context) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Constant(_) | Expr::Variable { .. } => false,
+        Expr::Unary { operand, .. } => calls_host(operand, context),
+        Expr::Binary { left, right, .. } => calls_host(left, context) || calls_host(right, context),
+        Expr::Call {
+            name, arguments, ..
+        } => {
+            context.function(name).is_some()
+                || arguments
+                    .iter()
+                    .any(|argument| calls_host(argument, context))
+        }
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => [condition, then, otherwise]
+            .iter()
+            .any(|part| calls_host(part, context)),
+    }
+}
+
+// central difference with a step scaled to the variable
+fn numeric_derivative(
+    expression: &str,
+    context: &Context,
+    name: &str,
+    at: f64,
+) -> anyhow::Result<f64> {
+    let step = 1e-6 * at.abs().max(1.0);
+    let mut shifted = context.clone();
+    shifted.set(name, at + step);
+    let above = crate::evaluate_with(expression, &shifted)?;
+    shifted.set(name, at - step);
+    let below = crate::evaluate_with(expression, &shifted)?;
+    Ok((above - below) / (2.0 * step))
+}
+
+pub fn explain_difference(
+    expression: &str,
+    before: &Context,
+    after: &Context,
+) -> anyhow::Result<Difference> {
+    let expr = crate::parse_with(expression, before)?;
+    let before_value = crate::evaluate_with(expression, before)?;
+    let after_value = crate::evaluate_with(expression, after)?;
+
+    let mut contributions = vec![];
+    for (name, old) in before.variables() {
+        let Some(new) = after.get(name) else {
+            continue;
+        };
+        if new == old || !depends(&expr, name) {
+            continue;
+        }
+        let slope = match expr.differentiate_with(name, before) {
+            Ok(derivative) => derivative.evaluate(before)?,
+            Err(_) if calls_host(&expr, before) => {
+                numeric_derivative(expression, before, name, old)?
+            }
+            Err(error) => return Err(error),
+        };
+        contributions.push((name.to_string(), slope * (new - old)));
+    }
+    contributions.sort_by(|(_, a), (_, b)| b.abs().total_cmp(&a.abs()));
+
+    let explained: f64 = contributions.iter().map(|(_, change)| change).sum();
+    Ok(Difference {
+        before: before_value,
+        after: after_value,
+        residual: after_value - before_value - explained,
+        contributions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn explain_difference_works() {
+        let mut before = Context::new();
+        before.set("price", 10.0);
+        before.set("units", 100.0);
+        before.set("fee", 5.0);
+        let mut after = before.clone();
+        after.set("price", 11.0);
+        after.set("units", 95.0);
+
+        let difference = explain_difference("price * units - fee", &before, &after).unwrap();
+        assert_eq!(difference.before, 995.0);
+        assert_eq!(difference.after, 1040.0);
+
+        let names: Vec<&str> = difference
+            .contributions
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["price", "units"]);
+        assert!(close(difference.contributions[0].1, 100.0));
+        assert!(close(difference.contributions[1].1, -50.0));
+        // the cross term of a product is second order
+        assert!(close(difference.residual, -5.0));
+    }
+    #[test]
+    fn explain_difference_slopes() {
+        let mut before = Context::new();
+        before.set("x", 2.0);
+        before.set("unused", 1.0);
+        let mut after = before.clone();
+        after.set("x", 3.0);
+        after.set("unused", 2.0);
+
+        // the slope is the exact derivative, and names the expression never reads are left out
+        let difference = explain_difference("x^3", &before, &after).unwrap();
+        assert_eq!(difference.contributions, vec![(String::from("x"), 12.0)]);
+        assert_eq!(difference.residual, 7.0);
+
+        before.register_fn("cube", 1, |args| args[0].powi(3));
+        after.register_fn("cube", 1, |args| args[0].powi(3));
+        let difference = explain_difference("cube(x)", &before, &after).unwrap();
+        assert_eq!(difference.contributions.len(), 1);
+        assert!(close(difference.contributions[0].1, 12.0));
+    }
+    #[test]
+    fn explain_difference_errors() {
+        let context = Context::new();
+        assert!(explain_difference("x + 1", &context, &context).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+// the C library is built on request, so other builds don't produce a second libsolver:
+// `cargo rustc --lib --release --features ffi --crate-type cdylib`, with include/solver.h
+
+// strings handed out by this module are owned by the caller and go back through solver_string_free
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // an interior nul would truncate the message anyway, so cut it there
+    let message = message.split('\0').next().unwrap_or_default().to_string();
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+// unwinding into C is undefined, so a panic becomes `on_panic` and the last error says what happened
+fn guarded<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload: Box<dyn Any + Send>| {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(|reason| reason.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        set_last_error(format!("internal error: {}", reason));
+        on_panic
+    })
+}
+
+/// Evaluates a nul-terminated expression, writing the value to `result`.
+/// Returns 0 on success, -1 on failure and -2 if the library panicked, with
+/// the reason available from `solver_last_error_message` on the same thread.
+///
+/// # Safety
+///
+/// `expression` must be a valid nul-terminated string and `result` must point
+/// to writable memory for one `double`.
+#[no_mangle]
+pub unsafe extern "C" fn solver_evaluate(expression: *const c_char, result: *mut f64) -> c_int {
+    guarded(-2, || evaluate(expression, result))
+}
+
+unsafe fn evaluate(expression: *const c_char, result: *mut f64) -> c_int {
+    if expression.is_null() || result.is_null() {
+        set_last_error(String::from("null argument"));
+        return -1;
+    }
+    let expression = match CStr::from_ptr(expression).to_str() {
+        Ok(expression) => expression,
+        Err(_) => {
+            set_last_error(String::from("expression is not valid utf-8"));
+            return -1;
+        }
+    };
+
+    match crate::evaluate(expression) {
+        Ok(value) => {
+            *result = value;
+            LAST_ERROR.with(|last| *last.borrow_mut() = None);
+            0
+        }
+        Err(error) => {
+            set_last_error(error.to_string());
+            -1
+        }
+    }
+}
+
+/// Returns a copy of the last error message on this thread, or null when the
+/// last evaluation succeeded. The caller owns the string and must release it
+/// with `solver_string_free`.
+#[no_mangle]
+pub extern "C" fn solver_last_error_message() -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        LAST_ERROR.with(|last| match &*last.borrow() {
+            Some(message) => message.clone().into_raw(),
+            None => ptr::null_mut(),
+        })
+    })
+}
+
+/// Releases a string returned by this library. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `string` must be null or a pointer returned by `solver_last_error_message`
+/// that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn solver_string_free(string: *mut c_char) {
+    guarded((), || {
+        if !string.is_null() {
+            drop(CString::from_raw(string));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> Option<String> {
+        let message = solver_last_error_message();
+        if message.is_null() {
+            return None;
+        }
+        let copy = unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { solver_string_free(message) };
+        Some(copy)
+    }
+
+    #[test]
+    fn ffi_works() {
+        let mut value = 0.0;
+        let expression = CString::new("2^10 + 1").unwrap();
+        assert_eq!(
+            unsafe { solver_evaluate(expression.as_ptr(), &mut value) },
+            0
+        );
+        assert_eq!(value, 1025.0);
+        assert_eq!(last_error(), None);
+
+        let expression = CString::new("1 / 0").unwrap();
+        assert_eq!(
+            unsafe { solver_evaluate(expression.as_ptr(), &mut value) },
+            -1
+        );
+        assert!(last_error().unwrap().starts_with("division by zero"));
+        // reading the message does not clear it
+        assert!(last_error().is_some());
+
+        assert_eq!(unsafe { solver_evaluate(ptr::null(), &mut value) }, -1);
+        assert_eq!(last_error().as_deref(), Some("null argument"));
+        unsafe { solver_string_free(ptr::null_mut()) };
+    }
+    #[test]
+    fn panics_become_errors() {
+        assert_eq!(guarded(-2, || -> c_int { panic!("boom") }), -2);
+        assert_eq!(last_error().as_deref(), Some("internal error: boom"));
+        let formatted = guarded(ptr::null_mut(), || -> *mut c_char {
+            panic!("{} went wrong", 1)
+        });
+        assert!(formatted.is_null());
+        assert_eq!(
+            last_error().as_deref(),
+            Some("internal error: 1 went wrong")
+        );
+        assert_eq!(guarded(0, || 5), 5);
+    }
+}
@@ -0,0 +1,140 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyFit {
+    // lowest power first, so coefficients[i] multiplies x^i
+    pub coefficients: Vec<f64>,
+    pub r2: f64,
+}
+
+impl PolyFit {
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |result, coefficient| result * x + coefficient)
+    }
+
+    pub fn expression(&self) -> String {
+        let mut terms = vec![];
+        for (power, coefficient) in self.coefficients.iter().enumerate() {
+            terms.push(match power {
+                0 => format!("{}", coefficient),
+                1 => format!("{}*x", coefficient),
+                _ => format!("{}*x^{}", coefficient, power),
+            });
+        }
+        terms.join(" + ")
+    }
+}
+
+// gaussian elimination with partial pivoting, `matrix` is row-major and augmented with the result column
+fn solve_linear(mut matrix: Vec<Vec<f64>>) -> anyhow::Result<Vec<f64>> {
+    let size = matrix.len();
+
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+            .unwrap_or(column);
+        if matrix[pivot][column].abs() < 1e-12 {
+            return Err(anyhow::Error::msg("points do not determine a unique fit"));
+        }
+        matrix.swap(column, pivot);
+
+        let (upper, lower) = matrix.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for row in lower {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let known: f64 = (row + 1..size)
+            .map(|index| matrix[row][index] * solution[index])
+            .sum();
+        solution[row] = (matrix[row][size] - known) / matrix[row][row];
+    }
+    Ok(solution)
+}
+
+pub fn fit_poly(xs: &[f64], ys: &[f64], degree: usize) -> anyhow::Result<PolyFit> {
+    if xs.len() != ys.len() {
+        return Err(anyhow::Error::msg("xs and ys must be the same length"));
+    }
+    if xs.len() <= degree {
+        return Err(anyhow::Error::msg(
+            "not enough points for the requested degree",
+        ));
+    }
+
+    // normal equations: sum(x^(i+j)) * c = sum(y * x^i)
+    let size = degree + 1;
+    let mut matrix = vec![vec![0.0; size + 1]; size];
+    for (&x, &y) in xs.iter().zip(ys) {
+        for (power, row) in matrix.iter_mut().enumerate() {
+            for (column, value) in row.iter_mut().take(size).enumerate() {
+                *value += x.powi((power + column) as i32);
+            }
+            row[size] += y * x.powi(power as i32);
+        }
+    }
+    let coefficients = solve_linear(matrix)?;
+
+    let mut fit = PolyFit {
+        coefficients,
+        r2: 0.0,
+    };
+    let mean = ys.iter().sum::<f64>() / ys.len() as f64;
+    let total: f64 = ys.iter().map(|y| (y - mean).powi(2)).sum();
+    let residual: f64 = xs
+        .iter()
+        .zip(ys)
+        .map(|(&x, y)| (y - fit.evaluate(x)).powi(2))
+        .sum();
+    // a constant dataset is fit perfectly by its mean
+    fit.r2 = if total == 0.0 {
+        1.0
+    } else {
+        1.0 - residual / total
+    };
+
+    Ok(fit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn fit_line() {
+        let fit = fit_poly(&[0.0, 1.0, 2.0, 3.0], &[1.0, 3.0, 5.0, 7.0], 1).unwrap();
+        assert!(close(fit.coefficients[0], 1.0));
+        assert!(close(fit.coefficients[1], 2.0));
+        assert!(close(fit.r2, 1.0));
+    }
+    #[test]
+    fn fit_quadratic() {
+        let xs = [-2.0, -1.0, 0.0, 1.0, 2.0, 3.0];
+        let ys: Vec<f64> = xs.iter().map(|x| 3.0 * x * x - x + 0.5).collect();
+        let fit = fit_poly(&xs, &ys, 2).unwrap();
+        assert!(close(fit.evaluate(4.0), 44.5));
+        assert_eq!(fit.coefficients.len(), 3);
+    }
+    #[test]
+    fn fit_noisy() {
+        let fit = fit_poly(&[0.0, 1.0, 2.0, 3.0], &[0.0, 1.2, 1.8, 3.1], 1).unwrap();
+        assert!(fit.r2 > 0.95 && fit.r2 < 1.0);
+    }
+    #[test]
+    fn fit_errors() {
+        assert!(fit_poly(&[1.0, 2.0], &[1.0], 1).is_err());
+        assert!(fit_poly(&[1.0, 2.0], &[1.0, 2.0], 2).is_err());
+        assert!(fit_poly(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0], 1).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+use crate::ast::Expr;
+use crate::context::IntegerDivision;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::Operator;
+use crate::Context;
+
+// variables are f64, which only holds every integer up to 2^53 exactly
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+fn error(kind: ErrorKind, token: usize) -> anyhow::Error {
+    Error::new(kind, Some(token)).into()
+}
+
+fn unsupported(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("{} is not available in integer arithmetic", what))
+}
+
+// numbers the parser makes up have no token of their own
+fn integer(value: f64, token: Option<usize>) -> anyhow::Result<i64> {
+    if value.fract() != 0.0 || value.abs() > MAX_EXACT {
+        return Err(Error::new(ErrorKind::NotAnInteger, token).into());
+    }
+    Ok(value as i64)
+}
+
+// a literal is read from its source text, so every digit of it counts, `2.0` and `1e3` included
+fn literal(name: &str, token: usize) -> anyhow::Result<i64> {
+    let text = name.trim_start_matches('#');
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => 10,
+    };
+    if radix != 10 {
+        return i64::from_str_radix(&text[2..], radix)
+            .map_err(|_| error(ErrorKind::Overflow, token));
+    }
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, "0"),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    let digits = digits.trim_start_matches('0');
+    // the power of ten the digits are scaled by
+    let scale = exponent
+        .parse::<i64>()
+        .map_err(|_| error(ErrorKind::Overflow, token))?
+        .saturating_sub(fraction.len() as i64);
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    let (digits, scale) = match usize::try_from(-scale) {
+        Ok(dropped) if dropped >= digits.len() => {
+            return Err(error(ErrorKind::NotAnInteger, token))
+        }
+        Ok(dropped) => {
+            let (kept, dropped) = digits.split_at(digits.len() - dropped);
+            if !dropped.trim_end_matches('0').is_empty() {
+                return Err(error(ErrorKind::NotAnInteger, token));
+            }
+            (kept, 0)
+        }
+        Err(_) => (digits, scale),
+    };
+    let overflow = || error(ErrorKind::Overflow, token);
+    let value = digits.parse::<i64>().map_err(|_| overflow())?;
+    let scale = u32::try_from(scale).map_err(|_| overflow())?;
+    10i64
+        .checked_pow(scale)
+        .and_then(|power| value.checked_mul(power))
+        .ok_or_else(overflow)
+}
+
+fn divide(left: i64, right: i64, token: usize, context: &Context) -> anyhow::Result<i64> {
+    if right == 0 {
+        return Err(error(ErrorKind::DivisionByZero, token));
+    }
+    let quotient = left
+        .checked_div(right)
+        .ok_or_else(|| error(ErrorKind::Overflow, token))?;
+    if context.options.integer_division == IntegerDivision::Exact && left % right != 0 {
+        return Err(error(ErrorKind::InexactDivision, token));
+    }
+    Ok(quotient)
+}
+
+fn power(base: i64, exponent: i64, token: usize, context: &Context) -> anyhow::Result<i64> {
+    if exponent >= 0 {
+        let exponent = u32::try_from(exponent).map_err(|_| error(ErrorKind::Overflow, token))?;
+        return match base {
+            // these never overflow, however large the exponent
+            0 | 1 => Ok(if exponent == 0 { 1 } else { base }),
+            -1 => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+            _ => base
+                .checked_pow(exponent)
+                .ok_or_else(|| error(ErrorKind::Overflow, token)),
+        };
+    }
+    // a negative exponent is 1 / base^-exponent
+    match base {
+        0 => Err(error(ErrorKind::DivisionByZero, token)),
+        1 => Ok(1),
+        -1 => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+        _ if context.options.integer_division == IntegerDivision::Exact => {
+            Err(error(ErrorKind::InexactDivision, token))
+        }
+        _ => Ok(0),
+    }
+}
+
+fn factorial(value: i64, token: usize) -> anyhow::Result<i64> {
+    if value < 0 {
+        return Err(error(ErrorKind::InvalidFactorial, token));
+    }
+    (2..=value).try_fold(1i64, |product, n| {
+        product
+            .checked_mul(n)
+            .ok_or_else(|| error(ErrorKind::Overflow, token))
+    })
+}
+
+fn evaluate_call(
+    name: &str,
+    arguments: &[Expr],
+    token: usize,
+    context: &Context,
+) -> anyhow::Result<i64> {
+    if !matches!(name, "abs" | "max" | "min") || context.function(name).is_some() {
+        return Err(unsupported(&format!("{}()", name)));
+    }
+    let arguments = arguments
+        .iter()
+        .map(|argument| evaluate_expr(argument, context))
+        .collect::<anyhow::Result<Vec<i64>>>()?;
+    if name == "abs" && arguments.len() != 1 {
+        let kind = ErrorKind::WrongArgumentCount {
+            name: name.to_string(),
+            expected: 1,
+            found: arguments.len(),
+        };
+        return Err(error(kind, token));
+    }
+    let result = match name {
+        "abs" => arguments[0].checked_abs(),
+        "max" => arguments.iter().copied().max(),
+        _ => arguments.iter().copied().min(),
+    };
+    result.ok_or_else(|| match arguments.len() {
+        0 => error(
+            ErrorKind::TooFewArguments {
+                name: name.to_string(),
+                minimum: 1,
+                found: 0,
+            },
+            token,
+        ),
+        _ => error(ErrorKind::Overflow, token),
+    })
+}
+
+fn evaluate_expr(expr: &Expr, context: &Context) -> anyhow::Result<i64> {
+    match expr {
+        Expr::Number(number) => integer(*number, None),
+        Expr::Constant(_) => Err(unsupported("an irrational constant")),
+        Expr::Variable { name, token } if name.starts_with('#') => literal(name, *token),
+        Expr::Variable { name, token } => match context.get(name) {
+            Some(value) => integer(value, Some(*token)),
+            None => Err(error(ErrorKind::UnknownVariable(name.clone()), *token)),
+        },
+        Expr::Unary {
+            operator,
+            operand,
+            token,
+        } => {
+            let operand = evaluate_expr(operand, context)?;
+            match operator {
+                Operator::Negative => operand
+                    .checked_neg()
+                    .ok_or_else(|| error(ErrorKind::Overflow, *token)),
+                Operator::Factorial => factorial(operand, *token),
+                Operator::Percent => Err(unsupported("a percentage")),
+                Operator::Not => Ok((operand == 0) as i64),
+                Operator::BitNot => Ok(!operand),
+                _ => Err(unsupported("a custom operator")),
+            }
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            token,
+        } => {
+            let left = evaluate_expr(left, context)?;
+            match operator {
+                Operator::And if left == 0 => return Ok(0),
+                Operator::Or if left != 0 => return Ok(1),
+                _ => {}
+            }
+            let right = evaluate_expr(right, context)?;
+            let overflow = || error(ErrorKind::Overflow, *token);
+            match operator {
+                Operator::And | Operator::Or => Ok((right != 0) as i64),
+                Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                    Ok(operator.bitwise(left, right).unwrap_or_default())
+                }
+                Operator::Plus => left.checked_add(right).ok_or_else(overflow),
+                Operator::Minus => left.checked_sub(right).ok_or_else(overflow),
+                Operator::Multiply => left.checked_mul(right).ok_or_else(overflow),
+                Operator::Divide => divide(left, right, *token, context),
+                // the remainder is exact either way, it is only undefined for zero
+                Operator::Remainder if right == 0 => Err(error(ErrorKind::DivisionByZero, *token)),
+                Operator::Remainder => left.checked_rem(right).ok_or_else(overflow),
+                Operator::Power => power(left, right, *token, context),
+                Operator::Approx => Err(unsupported("the ~= operator")),
+                comparison => match comparison.compare(left, right) {
+                    Some(holds) => Ok(holds as i64),
+                    None => Err(unsupported("a custom operator")),
+                },
+            }
+        }
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } => evaluate_call(name, arguments, *token, context),
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            let branch = if evaluate_expr(condition, context)? != 0 {
+                then
+            } else {
+                otherwise
+            };
+            evaluate_expr(branch, context)
+        }
+    }
+}
+
+pub fn evaluate_i64(expression: &str) -> anyhow::Result<i64> {
+    evaluate_i64_with(expression, &Context::new())
+}
+
+// checked 64-bit integer arithmetic; division follows options.integer_division
+pub fn evaluate_i64_with(expression: &str, context: &Context) -> anyhow::Result<i64> {
+    let (expr, spans) = crate::parse_literals(expression, context)?;
+    evaluate_expr(&expr, context).map_err(|error| crate::framed(error, expression, &spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(result: anyhow::Result<i64>) -> Option<ErrorKind> {
+        let error = result.unwrap_err();
+        error
+            .downcast_ref::<Error>()
+            .map(|error| error.kind.clone())
+    }
+
+    #[test]
+    fn evaluate_i64_works() {
+        assert_eq!(evaluate_i64("7 / 2").unwrap(), 3);
+        assert_eq!(evaluate_i64("-7 / 2").unwrap(), -3);
+        assert_eq!(evaluate_i64("-7 % 3").unwrap(), -1);
+        assert_eq!(evaluate_i64("2^62 + (2^62 - 1)").unwrap(), i64::MAX);
+        assert_eq!(evaluate_i64("20!").unwrap(), 2_432_902_008_176_640_000);
+        assert_eq!(evaluate_i64("2^-1 + (-1)^-3").unwrap(), -1);
+        assert_eq!(evaluate_i64("max(3, abs(-9), 4) - min(5, 2)").unwrap(), 7);
+        assert_eq!(evaluate_i64("2^53 + 1 > 2^53").unwrap(), 1);
+        assert_eq!(evaluate_i64("0 and 1 / 0 or not 2 > 3").unwrap(), 1);
+        assert_eq!(evaluate_i64("2^62 > 0 ? 2^62 : 1 / 0").unwrap(), 1 << 62);
+        assert_eq!(evaluate_i64("~0 & 0xff xor 2^62").unwrap(), (1 << 62) + 255);
+
+        let mut context = Context::new();
+        context.set("n", 12.0);
+        assert_eq!(evaluate_i64_with("n / 4", &context).unwrap(), 3);
+    }
+    #[test]
+    fn evaluate_i64_literals() {
+        // literals are read from their digits, not through an f64
+        assert_eq!(
+            evaluate_i64("9007199254740993 + 0").unwrap(),
+            9_007_199_254_740_993
+        );
+        assert_eq!(evaluate_i64("9223372036854775807").unwrap(), i64::MAX);
+        assert_eq!(evaluate_i64("0x7fffffffffffffff").unwrap(), i64::MAX);
+        assert_eq!(evaluate_i64("2.0 * 1e3 + 1.5e1 + 00.0").unwrap(), 2015);
+        assert_eq!(
+            kind_of(evaluate_i64("9223372036854775808")),
+            Some(ErrorKind::Overflow)
+        );
+        assert_eq!(kind_of(evaluate_i64("1e19")), Some(ErrorKind::Overflow));
+        assert_eq!(
+            kind_of(evaluate_i64("0x1ffffffffffffffff")),
+            Some(ErrorKind::Overflow)
+        );
+        assert_eq!(
+            kind_of(evaluate_i64("1.5e0")),
+            Some(ErrorKind::NotAnInteger)
+        );
+        assert_eq!(kind_of(evaluate_i64("1e-1")), Some(ErrorKind::NotAnInteger));
+    }
+    #[test]
+    fn evaluate_i64_errors() {
+        assert_eq!(kind_of(evaluate_i64("2^63")), Some(ErrorKind::Overflow));
+        assert_eq!(kind_of(evaluate_i64("21!")), Some(ErrorKind::Overflow));
+        assert_eq!(
+            kind_of(evaluate_i64("(-2^62 - 2^62) / -1")),
+            Some(ErrorKind::Overflow)
+        );
+        assert_eq!(
+            kind_of(evaluate_i64("1 % 0")),
+            Some(ErrorKind::DivisionByZero)
+        );
+        assert_eq!(
+            kind_of(evaluate_i64("0^-1")),
+            Some(ErrorKind::DivisionByZero)
+        );
+        assert_eq!(
+            kind_of(evaluate_i64("1.5 + 1")),
+            Some(ErrorKind::NotAnInteger)
+        );
+        assert_eq!(kind_of(evaluate_i64("2pi")), None);
+        assert_eq!(kind_of(evaluate_i64("sqrt(4)")), None);
+
+        let mut context = Context::new();
+        context.options.integer_division = IntegerDivision::Exact;
+        assert_eq!(evaluate_i64_with("12 / 4", &context).unwrap(), 3);
+        assert_eq!(
+            kind_of(evaluate_i64_with("7 / 2", &context)),
+            Some(ErrorKind::InexactDivision)
+        );
+        assert_eq!(
+            kind_of(evaluate_i64_with("2^-1", &context)),
+            Some(ErrorKind::InexactDivision)
+        );
+        // evaluation errors point into the expression like parse errors do
+        assert_eq!(
+            evaluate_i64("2^62 * 2").unwrap_err().to_string(),
+            "integer overflow\n  | 2^62 * 2\n  |      ^\n  = hint: integer results must fit in 64 bits"
+        );
+    }
+}
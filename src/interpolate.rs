@@ -0,0 +1,100 @@
+fn check_table(x: f64, xs: &[f64], ys: &[f64]) -> anyhow::Result<usize> {
+    if xs.len() != ys.len() {
+        return Err(anyhow::Error::msg("xs and ys must be the same length"));
+    }
+    if xs.len() < 2 {
+        return Err(anyhow::Error::msg(
+            "interpolation needs at least two points",
+        ));
+    }
+    if xs.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(anyhow::Error::msg("xs must be strictly increasing"));
+    }
+    // extrapolating silently is how table lookups give wrong answers
+    if x < xs[0] || x > xs[xs.len() - 1] || x.is_nan() {
+        return Err(anyhow::Error::msg("x is outside the range of the table"));
+    }
+
+    // index of the interval [xs[i], xs[i + 1]] containing x
+    let upper = xs.partition_point(|&value| value <= x);
+    Ok(upper.clamp(1, xs.len() - 1) - 1)
+}
+
+pub fn interp(x: f64, xs: &[f64], ys: &[f64]) -> anyhow::Result<f64> {
+    let i = check_table(x, xs, ys)?;
+    let t = (x - xs[i]) / (xs[i + 1] - xs[i]);
+    Ok(ys[i] + t * (ys[i + 1] - ys[i]))
+}
+
+// second derivatives of a natural cubic spline, solved with the thomas algorithm
+fn spline_moments(xs: &[f64], ys: &[f64]) -> Vec<f64> {
+    let n = xs.len();
+    let mut moments = vec![0.0; n];
+    if n < 3 {
+        return moments;
+    }
+
+    let h: Vec<f64> = xs.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let mut diagonal = vec![0.0; n];
+    let mut rhs = vec![0.0; n];
+    for i in 1..n - 1 {
+        diagonal[i] = 2.0 * (h[i - 1] + h[i]);
+        rhs[i] = 6.0 * ((ys[i + 1] - ys[i]) / h[i] - (ys[i] - ys[i - 1]) / h[i - 1]);
+    }
+    for i in 2..n - 1 {
+        let factor = h[i - 1] / diagonal[i - 1];
+        diagonal[i] -= factor * h[i - 1];
+        rhs[i] -= factor * rhs[i - 1];
+    }
+    for i in (1..n - 1).rev() {
+        moments[i] = (rhs[i] - h[i] * moments[i + 1]) / diagonal[i];
+    }
+    moments
+}
+
+pub fn spline(x: f64, xs: &[f64], ys: &[f64]) -> anyhow::Result<f64> {
+    let i = check_table(x, xs, ys)?;
+    let moments = spline_moments(xs, ys);
+
+    let h = xs[i + 1] - xs[i];
+    let (a, b) = ((xs[i + 1] - x) / h, (x - xs[i]) / h);
+    Ok(a * ys[i]
+        + b * ys[i + 1]
+        + ((a.powi(3) - a) * moments[i] + (b.powi(3) - b) * moments[i + 1]) * h * h / 6.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn interp_works() {
+        let (xs, ys) = ([0.0, 10.0, 20.0], [0.0, 100.0, 50.0]);
+        assert!(close(interp(5.0, &xs, &ys).unwrap(), 50.0));
+        assert!(close(interp(15.0, &xs, &ys).unwrap(), 75.0));
+        assert!(close(interp(20.0, &xs, &ys).unwrap(), 50.0));
+        assert!(close(interp(10.0, &xs, &ys).unwrap(), 100.0));
+    }
+    #[test]
+    fn spline_works() {
+        let xs = [0.0, 1.0, 2.0, 3.0];
+        let ys = [0.0, 1.0, 8.0, 27.0];
+        for (x, y) in xs.iter().zip(ys) {
+            assert!(close(spline(*x, &xs, &ys).unwrap(), y));
+        }
+        // a straight line is reproduced exactly
+        let line = [1.0, 3.0, 5.0, 7.0];
+        assert!(close(spline(1.5, &xs, &line).unwrap(), 4.0));
+    }
+    #[test]
+    fn table_errors() {
+        assert!(interp(30.0, &[0.0, 10.0], &[0.0, 1.0]).is_err());
+        assert!(interp(1.0, &[0.0, 0.0], &[0.0, 1.0]).is_err());
+        assert!(spline(1.0, &[0.0, 2.0], &[0.0]).is_err());
+        assert!(spline(1.0, &[0.0], &[0.0]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Context;
+
+// points checked for monotonicity before bisecting
+const SAMPLES: usize = 64;
+const ITERATIONS: usize = 200;
+
+pub fn invert(
+    expression: &str,
+    variable: &str,
+    y: f64,
+    interval: (f64, f64),
+) -> anyhow::Result<f64> {
+    invert_with(expression, variable, y, interval, &Context::new())
+}
+
+// solves expression(variable) = y for variable in the interval by bisection
+pub fn invert_with(
+    expression: &str,
+    variable: &str,
+    y: f64,
+    (low, high): (f64, f64),
+    context: &Context,
+) -> anyhow::Result<f64> {
+    // the samples are spread over high - low, which must itself be a finite width
+    if !(high - low).is_finite() {
+        return Err(anyhow::Error::msg(
+            "interval bounds must be finite, and no further apart than f64::MAX",
+        ));
+    }
+    if low >= high {
+        return Err(anyhow::Error::msg("interval must have low < high"));
+    }
+    let expr = crate::parse_with(expression, context)?;
+    let mut context = context.clone();
+    let mut at = |x: f64| -> anyhow::Result<f64> {
+        context.set(variable, x);
+        let value = expr.evaluate(&context)?;
+        if value.is_nan() {
+            return Err(anyhow::Error::msg(format!(
+                "expression is undefined at {} = {}",
+                variable, x
+            )));
+        }
+        Ok(value)
+    };
+
+    let values = (0..=SAMPLES)
+        .map(|index| at(low + (high - low) * index as f64 / SAMPLES as f64))
+        .collect::<anyhow::Result<Vec<f64>>>()?;
+    let rising = values.windows(2).all(|pair| pair[0] < pair[1]);
+    let falling = values.windows(2).all(|pair| pair[0] > pair[1]);
+    if !rising && !falling {
+        return Err(anyhow::Error::msg(
+            "expression is not strictly monotonic on the interval",
+        ));
+    }
+
+    let (first, last) = (values[0], values[SAMPLES]);
+    if y < first.min(last) || y > first.max(last) {
+        return Err(anyhow::Error::msg(format!(
+            "{} is outside the range {} to {} of the expression on the interval",
+            y,
+            first.min(last),
+            first.max(last)
+        )));
+    }
+
+    let (mut low, mut high) = (low, high);
+    for _ in 0..ITERATIONS {
+        let middle = low + (high - low) / 2.0;
+        if middle <= low || middle >= high {
+            break;
+        }
+        // keep the half whose ends straddle y
+        if (at(middle)? < y) == rising {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low + (high - low) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn invert_works() {
+        assert!(close(
+            invert("x^2", "x", 2.0, (0.0, 10.0)).unwrap(),
+            2f64.sqrt()
+        ));
+        assert!(close(invert("1 / x", "x", 4.0, (0.1, 1.0)).unwrap(), 0.25));
+        assert!(close(invert("exp(t)", "t", 1.0, (-1.0, 1.0)).unwrap(), 0.0));
+
+        let mut context = Context::new();
+        context.set("rate", 0.05);
+        let years = invert_with("(1 + rate)^n", "n", 2.0, (0.0, 100.0), &context).unwrap();
+        assert!(close(years, 2f64.ln() / 1.05f64.ln()));
+    }
+    #[test]
+    fn invert_errors() {
+        let error = invert("x^2", "x", 1.0, (-2.0, 2.0)).unwrap_err();
+        assert!(error.to_string().contains("not strictly monotonic"));
+        let error = invert("x^2", "x", 200.0, (0.0, 10.0)).unwrap_err();
+        assert!(error.to_string().contains("outside the range"));
+        assert!(invert("sqrt(x)", "x", 1.0, (-1.0, 4.0)).is_err());
+        assert!(invert("x + y", "x", 1.0, (0.0, 4.0)).is_err());
+        assert!(invert("x", "x", 1.0, (4.0, 0.0)).is_err());
+        for interval in [
+            (f64::NEG_INFINITY, f64::INFINITY),
+            (0.0, f64::INFINITY),
+            (f64::NAN, 1.0),
+            (-f64::MAX, f64::MAX),
+        ] {
+            let error = invert("x", "x", 5.0, interval).unwrap_err();
+            assert!(error.to_string().contains("must be finite"), "{}", error);
+        }
+    }
+}
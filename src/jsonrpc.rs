@@ -0,0 +1,351 @@
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use crate::dice;
+use crate::error::Error;
+use crate::parser::Parser;
+use crate::Context;
+
+// json-rpc 2.0 reserved codes, plus one server error for failed evaluations
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EVALUATION_ERROR: i64 = -32000;
+
+struct Failure {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl Failure {
+    fn new(code: i64, message: &str) -> Failure {
+        Failure {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+}
+
+fn expression_param(params: &Map<String, Value>) -> Result<&str, Failure> {
+    params
+        .get("expression")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Failure::new(INVALID_PARAMS, "params.expression must be a string"))
+}
+
+fn evaluation_failure(error: anyhow::Error) -> Failure {
+    let mut failure = Failure::new(EVALUATION_ERROR, &error.to_string());
+    if let Some(error) = error.downcast_ref::<Error>() {
+        // the code frame is for terminals, clients get the parts separately
+        failure.message = error.kind.to_string();
+        failure.data = Some(json!({
+            "kind": format!("{:?}", error.kind),
+            "token": error.token,
+        }));
+    }
+    failure
+}
+
+fn evaluate(params: &Map<String, Value>) -> Result<Value, Failure> {
+    let expression = expression_param(params)?;
+
+    let mut context = Context::new();
+    if let Some(variables) = params.get("variables") {
+        let variables = variables
+            .as_object()
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, "params.variables must be an object"))?;
+        for (name, value) in variables {
+            let value = value.as_f64().ok_or_else(|| {
+                Failure::new(INVALID_PARAMS, "params.variables values must be numbers")
+            })?;
+            context.set(name, value);
+        }
+    }
+
+    let value = crate::evaluate_value_with(expression, &context).map_err(evaluation_failure)?;
+    Ok(json!({ "value": value_json(value) }))
+}
+
+// json has no infinities or NaN, so those are spelled out the way rust prints them
+fn value_json(value: crate::Value) -> Value {
+    match value {
+        crate::Value::Bool(boolean) => json!(boolean),
+        crate::Value::Number(number) if number.is_finite() => json!(number),
+        crate::Value::Number(number) => json!(number.to_string()),
+    }
+}
+
+// the tokens after implied multiplication, and the expression they parse to
+fn parse(params: &Map<String, Value>) -> Result<Value, Failure> {
+    let expression = expression_param(params)?;
+    let context = Context::new();
+    let (tokens, spans) =
+        crate::tokenize(expression, &context, false).map_err(evaluation_failure)?;
+    let expr = Parser::new(&tokens, &context)
+        .parse()
+        .map_err(|error| evaluation_failure(crate::framed(error, expression, &spans)))?;
+    let tokens: Vec<String> = tokens.iter().map(|token| token.to_string()).collect();
+    Ok(json!({ "tokens": tokens, "expression": expr.to_string() }))
+}
+
+// rolls dice with the request's seed, or a fresh one, and reports the seed so the roll can be repeated
+fn roll(params: &Map<String, Value>) -> Result<Value, Failure> {
+    let expression = expression_param(params)?;
+    let seed = match params.get("seed") {
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+        Some(seed) => seed.as_u64().ok_or_else(|| {
+            Failure::new(INVALID_PARAMS, "params.seed must be a non-negative integer")
+        })?,
+    };
+    let value = dice::roll(expression, seed).map_err(evaluation_failure)?;
+    Ok(json!({ "value": value, "seed": seed }))
+}
+
+// `style` is "minify", the default, or "pretty" for the canonical spacing
+fn format(params: &Map<String, Value>) -> Result<Value, Failure> {
+    let expression = expression_param(params)?;
+    let formatted = match params.get("style").map(Value::as_str) {
+        None | Some(Some("minify")) => crate::minify::minify(expression),
+        Some(Some("pretty")) => crate::parse(expression).map(|expr| expr.to_string()),
+        Some(_) => {
+            return Err(Failure::new(
+                INVALID_PARAMS,
+                "params.style must be \"minify\" or \"pretty\"",
+            ))
+        }
+    };
+    Ok(json!({ "expression": formatted.map_err(evaluation_failure)? }))
+}
+
+// built-in names starting with `prefix`, functions before constants
+fn complete(params: &Map<String, Value>) -> Result<Value, Failure> {
+    let prefix = match params.get("prefix") {
+        None => "",
+        Some(prefix) => prefix
+            .as_str()
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, "params.prefix must be a string"))?,
+    };
+    let names: Vec<&str> = crate::builtin_functions()
+        .chain(["pi", "e", "tau"])
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    Ok(json!({ "completions": names }))
+}
+
+fn dispatch(request: &Map<String, Value>) -> Result<Value, Failure> {
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(Failure::new(
+            INVALID_REQUEST,
+            "request.jsonrpc must be \"2.0\"",
+        ));
+    }
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Failure::new(INVALID_REQUEST, "request.method must be a string"))?;
+    let empty = Map::new();
+    let params = match request.get("params") {
+        None => &empty,
+        Some(params) => params
+            .as_object()
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, "params must be an object"))?,
+    };
+
+    match method {
+        "evaluate" => evaluate(params),
+        "parse" => parse(params),
+        "format" => format(params),
+        "roll" => roll(params),
+        "complete" => complete(params),
+        _ => Err(Failure::new(METHOD_NOT_FOUND, "method not found")),
+    }
+}
+
+// none for a notification, a request without an id, which never gets an answer
+pub fn respond(line: &str) -> Option<Value> {
+    let (id, outcome) = match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(request)) => {
+            // nothing is kept between requests, so a notification has nothing to do
+            let id = request.get("id")?.clone();
+            (id, dispatch(&request))
+        }
+        Ok(_) => (
+            Value::Null,
+            Err(Failure::new(INVALID_REQUEST, "request must be an object")),
+        ),
+        Err(_) => (Value::Null, Err(Failure::new(PARSE_ERROR, "parse error"))),
+    };
+
+    Some(match outcome {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(failure) => {
+            let mut error = json!({ "code": failure.code, "message": failure.message });
+            if let Some(data) = failure.data {
+                error["data"] = data;
+            }
+            json!({ "jsonrpc": "2.0", "id": id, "error": error })
+        }
+    })
+}
+
+// one request per line in, one response per line out, until the input closes
+pub fn serve<R: BufRead, W: Write>(input: R, output: &mut W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line) {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(line: &str) -> Value {
+        respond(line).unwrap()
+    }
+
+    #[test]
+    fn evaluate_request() {
+        let response = answer(
+            r#"{"jsonrpc":"2.0","id":1,"method":"evaluate","params":{"expression":"2x + 1","variables":{"x":3}}}"#,
+        );
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["value"], 7.0);
+
+        let evaluate = |expression: &str| {
+            answer(&format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"evaluate","params":{{"expression":"{}"}}}}"#,
+                expression
+            ))["result"]["value"]
+                .clone()
+        };
+        // predicates are json booleans, and values json can't hold are spelled out
+        assert_eq!(evaluate("2 > 1 and 3 > 4"), json!(false));
+        assert_eq!(evaluate("1 ~= 1"), json!(true));
+        assert_eq!(evaluate("1e400"), json!("inf"));
+        assert_eq!(evaluate("-1e400"), json!("-inf"));
+        assert_eq!(evaluate("sqrt(-1)"), json!("NaN"));
+    }
+    #[test]
+    fn parse_request() {
+        let response =
+            answer(r#"{"jsonrpc":"2.0","id":"a","method":"parse","params":{"expression":"2pi"}}"#);
+        assert_eq!(response["result"]["tokens"], json!(["2", "*", "pi"]));
+        assert_eq!(response["result"]["expression"], "2 * pi");
+
+        let response =
+            answer(r#"{"jsonrpc":"2.0","id":"b","method":"parse","params":{"expression":"1 +"}}"#);
+        assert_eq!(response["error"]["code"], EVALUATION_ERROR);
+        assert_eq!(response["error"]["data"]["kind"], "UnexpectedEnd");
+    }
+    #[test]
+    fn format_request() {
+        let format = |params: &str| {
+            answer(&format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"format","params":{}}}"#,
+                params
+            ))
+        };
+        let response = format(r#"{"expression":"((1 + 2)) * x"}"#);
+        assert_eq!(response["result"]["expression"], "(1+2)*x");
+        let response = format(r#"{"expression":"((1+2))*x","style":"pretty"}"#);
+        assert_eq!(response["result"]["expression"], "(1 + 2) * x");
+        let response = format(r#"{"expression":"1 +","style":"pretty"}"#);
+        assert_eq!(response["error"]["code"], EVALUATION_ERROR);
+        let response = format(r#"{"expression":"1","style":"wide"}"#);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+    #[test]
+    fn complete_request() {
+        let response =
+            answer(r#"{"jsonrpc":"2.0","id":1,"method":"complete","params":{"prefix":"a"}}"#);
+        assert_eq!(
+            response["result"]["completions"],
+            json!(["abs", "approx", "avg"])
+        );
+        let response = answer(r#"{"jsonrpc":"2.0","id":2,"method":"complete"}"#);
+        let completions = response["result"]["completions"].as_array().unwrap();
+        assert!(completions.contains(&json!("sin")));
+        assert!(completions.contains(&json!("piecewise")));
+        assert!(completions.contains(&json!("tau")));
+    }
+    #[test]
+    fn roll_request() {
+        let roll = |params: &str| {
+            answer(&format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"roll","params":{}}}"#,
+                params
+            ))
+        };
+        let first = roll(r#"{"expression":"10d6 + 2","seed":42}"#);
+        assert_eq!(first["result"]["seed"], 42);
+        assert_eq!(first, roll(r#"{"expression":"10d6 + 2","seed":42}"#));
+        let value = first["result"]["value"].as_i64().unwrap();
+        assert!((12..=62).contains(&value));
+
+        let fresh = roll(r#"{"expression":"d20"}"#);
+        let seed = fresh["result"]["seed"].as_u64().unwrap();
+        let again = roll(&format!(r#"{{"expression":"d20","seed":{}}}"#, seed));
+        assert_eq!(fresh["result"]["value"], again["result"]["value"]);
+
+        let response = roll(r#"{"expression":"2d6","seed":-1}"#);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = roll(r#"{"expression":"2d0"}"#);
+        assert_eq!(response["error"]["code"], EVALUATION_ERROR);
+    }
+    #[test]
+    fn error_responses() {
+        let response =
+            answer(r#"{"jsonrpc":"2.0","id":2,"method":"evaluate","params":{"expression":"1/0"}}"#);
+        assert_eq!(response["error"]["code"], EVALUATION_ERROR);
+        assert_eq!(response["error"]["data"]["kind"], "DivisionByZero");
+        assert_eq!(response["error"]["data"]["token"], 1);
+
+        assert_eq!(answer("{")["error"]["code"], PARSE_ERROR);
+        assert_eq!(answer("[1]")["error"]["code"], INVALID_REQUEST);
+        let response = answer(r#"{"jsonrpc":"2.0","id":3,"method":"plot"}"#);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = answer(r#"{"jsonrpc":"2.0","id":4,"method":"evaluate","params":{}}"#);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = answer(r#"{"id":5,"method":"evaluate","params":{"expression":"1"}}"#);
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        let response = answer(r#"{"jsonrpc":"1.0","id":6,"method":"evaluate"}"#);
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+    #[test]
+    fn notifications_get_no_response() {
+        assert_eq!(
+            respond(r#"{"jsonrpc":"2.0","method":"evaluate","params":{"expression":"1"}}"#),
+            None
+        );
+        assert_eq!(respond(r#"{"jsonrpc":"2.0","method":"plot"}"#), None);
+        let response = answer(r#"{"jsonrpc":"2.0","id":null,"method":"evaluate"}"#);
+        assert_eq!(response["id"], Value::Null);
+    }
+    #[test]
+    fn serve_works() {
+        let input = concat!(
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"evaluate\",\"params\":{\"expression\":\"1+1\"}}\n\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"evaluate\",\"params\":{\"expression\":\"2+2\"}}\n",
+        );
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("\"value\":2.0"));
+    }
+}
@@ -1,6 +1,11 @@
+use std::fmt;
 use std::iter::Peekable;
+use std::ops::{BitAnd, BitOr, BitXor, Range};
 
-#[derive(Debug, PartialEq)]
+use crate::error::{Error, ErrorKind};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operator {
     Plus,
     Minus,
@@ -9,26 +14,222 @@ pub enum Operator {
     Remainder,
     Power,
     Factorial,
+    Percent,
     Negative,
+    Approx,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    // `5 km in mi`, only meaningful to unit-aware evaluation
+    Convert,
+    // registered on a context, see `Context::register_binary_op`
+    Custom(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Group {
     Parenthesis,
     Bracket,
     Brace,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constant {
+    Pi,
+    E,
+    Tau,
+}
+
+impl Operator {
+    // the ones that compare two values and hold or not; `~=` compares with a tolerance and is not one
+    pub fn compare<T: PartialOrd>(&self, left: T, right: T) -> Option<bool> {
+        match self {
+            Operator::Less => Some(left < right),
+            Operator::LessEqual => Some(left <= right),
+            Operator::Greater => Some(left > right),
+            Operator::GreaterEqual => Some(left >= right),
+            Operator::Equal => Some(left == right),
+            Operator::NotEqual => Some(left != right),
+            _ => None,
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        self.compare(0, 0).is_some()
+    }
+
+    pub fn bitwise<T>(&self, left: T, right: T) -> Option<T>
+    where
+        T: BitAnd<Output = T> + BitOr<Output = T> + BitXor<Output = T>,
+    {
+        match self {
+            Operator::BitAnd => Some(left & right),
+            Operator::BitOr => Some(left | right),
+            Operator::BitXor => Some(left ^ right),
+            _ => None,
+        }
+    }
+}
+
+impl Constant {
+    pub fn value(&self) -> f64 {
+        match self {
+            Constant::Pi => std::f64::consts::PI,
+            Constant::E => std::f64::consts::E,
+            Constant::Tau => std::f64::consts::TAU,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
     Number(f64),
+    Identifier(String),
+    Constant(Constant),
     Unary(Operator),
     Binary(Operator),
     Left(Group),
     Right(Group),
+    Assign,
+    Comma,
+    // `condition ? then : otherwise`
+    Question,
+    Colon,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Token::Number(number) => return write!(f, "{}", number),
+            Token::Identifier(name) => return write!(f, "{}", name),
+            Token::Constant(Constant::Pi) => "pi",
+            Token::Constant(Constant::E) => "e",
+            Token::Constant(Constant::Tau) => "tau",
+            Token::Unary(operator) | Token::Binary(operator) => match operator {
+                Operator::Plus => "+",
+                Operator::Minus | Operator::Negative => "-",
+                Operator::Multiply => "*",
+                Operator::Divide => "/",
+                Operator::Remainder | Operator::Percent => "%",
+                Operator::Power => "^",
+                Operator::Factorial => "!",
+                Operator::Approx => "~=",
+                Operator::Less => "<",
+                Operator::LessEqual => "<=",
+                Operator::Greater => ">",
+                Operator::GreaterEqual => ">=",
+                Operator::Equal => "==",
+                Operator::NotEqual => "!=",
+                Operator::And => "and",
+                Operator::Or => "or",
+                Operator::Not => "not",
+                Operator::BitAnd => "&",
+                Operator::BitOr => "|",
+                Operator::BitXor => "xor",
+                Operator::BitNot => "~",
+                Operator::Convert => "in",
+                Operator::Custom(symbol) => return write!(f, "{}", symbol),
+            },
+            Token::Left(Group::Parenthesis) => "(",
+            Token::Left(Group::Bracket) => "[",
+            Token::Left(Group::Brace) => "{",
+            Token::Right(Group::Parenthesis) => ")",
+            Token::Right(Group::Bracket) => "]",
+            Token::Right(Group::Brace) => "}",
+            Token::Assign => "=",
+            Token::Comma => ",",
+            Token::Question => "?",
+            Token::Colon => ":",
+        };
+        write!(f, "{}", symbol)
+    }
 }
 
-fn parse_number<Iter: Iterator<Item = char>>(char: char, iterator: &mut Peekable<Iter>) -> f64 {
+// an `e` only starts an exponent when digits follow, so `2e` is still two times e;
+// reading stops once the exponent is longer than `max_length`
+fn parse_exponent<Iter: Iterator<Item = char> + Clone>(
+    iterator: &mut Peekable<Iter>,
+    max_length: usize,
+) -> String {
+    let mut lookahead = iterator.clone();
+    let mut buffer = String::new();
+
+    match lookahead.next() {
+        Some(char @ ('e' | 'E')) => buffer.push(char),
+        _ => return buffer,
+    }
+    if let Some(&sign @ ('+' | '-')) = lookahead.peek() {
+        buffer.push(sign);
+        lookahead.next();
+    }
+    if !matches!(lookahead.peek(), Some('0'..='9')) {
+        return String::new();
+    }
+    while let Some(&digit @ '0'..='9') = lookahead.peek() {
+        buffer.push(digit);
+        lookahead.next();
+        if buffer.len() > max_length {
+            break;
+        }
+    }
+
+    *iterator = lookahead;
+    buffer
+}
+
+// `0x`, `0b` and `0o` prefixes, only when at least one digit of that base follows; also counts
+// the digits, stopping at one past `max_length`
+fn parse_radix<Iter: Iterator<Item = char> + Clone>(
+    iterator: &mut Peekable<Iter>,
+    max_length: usize,
+) -> Option<(f64, usize)> {
+    let mut lookahead = iterator.clone();
+    if lookahead.next() != Some('0') {
+        return None;
+    }
+    let radix = match lookahead.next() {
+        Some('x' | 'X') => 16,
+        Some('b' | 'B') => 2,
+        Some('o' | 'O') => 8,
+        _ => return None,
+    };
+
+    let mut value: Option<(f64, usize)> = None;
+    while let Some(digit) = lookahead.peek().and_then(|char| char.to_digit(radix)) {
+        let (total, digits) = value.unwrap_or_default();
+        value = Some((total * radix as f64 + digit as f64, digits + 1));
+        lookahead.next();
+        if digits + 1 > max_length {
+            break;
+        }
+    }
+
+    if value.is_some() {
+        *iterator = lookahead;
+    }
+    value
+}
+
+// none for malformed literals like `1.2.3` and for ones longer than `max_length`,
+// which are given up on as soon as they run past it
+fn parse_number<Iter: Iterator<Item = char> + Clone>(
+    char: char,
+    iterator: &mut Peekable<Iter>,
+    max_length: usize,
+) -> Option<f64> {
     let mut buffer = char.to_string();
     iterator.next();
 
@@ -39,29 +240,227 @@ fn parse_number<Iter: Iterator<Item = char>>(char: char, iterator: &mut Peekable
             _ => break,
         }
         iterator.next();
+        if buffer.len() > max_length {
+            return None;
+        }
     }
+    buffer += &parse_exponent(iterator, max_length.saturating_sub(buffer.len()));
 
-    buffer.parse::<f64>().unwrap_or_default()
+    if buffer.len() > max_length {
+        return None;
+    }
+    buffer.parse::<f64>().ok()
 }
 
-pub fn lex(expression: &String) -> anyhow::Result<Vec<Token>> {
+fn parse_identifier<Iter: Iterator<Item = char>>(iterator: &mut Peekable<Iter>) -> String {
+    let mut buffer = String::new();
+
+    while let Some(&char) = iterator.peek() {
+        match char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => buffer.push(char),
+            _ => break,
+        }
+        iterator.next();
+    }
+
+    buffer
+}
+
+// a minus is negation wherever an operand is expected instead of an operator
+pub(crate) fn expects_operand(previous: Option<&Token>) -> bool {
+    matches!(
+        previous,
+        None | Some(Token::Binary(_))
+            | Some(Token::Unary(
+                Operator::Negative | Operator::Not | Operator::BitNot | Operator::Custom(_)
+            ))
+            | Some(Token::Left(_))
+            | Some(Token::Assign)
+            | Some(Token::Comma)
+            | Some(Token::Question)
+            | Some(Token::Colon)
+    )
+}
+
+fn is_word(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_'
+}
+
+// `%` is a percent sign when no operand follows it, or before `of`: `50%`, `200 + 10%`, `20% of 50`;
+// `and`, `or` and `xor` count as operators there too;
+// `~` and `!` start an operand unless they are half of `~=` or `!=`, so `7 % ~1` is a remainder;
+// a `-` right against its operand is a negation, so `7 % -3` stays a remainder while `50% - 3` is a percent
+fn is_percent<Iter: Iterator<Item = char> + Clone>(iterator: &Peekable<Iter>) -> bool {
+    let mut lookahead = iterator.clone();
+    lookahead.next();
+    while lookahead.next_if(|char| char.is_whitespace()).is_some() {}
+    match lookahead.next() {
+        None
+        | Some(
+            ')' | ']' | '}' | ',' | '+' | '*' | '/' | '^' | '=' | '≈' | '<' | '>' | '&' | '|' | '?'
+            | ':',
+        ) => true,
+        Some('-') => lookahead.peek().is_none_or(|char| char.is_whitespace()),
+        Some('~' | '!') => lookahead.peek() == Some(&'='),
+        Some(first @ ('o' | 'a' | 'x')) => {
+            let word: String = std::iter::once(first)
+                .chain(std::iter::from_fn(|| {
+                    lookahead.next_if(|&char| is_word(char))
+                }))
+                .collect();
+            matches!(word.as_str(), "of" | "and" | "or" | "xor")
+        }
+        _ => false,
+    }
+}
+
+// whether a `%` with `rest` written after it reads as a percent
+pub(crate) fn percent_before(rest: &str) -> bool {
+    let text = format!("%{}", rest);
+    is_percent(&text.chars().peekable())
+}
+
+// longest registered symbol at the cursor; a word-like symbol must not run into more word characters
+fn parse_symbol<'a, Iter: Iterator<Item = char> + Clone>(
+    iterator: &mut Peekable<Iter>,
+    symbols: &[&'a str],
+) -> Option<&'a str> {
+    let mut best: Option<(&'a str, Peekable<Iter>)> = None;
+
+    for &symbol in symbols {
+        if best
+            .as_ref()
+            .is_some_and(|(found, _)| found.len() >= symbol.len())
+        {
+            continue;
+        }
+        let mut lookahead = iterator.clone();
+        if !symbol.chars().all(|char| lookahead.next() == Some(char)) {
+            continue;
+        }
+        let ends_word = symbol.chars().last().is_some_and(is_word);
+        if ends_word && lookahead.peek().is_some_and(|&char| is_word(char)) {
+            continue;
+        }
+        best = Some((symbol, lookahead));
+    }
+
+    let (symbol, lookahead) = best?;
+    *iterator = lookahead;
+    Some(symbol)
+}
+
+pub const MAX_LITERAL_LENGTH: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct LexOptions<'a> {
+    // custom operators, matched ahead of the built-in ones
+    pub symbols: &'a [&'a str],
+    // longest number literal accepted, counting digits, point and exponent
+    pub max_literal_length: usize,
+    // `in` and `to` between operands convert units, which only the unit evaluator understands
+    pub conversions: bool,
+}
+
+impl Default for LexOptions<'_> {
+    fn default() -> Self {
+        LexOptions {
+            symbols: &[],
+            max_literal_length: MAX_LITERAL_LENGTH,
+            conversions: false,
+        }
+    }
+}
+
+pub fn lex(expression: &str) -> anyhow::Result<Vec<Token>> {
+    lex_with(expression, &LexOptions::default())
+}
+
+pub fn lex_with(expression: &str, options: &LexOptions) -> anyhow::Result<Vec<Token>> {
+    Ok(lex_spans(expression, options)?.0)
+}
+
+// character columns of a token in the original expression
+pub type Span = Range<usize>;
+
+// an error at the last span, framed here since the tokens after it were never read
+fn failed(kind: ErrorKind, expression: &str, spans: &[Span]) -> anyhow::Error {
+    let error = Error::new(kind, Some(spans.len() - 1));
+    error.with_frame(expression, spans).into()
+}
+
+// like `lex_with`, plus where each token came from
+pub fn lex_spans(
+    expression: &str,
+    options: &LexOptions,
+) -> anyhow::Result<(Vec<Token>, Vec<Span>)> {
     let mut result: Vec<Token> = vec![];
+    let mut spans: Vec<Span> = vec![];
 
-    let mut iterator = expression.chars().peekable();
+    let chars: Vec<char> = expression.chars().collect();
+    let mut iterator = chars.iter().copied().peekable();
+    // the iterator knows exactly how much is left, peeked char included
+    let position = |iterator: &Peekable<_>| chars.len() - ExactSizeIterator::len(iterator);
     while let Some(&char) = iterator.peek() {
+        let start = position(&iterator);
+        let count = result.len();
+        if let Some(symbol) = parse_symbol(&mut iterator, options.symbols) {
+            // whether it is the prefix or the infix form depends on where it sits
+            let operator = Operator::Custom(symbol.to_string());
+            if expects_operand(result.last()) {
+                result.push(Token::Unary(operator));
+            } else {
+                result.push(Token::Binary(operator));
+            }
+            spans.push(start..position(&iterator));
+            continue;
+        }
         match char {
-            ' ' => {
+            char if char.is_whitespace() => {
                 iterator.next();
             }
             '0'..='9' | '.' => {
-                let number = parse_number(char, &mut iterator);
+                let max_length = options.max_literal_length;
+                let number = match parse_radix(&mut iterator, max_length) {
+                    Some((_, digits)) if digits > max_length => None,
+                    Some((number, _)) => Some(number),
+                    None => parse_number(char, &mut iterator, max_length),
+                };
+                let Some(number) = number else {
+                    spans.push(start..position(&iterator));
+                    return Err(failed(ErrorKind::InvalidNumber, expression, &spans));
+                };
                 result.push(Token::Number(number));
             }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let name = parse_identifier(&mut iterator);
+                result.push(match name.as_str() {
+                    "pi" => Token::Constant(Constant::Pi),
+                    "e" => Token::Constant(Constant::E),
+                    "tau" => Token::Constant(Constant::Tau),
+                    // only a keyword after an operand, so `in` can still name a variable
+                    "in" | "to" if options.conversions && !expects_operand(result.last()) => {
+                        Token::Binary(Operator::Convert)
+                    }
+                    "of" if result.last() == Some(&Token::Unary(Operator::Percent)) => {
+                        Token::Binary(Operator::Multiply)
+                    }
+                    "and" if !expects_operand(result.last()) => Token::Binary(Operator::And),
+                    "or" if !expects_operand(result.last()) => Token::Binary(Operator::Or),
+                    "xor" if !expects_operand(result.last()) => Token::Binary(Operator::BitXor),
+                    "not" if expects_operand(result.last()) => Token::Unary(Operator::Not),
+                    _ => Token::Identifier(name),
+                });
+            }
 
             '+' => {
                 result.push(Token::Binary(Operator::Plus));
                 iterator.next();
             }
+            '-' if expects_operand(result.last()) => {
+                result.push(Token::Unary(Operator::Negative));
+                iterator.next();
+            }
             '-' => {
                 result.push(Token::Binary(Operator::Minus));
                 iterator.next();
@@ -74,6 +473,10 @@ pub fn lex(expression: &String) -> anyhow::Result<Vec<Token>> {
                 result.push(Token::Binary(Operator::Divide));
                 iterator.next();
             }
+            '%' if is_percent(&iterator) => {
+                result.push(Token::Unary(Operator::Percent));
+                iterator.next();
+            }
             '%' => {
                 result.push(Token::Binary(Operator::Remainder));
                 iterator.next();
@@ -82,8 +485,74 @@ pub fn lex(expression: &String) -> anyhow::Result<Vec<Token>> {
                 result.push(Token::Binary(Operator::Power));
                 iterator.next();
             }
+            '!' if expects_operand(result.last()) => {
+                result.push(Token::Unary(Operator::Not));
+                iterator.next();
+            }
+            // `!=` always compares, so `3!=6` needs a space to be 3! = 6
             '!' => {
-                result.push(Token::Unary(Operator::Factorial));
+                iterator.next();
+                if iterator.next_if_eq(&'=').is_some() {
+                    result.push(Token::Binary(Operator::NotEqual));
+                } else {
+                    result.push(Token::Unary(Operator::Factorial));
+                }
+            }
+            '<' | '>' => {
+                iterator.next();
+                let equal = iterator.next_if_eq(&'=').is_some();
+                result.push(Token::Binary(match (char, equal) {
+                    ('<', false) => Operator::Less,
+                    ('<', true) => Operator::LessEqual,
+                    (_, false) => Operator::Greater,
+                    (_, true) => Operator::GreaterEqual,
+                }));
+            }
+            // doubled they are the logical operators
+            '&' | '|' => {
+                iterator.next();
+                let doubled = iterator.next_if_eq(&char).is_some();
+                result.push(Token::Binary(match (char, doubled) {
+                    ('&', false) => Operator::BitAnd,
+                    ('&', true) => Operator::And,
+                    (_, false) => Operator::BitOr,
+                    (_, true) => Operator::Or,
+                }));
+            }
+            '≈' => {
+                result.push(Token::Binary(Operator::Approx));
+                iterator.next();
+            }
+            '~' => {
+                iterator.next();
+                if iterator.next_if_eq(&'=').is_some() {
+                    result.push(Token::Binary(Operator::Approx));
+                } else if expects_operand(result.last()) {
+                    result.push(Token::Unary(Operator::BitNot));
+                } else {
+                    spans.push(start..position(&iterator));
+                    return Err(failed(ErrorKind::UnexpectedToken, expression, &spans));
+                }
+            }
+
+            '=' => {
+                iterator.next();
+                if iterator.next_if_eq(&'=').is_some() {
+                    result.push(Token::Binary(Operator::Equal));
+                } else {
+                    result.push(Token::Assign);
+                }
+            }
+            ',' => {
+                result.push(Token::Comma);
+                iterator.next();
+            }
+            '?' => {
+                result.push(Token::Question);
+                iterator.next();
+            }
+            ':' => {
+                result.push(Token::Colon);
                 iterator.next();
             }
 
@@ -97,11 +566,58 @@ pub fn lex(expression: &String) -> anyhow::Result<Vec<Token>> {
                 iterator.next();
             }
 
-            _ => return Err(anyhow::Error::msg("unknown character in expression")),
+            _ => {
+                spans.push(start..start + 1);
+                let kind = ErrorKind::UnknownCharacter(char);
+                return Err(failed(kind, expression, &spans));
+            }
+        }
+        if result.len() > count {
+            spans.push(start..position(&iterator));
+        }
+    }
+
+    Ok((result, spans))
+}
+
+// inserts the multiplication in `2pi`, `3x`, `2(1 + 1)` and `(a)(b)`; a name followed by a group stays a call
+pub fn imply_multiplication(tokens: Vec<Token>) -> Vec<Token> {
+    let spans = vec![0..0; tokens.len()];
+    imply_multiplication_spans(tokens, spans).0
+}
+
+// an inserted `*` gets an empty span where the token after it starts
+pub fn imply_multiplication_spans(tokens: Vec<Token>, spans: Vec<Span>) -> (Vec<Token>, Vec<Span>) {
+    let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut result_spans: Vec<Span> = Vec::with_capacity(spans.len());
+
+    for (token, span) in tokens.into_iter().zip(spans) {
+        let left_ends_operand = matches!(
+            result.last(),
+            Some(Token::Number(_))
+                | Some(Token::Identifier(_))
+                | Some(Token::Constant(_))
+                | Some(Token::Right(_))
+                | Some(Token::Unary(Operator::Factorial))
+        );
+        let right_starts_operand = matches!(
+            token,
+            Token::Identifier(_) | Token::Constant(_) | Token::Left(_)
+        );
+        let call = matches!(
+            (result.last(), &token),
+            (Some(Token::Identifier(_)), Token::Left(_))
+        );
+
+        if left_ends_operand && right_starts_operand && !call {
+            result.push(Token::Binary(Operator::Multiply));
+            result_spans.push(span.start..span.start);
         }
+        result.push(token);
+        result_spans.push(span);
     }
 
-    Ok(result)
+    (result, result_spans)
 }
 
 #[cfg(test)]
@@ -144,6 +660,285 @@ mod tests {
         assert!(compare_vec(&result, &equal_to));
     }
     #[test]
+    fn lex_identifier() {
+        let expression = String::from("rate * x_2");
+        let equal_to = vec![
+            Token::Identifier(String::from("rate")),
+            Token::Binary(Operator::Multiply),
+            Token::Identifier(String::from("x_2")),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_negative() {
+        let expression = String::from("-3 - (-2)");
+        let equal_to = vec![
+            Token::Unary(Operator::Negative),
+            Token::Number(3.0),
+            Token::Binary(Operator::Minus),
+            Token::Left(Group::Parenthesis),
+            Token::Unary(Operator::Negative),
+            Token::Number(2.0),
+            Token::Right(Group::Parenthesis),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_assign() {
+        let equal_to = vec![
+            Token::Identifier(String::from("x")),
+            Token::Assign,
+            Token::Unary(Operator::Negative),
+            Token::Number(2.0),
+        ];
+        let result = lex("x = -2").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_arguments() {
+        let equal_to = vec![
+            Token::Identifier(String::from("max")),
+            Token::Left(Group::Parenthesis),
+            Token::Number(1.0),
+            Token::Comma,
+            Token::Unary(Operator::Negative),
+            Token::Number(2.0),
+            Token::Right(Group::Parenthesis),
+        ];
+        let result = lex("max(1, -2)").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_custom_operators() {
+        let equal_to = vec![
+            Token::Unary(Operator::Custom(String::from("~"))),
+            Token::Number(7.0),
+            Token::Binary(Operator::Custom(String::from("//"))),
+            Token::Identifier(String::from("modulus")),
+            Token::Binary(Operator::Custom(String::from("mod"))),
+            Token::Number(2.0),
+            Token::Binary(Operator::Divide),
+            Token::Number(1.0),
+        ];
+        let options = LexOptions {
+            symbols: &["//", "mod", "~"],
+            ..LexOptions::default()
+        };
+        let result = lex_with("~7 // modulus mod 2 / 1", &options).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_approx() {
+        let equal_to = vec![
+            Token::Number(1.0),
+            Token::Binary(Operator::Approx),
+            Token::Number(2.0),
+            Token::Binary(Operator::Approx),
+            Token::Number(3.0),
+        ];
+        let result = lex("1 ~= 2 ≈ 3").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+        assert!(lex("1 ~ 2").is_err());
+    }
+    #[test]
+    fn lex_comparisons() {
+        let equal_to = vec![
+            Token::Identifier(String::from("x")),
+            Token::Binary(Operator::LessEqual),
+            Token::Number(1.0),
+            Token::Binary(Operator::NotEqual),
+            Token::Number(2.0),
+            Token::Binary(Operator::Equal),
+            Token::Number(3.0),
+            Token::Unary(Operator::Factorial),
+            Token::Binary(Operator::Greater),
+            Token::Number(4.0),
+            Token::Binary(Operator::Less),
+            Token::Unary(Operator::Negative),
+            Token::Number(5.0),
+            Token::Binary(Operator::GreaterEqual),
+            Token::Number(6.0),
+            Token::Unary(Operator::Percent),
+            Token::Binary(Operator::Greater),
+            Token::Number(1.0),
+        ];
+        let result = lex("x <= 1 != 2 == 3! > 4 < -5 >= 6% > 1").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+        let assign = lex("y = 3! = 6").unwrap();
+        assert_eq!(assign[1], Token::Assign);
+        assert_eq!(assign[4], Token::Assign);
+    }
+    #[test]
+    fn lex_logical() {
+        let equal_to = vec![
+            Token::Unary(Operator::Not),
+            Token::Identifier(String::from("x")),
+            Token::Binary(Operator::And),
+            Token::Number(3.0),
+            Token::Unary(Operator::Factorial),
+            Token::Binary(Operator::Or),
+            Token::Unary(Operator::Not),
+            Token::Identifier(String::from("y")),
+            Token::Binary(Operator::And),
+            Token::Number(5.0),
+            Token::Unary(Operator::Percent),
+            Token::Binary(Operator::Or),
+            Token::Identifier(String::from("and")),
+        ];
+        let result = lex("not x and 3! || !y && 5% or and").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_bitwise() {
+        let equal_to = vec![
+            Token::Unary(Operator::BitNot),
+            Token::Number(255.0),
+            Token::Binary(Operator::BitAnd),
+            Token::Identifier(String::from("x")),
+            Token::Binary(Operator::BitOr),
+            Token::Number(1.0),
+            Token::Binary(Operator::BitXor),
+            Token::Unary(Operator::BitNot),
+            Token::Number(2.0),
+            Token::Binary(Operator::Approx),
+            Token::Number(3.0),
+        ];
+        let result = lex("~0xff & x | 1 xor ~2 ~= 3").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+        assert!(lex("1 ~ 2").is_err());
+    }
+    #[test]
+    fn lex_conditional() {
+        let equal_to = vec![
+            Token::Identifier(String::from("x")),
+            Token::Question,
+            Token::Number(50.0),
+            Token::Unary(Operator::Percent),
+            Token::Colon,
+            Token::Unary(Operator::Negative),
+            Token::Identifier(String::from("x")),
+        ];
+        let result = lex("x ? 50%:-x").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_percent() {
+        let equal_to = vec![
+            Token::Number(20.0),
+            Token::Unary(Operator::Percent),
+            Token::Binary(Operator::Multiply),
+            Token::Number(50.0),
+            Token::Binary(Operator::Remainder),
+            Token::Left(Group::Parenthesis),
+            Token::Number(3.0),
+            Token::Unary(Operator::Percent),
+            Token::Right(Group::Parenthesis),
+            Token::Binary(Operator::Remainder),
+            Token::Identifier(String::from("off")),
+        ];
+        let result = lex("20% of 50 % (3%) % off").unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_convert() {
+        let equal_to = vec![
+            Token::Number(5.0),
+            Token::Identifier(String::from("km")),
+            Token::Binary(Operator::Convert),
+            Token::Identifier(String::from("mi")),
+            Token::Binary(Operator::Convert),
+            Token::Identifier(String::from("in")),
+        ];
+        let options = LexOptions {
+            conversions: true,
+            ..LexOptions::default()
+        };
+        let result = lex_with("5 km in mi to in", &options).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+        assert_eq!(
+            lex("2 in").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier(String::from("in"))]
+        );
+    }
+    #[test]
+    fn lex_constant() {
+        let expression = String::from("2pi + e - tau + epsilon");
+        let equal_to = vec![
+            Token::Number(2.0),
+            Token::Constant(Constant::Pi),
+            Token::Binary(Operator::Plus),
+            Token::Constant(Constant::E),
+            Token::Binary(Operator::Minus),
+            Token::Constant(Constant::Tau),
+            Token::Binary(Operator::Plus),
+            Token::Identifier(String::from("epsilon")),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn imply_multiplication_works() {
+        let tokens = lex(&String::from("2pi(x)sin(x)")).unwrap();
+        let equal_to = vec![
+            Token::Number(2.0),
+            Token::Binary(Operator::Multiply),
+            Token::Constant(Constant::Pi),
+            Token::Binary(Operator::Multiply),
+            Token::Left(Group::Parenthesis),
+            Token::Identifier(String::from("x")),
+            Token::Right(Group::Parenthesis),
+            Token::Binary(Operator::Multiply),
+            Token::Identifier(String::from("sin")),
+            Token::Left(Group::Parenthesis),
+            Token::Identifier(String::from("x")),
+            Token::Right(Group::Parenthesis),
+        ];
+        let result = imply_multiplication(tokens);
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_scientific() {
+        let expression = String::from("1e5 2.5E-3 6.02e23 .5e+1");
+        let equal_to = vec![
+            Token::Number(1e5),
+            Token::Number(2.5e-3),
+            Token::Number(6.02e23),
+            Token::Number(5.0),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+
+        let expression = String::from("2e-x");
+        let equal_to = vec![
+            Token::Number(2.0),
+            Token::Constant(Constant::E),
+            Token::Binary(Operator::Minus),
+            Token::Identifier(String::from("x")),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
+    fn lex_radix() {
+        let expression = String::from("0xFF + 0b1010 0o17 0x1f");
+        let equal_to = vec![
+            Token::Number(255.0),
+            Token::Binary(Operator::Plus),
+            Token::Number(10.0),
+            Token::Number(15.0),
+            Token::Number(31.0),
+        ];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+
+        let expression = String::from("0b2");
+        let equal_to = vec![Token::Number(0.0), Token::Identifier(String::from("b2"))];
+        let result = lex(&expression).unwrap();
+        assert!(compare_vec(&result, &equal_to));
+    }
+    #[test]
     fn lex_decimal() {
         let expression = String::from("5.3 .110 333.");
         let equal_to = vec![
@@ -154,4 +949,79 @@ mod tests {
         let result = lex(&expression).unwrap();
         assert!(compare_vec(&result, &equal_to));
     }
+    #[test]
+    fn lex_spans_works() {
+        let (tokens, spans) = lex_spans("  12 +sqrt(x)", &LexOptions::default()).unwrap();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(spans, vec![2..4, 5..6, 6..10, 10..11, 11..12, 12..13]);
+
+        let (tokens, spans) = lex_spans("2pi r", &LexOptions::default()).unwrap();
+        let (tokens, spans) = imply_multiplication_spans(tokens, spans);
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(spans, vec![0..1, 1..1, 1..3, 4..4, 4..5]);
+    }
+    #[test]
+    fn lex_long_literals() {
+        let kind_of = |error: anyhow::Error| error.downcast_ref::<Error>().unwrap().clone();
+
+        let digits = "7".repeat(MAX_LITERAL_LENGTH);
+        assert_eq!(lex(&digits).unwrap(), vec![Token::Number(f64::INFINITY)]);
+        assert_eq!(lex("1e400").unwrap(), vec![Token::Number(f64::INFINITY)]);
+        let huge = format!("1 + {}", "9".repeat(10_000));
+        assert_eq!(
+            kind_of(lex(&huge).unwrap_err()),
+            Error::new(ErrorKind::InvalidNumber, Some(2))
+        );
+        // reading stops just past the limit, and the frame covers only what was read
+        let frame = |error: anyhow::Error| error.downcast::<Error>().unwrap().frame.unwrap();
+        assert_eq!(frame(lex(&huge).unwrap_err()).width, MAX_LITERAL_LENGTH + 1);
+        let hex = format!("0x{}", "f".repeat(10_000));
+        assert_eq!(frame(lex(&hex).unwrap_err()).width, MAX_LITERAL_LENGTH + 3);
+        let exponent = format!("1e{}", "0".repeat(10_000));
+        assert_eq!(
+            frame(lex(&exponent).unwrap_err()).width,
+            MAX_LITERAL_LENGTH + 1
+        );
+
+        let options = LexOptions {
+            max_literal_length: 3,
+            ..LexOptions::default()
+        };
+        assert!(lex_with("123", &options).is_ok());
+        assert!(lex_with("1234", &options).is_err());
+        assert!(lex_with("0b1111", &options).is_err());
+        assert!(lex_with("1e10", &options).is_err());
+        assert!(lex_with("1e1", &options).is_ok());
+        assert_eq!(
+            kind_of(lex("1.2.3").unwrap_err()),
+            Error::new(ErrorKind::InvalidNumber, Some(0))
+        );
+    }
+    #[test]
+    fn lex_tilde_without_equals() {
+        let error = lex("2 ~ 3").unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error.kind, ErrorKind::UnexpectedToken);
+        assert_eq!(error.token, Some(1));
+        assert_eq!(error.frame.unwrap().width, 1);
+        assert_eq!(lex("~3").unwrap().len(), 2);
+    }
+    #[test]
+    fn lex_unknown_character() {
+        let error = lex("5 $ 3").unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error.kind, ErrorKind::UnknownCharacter('$'));
+        assert_eq!(error.token, Some(1));
+        assert_eq!(
+            error.to_string(),
+            "unknown character '$'\n  | 5 $ 3\n  |   ^"
+        );
+        // any whitespace separates tokens, not just spaces
+        assert_eq!(
+            lex("1\t+\n2").unwrap(),
+            vec![
+                Token::Number(1.0),
+                Token::Binary(Operator::Plus),
+                Token::Number(2.0)
+            ]
+        );
+    }
 }
@@ -1 +1,711 @@
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod assume;
+pub mod ast;
+pub mod chemistry;
+pub mod codegen;
+pub mod complexity;
+pub mod context;
+mod derivative;
+pub mod dice;
+pub mod error;
+#[cfg(feature = "bigdecimal")]
+pub mod exact;
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fit;
+pub mod integer;
+pub mod interpolate;
+pub mod invert;
+#[cfg(feature = "jsonrpc")]
+pub mod jsonrpc;
 pub mod lexer;
+pub mod limit;
+pub mod matrix;
+pub mod minify;
+#[cfg(feature = "natural")]
+pub mod natural;
+pub mod number_theory;
+mod parser;
+pub mod rational;
+pub mod rewrite;
+pub mod speech;
+#[cfg(feature = "bigdecimal")]
+pub mod symbolic;
+pub mod testing;
+pub mod units;
+pub mod url;
+pub mod vector;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "wgsl")]
+pub mod wgsl;
+
+use ast::Expr;
+pub use ast::Value;
+pub use context::{AngleMode, Associativity, Context, EvalOptions, IntegerDivision};
+use error::Error;
+use lexer::{Span, Token};
+use parser::Parser;
+
+type Function = fn(f64) -> f64;
+
+const FUNCTIONS: [(&str, Function); 8] = [
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("sqrt", f64::sqrt),
+    ("ln", f64::ln),
+    ("log", f64::log10),
+    ("exp", f64::exp),
+    ("abs", f64::abs),
+];
+
+type MultipleFunction = fn(&[f64], &EvalOptions) -> f64;
+
+// built-ins taking more than one argument, with their arity; none means one or more
+const MULTIPLE_FUNCTIONS: [(&str, Option<usize>, MultipleFunction); 4] = [
+    ("approx", Some(3), |args, _| {
+        approx(args[0], args[1], args[2], args[2])
+    }),
+    ("max", None, |args, options| {
+        aggregate(args, options, |values| {
+            values.fold(f64::NEG_INFINITY, f64::max)
+        })
+    }),
+    ("min", None, |args, options| {
+        aggregate(args, options, |values| values.fold(f64::INFINITY, f64::min))
+    }),
+    ("avg", None, |args, options| {
+        aggregate(args, options, |values| {
+            let (sum, count) =
+                values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+            sum / count as f64
+        })
+    }),
+];
+
+// a NaN argument makes the result NaN, unless the options say to skip it
+fn aggregate<F>(args: &[f64], options: &EvalOptions, reduce: F) -> f64
+where
+    F: Fn(&mut dyn Iterator<Item = f64>) -> f64,
+{
+    let mut values = args.iter().copied().filter(|value| !value.is_nan());
+    let nan_count = args.iter().filter(|value| value.is_nan()).count();
+    if nan_count == args.len() || (nan_count > 0 && !options.ignore_nan) {
+        return f64::NAN;
+    }
+    reduce(&mut values)
+}
+
+// every built-in function name, the one argument ones first and the ones the parser reads
+// itself, because they only evaluate the branch they choose, last
+pub(crate) fn builtin_functions() -> impl Iterator<Item = &'static str> {
+    FUNCTIONS
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(MULTIPLE_FUNCTIONS.iter().map(|(name, _, _)| *name))
+        .chain(["if", "piecewise"])
+}
+
+pub(crate) const TRIGONOMETRIC: [&str; 3] = ["sin", "cos", "tan"];
+
+pub(crate) fn lookup_function(name: &str) -> Option<Function> {
+    FUNCTIONS
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map(|(_, function)| *function)
+}
+
+pub(crate) fn lookup_multiple(name: &str) -> Option<(Option<usize>, MultipleFunction)> {
+    MULTIPLE_FUNCTIONS
+        .iter()
+        .find(|(function, _, _)| *function == name)
+        .map(|(_, arity, function)| (*arity, *function))
+}
+
+// 1 when a and b agree within an absolute bound, or within a bound relative to the larger one
+pub(crate) fn approx(a: f64, b: f64, absolute: f64, relative: f64) -> f64 {
+    let bound = absolute.max(relative * a.abs().max(b.abs()));
+    if a == b || (a - b).abs() <= bound {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// lanczos approximation (g = 7, n = 9), reflected for the left half-plane
+fn gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        return std::f64::consts::PI / ((std::f64::consts::PI * x).sin() * gamma(1.0 - x));
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (index, coefficient)| {
+            sum + coefficient / (x + index as f64 + 1.0)
+        });
+    (2.0 * std::f64::consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * series
+}
+
+pub(crate) fn factorial(value: f64, options: &EvalOptions) -> Option<f64> {
+    let integer = value.fract() == 0.0;
+    // negative integers are poles of the gamma function
+    if (integer && value < 0.0) || (!integer && options.integer_factorial) || value.is_nan() {
+        return None;
+    }
+    // 171! is already past f64::MAX
+    if value > 170.0 {
+        return Some(f64::INFINITY);
+    }
+    if integer {
+        return Some((1..=value as u64).map(|n| n as f64).product());
+    }
+    Some(gamma(value + 1.0))
+}
+
+pub fn parse(expression: &str) -> anyhow::Result<Expr> {
+    parse_with(expression, &Context::new())
+}
+
+pub(crate) fn tokenize(
+    expression: &str,
+    context: &Context,
+    conversions: bool,
+) -> anyhow::Result<(Vec<Token>, Vec<Span>)> {
+    let options = lexer::LexOptions {
+        symbols: &context.operator_symbols(),
+        max_literal_length: context.options.max_literal_length,
+        conversions,
+    };
+    let (tokens, spans) = lexer::lex_spans(expression, &options)?;
+    Ok(lexer::imply_multiplication_spans(tokens, spans))
+}
+
+// attaches the code frame to our own errors, anything else passes through
+pub(crate) fn framed(error: anyhow::Error, source: &str, spans: &[Span]) -> anyhow::Error {
+    match error.downcast::<Error>() {
+        Ok(error) => error.with_frame(source, spans).into(),
+        Err(error) => error,
+    }
+}
+
+// the context only matters for the custom operators it registers
+pub fn parse_with(expression: &str, context: &Context) -> anyhow::Result<Expr> {
+    parse_tokens(expression, context, false)
+}
+
+// `conversions` reads `in` and `to` as unit conversions, otherwise they are plain names
+pub(crate) fn parse_tokens(
+    expression: &str,
+    context: &Context,
+    conversions: bool,
+) -> anyhow::Result<Expr> {
+    let (tokens, spans) = tokenize(expression, context, conversions)?;
+    Parser::new(&tokens, context)
+        .parse()
+        .map_err(|error| framed(error, expression, &spans))
+}
+
+// the lexer never makes an identifier starting with #, so every literal is swapped for one
+// holding its source text, for evaluators that read every digit the f64 would lose;
+// the spans are kept to frame evaluation errors
+pub(crate) fn parse_literals(
+    expression: &str,
+    context: &Context,
+) -> anyhow::Result<(Expr, Vec<Span>)> {
+    let (mut tokens, spans) = tokenize(expression, context, false)?;
+    let chars: Vec<char> = expression.chars().collect();
+    for (token, span) in tokens.iter_mut().zip(&spans) {
+        if let Token::Number(_) = token {
+            let text: String = chars[span.clone()].iter().collect();
+            *token = Token::Identifier(format!("#{}", text));
+        }
+    }
+    let expr = Parser::new(&tokens, context)
+        .parse()
+        .map_err(|error| framed(error, expression, &spans))?;
+    Ok((expr, spans))
+}
+
+pub fn evaluate(expression: &str) -> anyhow::Result<f64> {
+    evaluate_with(expression, &Context::new())
+}
+
+pub fn evaluate_with(expression: &str, context: &Context) -> anyhow::Result<f64> {
+    let (tokens, spans) = tokenize(expression, context, false)?;
+    Parser::new(&tokens, context)
+        .parse()
+        .and_then(|expr| expr.evaluate(context))
+        .map_err(|error| framed(error, expression, &spans))
+}
+
+// like `evaluate`, but predicates come back as booleans
+pub fn evaluate_value(expression: &str) -> anyhow::Result<Value> {
+    evaluate_value_with(expression, &Context::new())
+}
+
+pub fn evaluate_value_with(expression: &str, context: &Context) -> anyhow::Result<Value> {
+    let (tokens, spans) = tokenize(expression, context, false)?;
+    Parser::new(&tokens, context)
+        .parse()
+        .and_then(|expr| expr.evaluate_value(context))
+        .map_err(|error| framed(error, expression, &spans))
+}
+
+// like `evaluate_with`, but `x = 3 + 4` also binds x for later expressions
+pub fn execute(expression: &str, context: &mut Context) -> anyhow::Result<f64> {
+    execute_value(expression, context).map(f64::from)
+}
+
+pub fn execute_value(expression: &str, context: &mut Context) -> anyhow::Result<Value> {
+    let (tokens, spans) = tokenize(expression, context, false)?;
+    let (target, expr) = Parser::new(&tokens, context)
+        .parse_statement()
+        .map_err(|error| framed(error, expression, &spans))?;
+
+    let result = expr
+        .evaluate_value(context)
+        .map_err(|error| framed(error, expression, &spans))?;
+    if let Some(name) = target {
+        context.set(&name, result.into());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ErrorKind;
+
+    #[test]
+    fn evaluate_works() {
+        assert_eq!(evaluate("(53+110)").unwrap(), 163.0);
+        assert_eq!(evaluate("2 + 3 * 4").unwrap(), 14.0);
+        assert_eq!(evaluate("2 ^ 3 ^ 2").unwrap(), 512.0);
+        assert_eq!(evaluate("-2 ^ 2").unwrap(), -4.0);
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(evaluate("7 % 4 * 2").unwrap(), 6.0);
+        assert_eq!(evaluate("3! + 2^-1").unwrap(), 6.5);
+        assert_eq!(evaluate("0xFF - 0b11 * 0o10 + 1.5").unwrap(), 232.5);
+    }
+    #[test]
+    fn evaluate_percent() {
+        assert_eq!(evaluate("20% * 50").unwrap(), 10.0);
+        assert_eq!(evaluate("20% of 50").unwrap(), 10.0);
+        assert_eq!(evaluate("200 + 10%").unwrap(), 220.0);
+        assert_eq!(evaluate("200 - 25%").unwrap(), 150.0);
+        assert_eq!(evaluate("200 + 10% * 3").unwrap(), 200.3);
+        assert_eq!(evaluate("50%").unwrap(), 0.5);
+        assert_eq!(evaluate("-50%^2").unwrap(), -0.25);
+        assert_eq!(evaluate("(10 % 4)%").unwrap(), 0.02);
+        assert_eq!(evaluate("7 % (-3)").unwrap(), 1.0);
+        assert_eq!(evaluate("7 % -3").unwrap(), 1.0);
+        assert_eq!(evaluate("7%-3").unwrap(), 1.0);
+        assert_eq!(evaluate("50% - 3").unwrap(), -2.5);
+        assert_eq!(evaluate("200 - 25% - 50").unwrap(), 100.0);
+        assert_eq!(evaluate("7 % ~1").unwrap(), 1.0);
+        assert_eq!(evaluate("7 % !0").unwrap(), 0.0);
+        assert_eq!(evaluate("50% ~= 0.5").unwrap(), 1.0);
+        assert_eq!(evaluate("50% != 0.5").unwrap(), 0.0);
+    }
+    fn error_of(expression: &str) -> Error {
+        let error = evaluate(expression).unwrap_err();
+        error.downcast_ref::<Error>().unwrap().clone()
+    }
+
+    #[test]
+    fn evaluate_errors() {
+        assert_eq!(error_of("").kind, ErrorKind::UnexpectedEnd);
+        assert_eq!(error_of("5 +"), Error::new(ErrorKind::UnexpectedEnd, None));
+        assert_eq!(error_of("(1 + 2").kind, ErrorKind::UnclosedGroup);
+        assert_eq!(
+            error_of("1 2"),
+            Error::new(ErrorKind::UnexpectedToken, Some(1))
+        );
+        assert_eq!(
+            error_of("* 2"),
+            Error::new(ErrorKind::UnexpectedToken, Some(0))
+        );
+        assert_eq!(
+            error_of("3 + )"),
+            Error::new(ErrorKind::UnexpectedToken, Some(2))
+        );
+        assert_eq!(
+            error_of("(-3)!"),
+            Error::new(ErrorKind::InvalidFactorial, Some(4))
+        );
+        assert_eq!(
+            error_of("10 / 0"),
+            Error::new(ErrorKind::DivisionByZero, Some(1))
+        );
+        assert_eq!(
+            error_of("1 + 5 % (2 - 2)"),
+            Error::new(ErrorKind::DivisionByZero, Some(3))
+        );
+        assert_eq!(
+            error_of("1 + y").kind,
+            ErrorKind::UnknownVariable(String::from("y"))
+        );
+    }
+    #[test]
+    fn evaluate_nesting_limit() {
+        let deep = |open: &str, close: &str, levels: usize| {
+            format!("{}1{}", open.repeat(levels), close.repeat(levels))
+        };
+        assert_eq!(evaluate(&deep("-", "", 150)).unwrap(), 1.0);
+        assert_eq!(evaluate(&deep("(", ")", 150)).unwrap(), 1.0);
+        assert_eq!(evaluate(&deep("", " + 1", 150)).unwrap(), 151.0);
+        assert_eq!(error_of(&deep("-", "", 200_000)).kind, ErrorKind::TooDeep);
+        assert_eq!(error_of(&deep("(", ")", 300)).kind, ErrorKind::TooDeep);
+        assert_eq!(error_of(&deep("abs(", ")", 300)).kind, ErrorKind::TooDeep);
+        // a long chain nests as deeply as parentheses would
+        assert_eq!(error_of(&deep("", " + 1", 300)).kind, ErrorKind::TooDeep);
+        assert_eq!(error_of(&deep("", " + 1%", 100)).kind, ErrorKind::TooDeep);
+        assert_eq!(
+            error_of(&deep("piecewise((0, 1), ", ")", 300)).kind,
+            ErrorKind::TooDeep
+        );
+    }
+    #[test]
+    fn evaluate_factorial() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9 * b.abs().max(1.0);
+        assert_eq!(evaluate("5!").unwrap(), 120.0);
+        assert!(close(evaluate("2.5!").unwrap(), 3.323_350_970_447_843));
+        assert!(close(
+            evaluate("0.5!").unwrap(),
+            std::f64::consts::PI.sqrt() / 2.0
+        ));
+        assert!(close(
+            evaluate("(-0.5)!").unwrap(),
+            std::f64::consts::PI.sqrt()
+        ));
+        assert!(close(evaluate("10.5!").unwrap(), 11_899_423.083_962_25));
+
+        let mut context = Context::new();
+        context.options.integer_factorial = true;
+        assert_eq!(evaluate_with("4!", &context).unwrap(), 24.0);
+        let error = evaluate_with("2.5!", &context).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Error>(),
+            Some(&Error::new(ErrorKind::InvalidFactorial, Some(1)))
+        );
+    }
+    #[test]
+    fn evaluate_functions() {
+        assert_eq!(evaluate("sqrt(16) + abs(-2)").unwrap(), 6.0);
+        assert_eq!(evaluate("log(1000)").unwrap(), 3.0);
+        assert_eq!(evaluate("ln(exp(2))").unwrap(), 2.0);
+        assert_eq!(evaluate("sin(0) + cos(0) * 2").unwrap(), 2.0);
+        assert_eq!(evaluate("sqrt(3 * 3)^2").unwrap(), 9.0);
+        assert_eq!(
+            error_of("foo(1)"),
+            Error::new(ErrorKind::UnknownFunction(String::from("foo")), Some(0))
+        );
+        assert_eq!(
+            error_of("sqrt()").kind,
+            ErrorKind::WrongArgumentCount {
+                name: String::from("sqrt"),
+                expected: 1,
+                found: 0
+            }
+        );
+
+        let mut context = Context::new();
+        context.set("x", 0.0);
+        assert_eq!(evaluate_with("tan(x)", &context).unwrap(), 0.0);
+
+        context.options.angle_mode = AngleMode::Degrees;
+        assert!((evaluate_with("sin(30)", &context).unwrap() - 0.5).abs() < 1e-12);
+        assert!((evaluate_with("cos(180)", &context).unwrap() + 1.0).abs() < 1e-12);
+        assert_eq!(evaluate_with("sqrt(16)", &context).unwrap(), 4.0);
+    }
+    #[test]
+    fn evaluate_constants() {
+        use std::f64::consts::{E, PI, TAU};
+        assert_eq!(evaluate("2pi").unwrap(), 2.0 * PI);
+        assert_eq!(evaluate("tau / 2").unwrap(), TAU / 2.0);
+        assert_eq!(evaluate("ln(e)").unwrap(), E.ln());
+        assert_eq!(evaluate("2(3 + 1)").unwrap(), 8.0);
+        assert_eq!(evaluate("(1 + 1)(2 + 2)").unwrap(), 8.0);
+
+        let mut context = Context::new();
+        context.set("r", 2.0);
+        assert_eq!(evaluate_with("pi r^2", &context).unwrap(), PI * 4.0);
+        assert_eq!(evaluate_with("3r!", &context).unwrap(), 6.0);
+    }
+    #[test]
+    fn evaluate_with_works() {
+        let mut context = Context::new();
+        context.set("x", 3.0);
+        context.set("rate", 0.5);
+        assert_eq!(evaluate_with("x * rate + x", &context).unwrap(), 4.5);
+        assert_eq!(evaluate_with("-x^2", &context).unwrap(), -9.0);
+        assert!(evaluate_with("y + 1", &context).is_err());
+        assert!(evaluate("x").is_err());
+        // conversions belong to the unit evaluator, so these are plain names here
+        context.set("in", 2.0);
+        context.set("to", 5.0);
+        assert_eq!(evaluate_with("3 in", &context).unwrap(), 6.0);
+        assert_eq!(evaluate_with("in to", &context).unwrap(), 10.0);
+    }
+    #[test]
+    fn evaluate_custom_functions() {
+        let mut context = Context::new();
+        context.register_fn("clamp", 3, |args: &[f64]| args[0].clamp(args[1], args[2]));
+        context.register_fn("answer", 0, |_: &[f64]| 42.0);
+        context.register_fn("sqrt", 1, |args: &[f64]| args[0] * 10.0);
+        context.set("x", 12.0);
+
+        assert_eq!(evaluate_with("clamp(x, 0, 10)", &context).unwrap(), 10.0);
+        assert_eq!(evaluate_with("clamp(-x, 0, 1) + 1", &context).unwrap(), 1.0);
+        assert_eq!(evaluate_with("2answer()", &context).unwrap(), 84.0);
+        assert_eq!(evaluate_with("sqrt(4)", &context).unwrap(), 40.0);
+
+        let error = evaluate_with("1 + clamp(x, 0)", &context).unwrap_err();
+        let error = error.downcast_ref::<Error>().unwrap();
+        assert_eq!(error.token, Some(2));
+        assert!(error
+            .to_string()
+            .starts_with("clamp takes 3 arguments, found 2\n  | 1 + clamp(x, 0)\n  |     ^^^^^"));
+        assert!(evaluate_with("clamp(x, 0, 1", &context).is_err());
+        assert!(evaluate_with("(1, 2)", &context).is_err());
+    }
+    #[test]
+    fn evaluate_custom_operators() {
+        let mut context = Context::new();
+        context.register_binary_op("//", 20, Associativity::Left, |a, b| (a / b).floor());
+        context.register_binary_op("@", 15, Associativity::Left, |a, b| a * 10.0 + b);
+        context.register_binary_op("^^", 40, Associativity::Right, f64::powf);
+        context.register_unary_op("to", 35, |a| a + 1.0);
+
+        assert_eq!(evaluate_with("7 // 2", &context).unwrap(), 3.0);
+        assert_eq!(evaluate_with("7 / 2", &context).unwrap(), 3.5);
+        assert_eq!(evaluate_with("1 + 2 @ 3 * 2", &context).unwrap(), 27.0);
+        assert_eq!(evaluate_with("2 ^^ 3 ^^ 2", &context).unwrap(), 512.0);
+        assert_eq!(evaluate_with("-to 2 ^ 2", &context).unwrap(), -5.0);
+        assert_eq!(evaluate_with("2 * to 1", &context).unwrap(), 4.0);
+
+        // word operators only match as whole words
+        context.set("total", 4.0);
+        assert_eq!(evaluate_with("total", &context).unwrap(), 4.0);
+        assert!(evaluate_with("2 to", &context).is_err());
+        assert!(evaluate_with("@ 2", &context).is_err());
+        assert!(evaluate("1 @ 2").is_err());
+
+        // the loosest possible level still has a next one for left associativity
+        context.register_binary_op("$", 255, Associativity::Left, |a, b| a - b);
+        assert_eq!(evaluate_with("10 $ 3 $ 2", &context).unwrap(), 5.0);
+        assert_eq!(evaluate_with("2 * 10 $ 3", &context).unwrap(), 14.0);
+    }
+    #[test]
+    fn evaluate_approx() {
+        assert_eq!(evaluate("0.1 + 0.2 ~= 0.3").unwrap(), 1.0);
+        assert_eq!(evaluate("0.1 + 0.2 ≈ 0.31").unwrap(), 0.0);
+        assert_eq!(evaluate("1e20 + 1e5 ~= 1e20").unwrap(), 1.0);
+        assert_eq!(evaluate("1e-15 ~= 0").unwrap(), 1.0);
+        assert_eq!(evaluate("approx(3.14, pi, 0.01)").unwrap(), 1.0);
+        assert_eq!(evaluate("approx(3.14, pi, 0.0001)").unwrap(), 0.0);
+
+        let mut context = Context::new();
+        context.options.approx_absolute = 0.5;
+        assert_eq!(evaluate_with("2 ~= 2.4", &context).unwrap(), 1.0);
+        assert!(evaluate("approx(1, 2)").is_err());
+    }
+    #[test]
+    fn evaluate_logical() {
+        let mut context = Context::new();
+        context.set("x", 5.0);
+        assert_eq!(evaluate_with("x > 3 and x < 10", &context).unwrap(), 1.0);
+        assert_eq!(evaluate_with("x > 3 && x > 10", &context).unwrap(), 0.0);
+        assert_eq!(evaluate_with("x < 3 or x == 5", &context).unwrap(), 1.0);
+        assert_eq!(evaluate_with("x < 3 || !x", &context).unwrap(), 0.0);
+        assert_eq!(evaluate_with("not x > 3 or x > 4", &context).unwrap(), 1.0);
+        assert_eq!(evaluate("1 or 0 and 0").unwrap(), 1.0);
+        assert_eq!(evaluate("not 2 + 3 == 6").unwrap(), 1.0);
+        assert_eq!(evaluate("(0.5 and 3) + 1").unwrap(), 2.0);
+        assert_eq!(evaluate("0 and 1 / 0").unwrap(), 0.0);
+        assert_eq!(evaluate("1 or 1 / 0").unwrap(), 1.0);
+        assert_eq!(error_of("1 and 1 / 0").kind, ErrorKind::DivisionByZero);
+    }
+    #[test]
+    fn evaluate_bitwise() {
+        assert_eq!(evaluate("0xf0 & 0x3c").unwrap(), 48.0);
+        assert_eq!(evaluate("0b1010 | 0b0101").unwrap(), 15.0);
+        assert_eq!(evaluate("6 xor 3").unwrap(), 5.0);
+        assert_eq!(evaluate("~5").unwrap(), -6.0);
+        assert_eq!(evaluate("1 | 2 xor 3 & 6").unwrap(), 1.0);
+        assert_eq!(evaluate("1 + 2 & 2").unwrap(), 2.0);
+        assert_eq!(evaluate("5 & 4 == 4").unwrap(), 1.0);
+        assert_eq!(evaluate("-8 & 0xff").unwrap(), 248.0);
+        assert_eq!(error_of("1.5 & 1").kind, ErrorKind::NotAnInteger);
+        assert_eq!(error_of("~0.5").kind, ErrorKind::NotAnInteger);
+        assert_eq!(error_of("2^54 | 1").kind, ErrorKind::NotAnInteger);
+    }
+    #[test]
+    fn evaluate_conditional() {
+        let mut context = Context::new();
+        context.set("x", -3.0);
+        assert_eq!(evaluate_with("x > 0 ? x : -x", &context).unwrap(), 3.0);
+        assert_eq!(evaluate_with("if(x < 0, 1, 2)", &context).unwrap(), 1.0);
+        assert_eq!(evaluate_with("x ? 1 : 2", &context).unwrap(), 1.0);
+        assert_eq!(evaluate("0 ? 1 : 0 ? 2 : 3").unwrap(), 3.0);
+        assert_eq!(evaluate("1 ? 0 ? 2 : 3 : 4").unwrap(), 3.0);
+        assert_eq!(evaluate("1 + 1 > 1 or 0 ? 10 + 1 : 20").unwrap(), 11.0);
+        assert_eq!(evaluate("2 * (0 ? 1 : 2) + 1").unwrap(), 5.0);
+        assert_eq!(evaluate("0 ? 1 / 0 : 5").unwrap(), 5.0);
+        assert_eq!(evaluate("if(1, 5, 1 / 0)").unwrap(), 5.0);
+        assert_eq!(error_of("1 ? 2").kind, ErrorKind::UnexpectedEnd);
+        assert_eq!(error_of("1 ? 2, 3").kind, ErrorKind::UnexpectedToken);
+        assert_eq!(
+            error_of("if(1, 2)").kind,
+            ErrorKind::WrongArgumentCount {
+                name: String::from("if"),
+                expected: 3,
+                found: 2
+            }
+        );
+        context.register_fn("if", 1, |args| args[0] * 10.0);
+        assert_eq!(evaluate_with("if(2)", &context).unwrap(), 20.0);
+        // a host if doesn't take over the operator, which still evaluates one branch
+        assert_eq!(evaluate_with("1 ? 2 : 1 / 0", &context).unwrap(), 2.0);
+        assert_eq!(evaluate_with("x > 0 ? x : -x", &context).unwrap(), 3.0);
+    }
+    #[test]
+    fn evaluate_piecewise() {
+        let tax = "piecewise((income <= 10000, 0), (income <= 40000, (income - 10000) * 20%), 6000 + (income - 40000) * 40%)";
+        let mut context = Context::new();
+        for (income, expected) in [(5000.0, 0.0), (25000.0, 3000.0), (50000.0, 10000.0)] {
+            context.set("income", income);
+            assert_eq!(evaluate_with(tax, &context).unwrap(), expected);
+        }
+        assert_eq!(evaluate("piecewise((1, 2), (1, 1 / 0), 3)").unwrap(), 2.0);
+        assert_eq!(evaluate("piecewise((0, 2), (1 < 2) * 5 + 1)").unwrap(), 6.0);
+        assert_eq!(evaluate("piecewise((0, 2), (1))").unwrap(), 1.0);
+        // a default in a group is read once, not again at every level it is nested in
+        let mut nested = String::from("1");
+        for _ in 0..150 {
+            nested = format!("piecewise((0, 1), ({}))", nested);
+        }
+        assert_eq!(evaluate(&nested).unwrap(), 1.0);
+        assert_eq!(evaluate("piecewise((0, 2), (3)! - 1)").unwrap(), 5.0);
+        assert_eq!(
+            error_of("piecewise((1, 2))").kind,
+            ErrorKind::UnexpectedToken
+        );
+        assert_eq!(
+            error_of("piecewise((1, 2), 3, 4)").kind,
+            ErrorKind::UnexpectedToken
+        );
+        assert_eq!(
+            error_of("piecewise((1, 2), 3").kind,
+            ErrorKind::UnclosedGroup
+        );
+        assert_eq!(
+            error_of("piecewise(3)").kind,
+            ErrorKind::TooFewArguments {
+                name: String::from("piecewise"),
+                minimum: 2,
+                found: 1
+            }
+        );
+        context.register_fn("if", 2, |args| args[0] + args[1]);
+        assert_eq!(
+            evaluate_with("piecewise((0, 1), (1, 2), 1 / 0)", &context).unwrap(),
+            2.0
+        );
+        context.register_fn("piecewise", 1, |args| args[0] + 1.0);
+        assert_eq!(evaluate_with("piecewise(2)", &context).unwrap(), 3.0);
+    }
+    #[test]
+    fn evaluate_aggregates() {
+        assert_eq!(evaluate("max(1, 9, 7)").unwrap(), 9.0);
+        assert_eq!(evaluate("min(4, -2 * 3, 7)").unwrap(), -6.0);
+        assert_eq!(evaluate("avg(1, 2, 3, 6)").unwrap(), 3.0);
+        assert_eq!(evaluate("max(5)").unwrap(), 5.0);
+        assert_eq!(
+            error_of("max()").kind,
+            ErrorKind::TooFewArguments {
+                name: String::from("max"),
+                minimum: 1,
+                found: 0
+            }
+        );
+
+        let mut context = Context::new();
+        context.set("missing", f64::NAN);
+        assert!(evaluate_with("max(1, missing)", &context).unwrap().is_nan());
+        context.options.ignore_nan = true;
+        assert_eq!(evaluate_with("max(1, missing, 3)", &context).unwrap(), 3.0);
+        assert_eq!(evaluate_with("avg(missing, 2, 4)", &context).unwrap(), 3.0);
+        assert!(evaluate_with("min(missing)", &context).unwrap().is_nan());
+    }
+    #[test]
+    fn evaluate_value_works() {
+        assert_eq!(evaluate_value("1 + 2").unwrap(), Value::Number(3.0));
+        assert_eq!(
+            evaluate_value("1 < 2 and 3 > 4").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(evaluate_value("(1 < 2) + 1").unwrap(), Value::Number(2.0));
+        let mut context = Context::new();
+        context.set("x", 5.0);
+        assert_eq!(
+            evaluate_value_with("x > 0 ? x == 5 : x != 5", &context).unwrap(),
+            Value::Bool(true)
+        );
+        let error = evaluate_value("1 / 0").unwrap_err();
+        assert!(error.to_string().contains("  | 1 / 0"));
+    }
+    #[test]
+    fn execute_works() {
+        let mut context = Context::new();
+        assert_eq!(execute("x = 3 + 4", &mut context).unwrap(), 7.0);
+        assert_eq!(context.get("x"), Some(7.0));
+        assert_eq!(execute("y = -x", &mut context).unwrap(), -7.0);
+        assert_eq!(execute("x = x * 2", &mut context).unwrap(), 14.0);
+        assert_eq!(execute("x y", &mut context).unwrap(), -98.0);
+        assert_eq!(
+            execute_value("same = x ~= 14", &mut context).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(context.get("same"), Some(1.0));
+
+        let mut error_of = |statement: &str| {
+            let error = execute(statement, &mut context).unwrap_err();
+            error.downcast_ref::<Error>().unwrap().clone()
+        };
+        assert_eq!(error_of("z = "), Error::new(ErrorKind::UnexpectedEnd, None));
+        assert_eq!(
+            error_of("2 = 3"),
+            Error::new(ErrorKind::UnexpectedToken, Some(1))
+        );
+        assert_eq!(
+            error_of("z = 1 = 1"),
+            Error::new(ErrorKind::UnexpectedToken, Some(3))
+        );
+        assert_eq!(
+            error_of("pi = 3"),
+            Error::new(ErrorKind::UnexpectedToken, Some(1))
+        );
+        // a failed assignment leaves the old binding alone
+        assert!(execute("x = 1 / 0", &mut context).is_err());
+        assert_eq!(context.get("x"), Some(14.0));
+        assert_eq!(context.get("z"), None);
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+
+use crate::ast::Expr;
+use crate::Context;
+
+// samples taken towards the point, each step halving the distance
+const LEVELS: usize = 16;
+// the relative error bound past which the estimate is no limit at all
+const TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    // infinite when the expression grows without bound
+    pub value: f64,
+    // the estimated distance to the true limit, infinite when a divergence is only a trend in the samples
+    pub error: f64,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.value.is_infinite() || self.error == 0.0 {
+            return write!(f, "{}", self.value);
+        }
+        write!(f, "{} ± {:.1e}", self.value, self.error)
+    }
+}
+
+// aitken's delta squared step, which removes a geometric error of whatever ratio the
+// samples show, so sqrt(x) or x * ln(x) speed up as well as a power series would
+fn accelerate(column: &[f64]) -> Vec<f64> {
+    column
+        .windows(3)
+        .map(|window| {
+            let (before, after) = (window[1] - window[0], window[2] - window[1]);
+            if before == after {
+                return window[2];
+            }
+            window[2] - after * after / (after - before)
+        })
+        .collect()
+}
+
+// a column only earns an estimate where its steps shrink at a steady ratio, and then the
+// rest of the geometric tail, with some slack for a ratio that still drifts, bounds the error
+fn settle(column: &[f64]) -> Option<Limit> {
+    let steps: Vec<f64> = column.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let mut best: Option<Limit> = None;
+    for (index, window) in steps.windows(3).enumerate() {
+        let value = column[index + 3];
+        let error = if window[1] == 0.0 && window[2] == 0.0 {
+            0.0
+        } else if window.contains(&0.0) {
+            continue;
+        } else {
+            let (previous, ratio) = (window[0] / window[1], window[1] / window[2]);
+            if ratio <= 1.0 || (ratio - previous).abs() > 0.1 * ratio {
+                continue;
+            }
+            4.0 * window[2].abs() / (ratio - 1.0)
+        };
+        let error = error.max(f64::EPSILON * value.abs());
+        if best.is_none_or(|best| error < best.error) {
+            best = Some(Limit { value, error });
+        }
+    }
+    best
+}
+
+// repeated acceleration over the samples, taken at distances halving each time,
+// keeping the estimate with the smallest error bound
+fn extrapolate(samples: &[f64]) -> Limit {
+    let mut best = Limit {
+        value: samples[samples.len() - 1],
+        error: f64::INFINITY,
+    };
+    let mut column = samples.to_vec();
+    while column.len() >= 4 {
+        if let Some(estimate) = settle(&column) {
+            if estimate.error < best.error {
+                best = estimate;
+            }
+        }
+        column = accelerate(&column);
+    }
+    best
+}
+
+// steps that keep their direction without shrinking, and lead away from zero, grow without bound
+fn diverges(samples: &[f64]) -> Option<f64> {
+    let tail = &samples[samples.len().saturating_sub(6)..];
+    let steps: Vec<f64> = tail.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let direction = steps.first()?.signum();
+    let growing = steps
+        .iter()
+        .all(|step| *step != 0.0 && step.signum() == direction)
+        && steps
+            .windows(2)
+            .all(|pair| pair[1].abs() >= 0.9 * pair[0].abs());
+    let away = tail[tail.len() - 1].signum() == direction;
+    (growing && away).then_some(direction * f64::INFINITY)
+}
+
+// the expression at each point, none once it is undefined at one
+fn sample(
+    expr: &Expr,
+    context: &mut Context,
+    variable: &str,
+    points: impl Iterator<Item = f64>,
+) -> anyhow::Result<Option<Vec<f64>>> {
+    let mut samples = vec![];
+    for x in points {
+        context.set(variable, x);
+        let value = expr.evaluate(context)?;
+        if value.is_nan() {
+            return Ok(None);
+        }
+        samples.push(value);
+    }
+    Ok(Some(samples))
+}
+
+pub fn limit(
+    expression: &str,
+    variable: &str,
+    approach: f64,
+    direction: Direction,
+) -> anyhow::Result<Limit> {
+    limit_with(expression, variable, approach, direction, &Context::new())
+}
+
+// estimates the limit as variable approaches `approach` from the given side, which may be infinite;
+// towards an infinity there is only one side to come from, so the direction is ignored
+pub fn limit_with(
+    expression: &str,
+    variable: &str,
+    approach: f64,
+    direction: Direction,
+    context: &Context,
+) -> anyhow::Result<Limit> {
+    if approach.is_nan() {
+        return Err(anyhow::Error::msg("cannot approach NaN"));
+    }
+    let expr = crate::parse_with(expression, context)?;
+    let mut context = context.clone();
+    let mut side = |sign: f64| -> anyhow::Result<Limit> {
+        let name = if sign < 0.0 { "left" } else { "right" };
+        let undefined = || {
+            anyhow::Error::msg(format!(
+                "expression is undefined near {} = {} from the {}",
+                variable, approach, name
+            ))
+        };
+        let points = (0..LEVELS).map(|level| {
+            if approach.is_infinite() {
+                approach.signum() * 2f64.powi(level as i32)
+            } else {
+                approach + sign * approach.abs().max(1.0) * 2f64.powi(-(level as i32) - 3)
+            }
+        });
+        let mut samples = sample(&expr, &mut context, variable, points)?.ok_or_else(undefined)?;
+        // something as slow as x / (x + 1000000) still looks like growth this close in,
+        // so towards an infinity the trend is checked again much further out
+        if approach.is_infinite() && diverges(&samples).is_some() {
+            let points =
+                (0..LEVELS).map(|level| approach.signum() * 2f64.powi(15 + 3 * level as i32));
+            samples = sample(&expr, &mut context, variable, points)?.ok_or_else(undefined)?;
+        }
+        let last = samples[samples.len() - 1];
+        if last.is_infinite() {
+            return Ok(Limit {
+                value: last,
+                error: 0.0,
+            });
+        }
+        // growth seen in the samples proves nothing, so it comes with no bound
+        if let Some(value) = diverges(&samples) {
+            return Ok(Limit {
+                value,
+                error: f64::INFINITY,
+            });
+        }
+        let estimate = extrapolate(&samples);
+        if estimate.error > TOLERANCE * estimate.value.abs().max(1.0) {
+            return Err(anyhow::Error::msg(format!(
+                "expression does not settle near {} = {} from the {}",
+                variable, approach, name
+            )));
+        }
+        Ok(estimate)
+    };
+
+    if approach.is_infinite() {
+        return side(-approach.signum());
+    }
+    match direction {
+        Direction::Left => side(-1.0),
+        Direction::Right => side(1.0),
+        Direction::Both => {
+            let (left, right) = (side(-1.0)?, side(1.0)?);
+            if left.value == right.value {
+                return Ok(Limit {
+                    value: left.value,
+                    error: left.error.max(right.error),
+                });
+            }
+            let gap = (left.value - right.value).abs();
+            let scale = left.value.abs().max(right.value.abs()).max(1.0);
+            if gap.is_infinite() || gap > left.error + right.error + TOLERANCE * scale {
+                return Err(anyhow::Error::msg(format!(
+                    "the limit from the left is {} but from the right {}",
+                    left, right
+                )));
+            }
+            Ok(Limit {
+                value: left.value + (right.value - left.value) / 2.0,
+                error: left.error.max(right.error) + gap / 2.0,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(expression: &str, approach: f64, direction: Direction, expected: f64) {
+        let found = limit(expression, "x", approach, direction).unwrap();
+        assert!(
+            (found.value - expected).abs() <= 1e-7 * expected.abs().max(1.0),
+            "{}: {}",
+            expression,
+            found
+        );
+        assert!(found.error < 1e-6, "{}: {}", expression, found);
+    }
+
+    #[test]
+    fn limit_works() {
+        close("sin(x) / x", 0.0, Direction::Both, 1.0);
+        close("(1 - cos(x)) / x^2", 0.0, Direction::Both, 0.5);
+        close("(x^2 - 1) / (x - 1)", 1.0, Direction::Both, 2.0);
+        close("(exp(x) - 1) / x", 0.0, Direction::Right, 1.0);
+        close(
+            "(1 + 1/x)^x",
+            f64::INFINITY,
+            Direction::Both,
+            std::f64::consts::E,
+        );
+        close(
+            "(2x + 1) / (x - 3)",
+            f64::NEG_INFINITY,
+            Direction::Both,
+            2.0,
+        );
+        close("abs(x) / x", 0.0, Direction::Right, 1.0);
+
+        let mut context = Context::new();
+        context.set("y", 1.0);
+        let found = limit_with("x^2 + y", "x", 3.0, Direction::Both, &context).unwrap();
+        assert!((found.value - 10.0).abs() < 1e-9);
+        assert_eq!(found.to_string().split(' ').next(), Some("10"));
+    }
+    #[test]
+    fn limit_diverges() {
+        let value = |expression, approach, direction| {
+            limit(expression, "x", approach, direction).unwrap().value
+        };
+        assert_eq!(value("1 / x^2", 0.0, Direction::Both), f64::INFINITY);
+        assert_eq!(value("1 / x", 0.0, Direction::Left), f64::NEG_INFINITY);
+        assert_eq!(value("ln(x)", 0.0, Direction::Right), f64::NEG_INFINITY);
+        assert_eq!(
+            value("exp(-x)", f64::NEG_INFINITY, Direction::Both),
+            f64::INFINITY
+        );
+        assert_eq!(
+            value("x^2 - x", f64::INFINITY, Direction::Both),
+            f64::INFINITY
+        );
+    }
+    #[test]
+    fn limit_errors() {
+        assert!(limit("1 / x", "x", 0.0, Direction::Both).is_err());
+        assert!(limit("abs(x) / x", "x", 0.0, Direction::Both).is_err());
+        assert!(limit("sin(1 / x)", "x", 0.0, Direction::Right).is_err());
+        assert!(limit("sqrt(x)", "x", 0.0, Direction::Left).is_err());
+        assert!(limit("x", "x", f64::NAN, Direction::Both).is_err());
+    }
+    #[test]
+    fn limit_bounds_are_honest() {
+        // neither converges like a power series, which once passed for a confident wrong answer
+        for expression in ["x * ln(x)", "sqrt(x)", "sqrt(x) * ln(x)", "x^x - 1"] {
+            let found = limit(expression, "x", 0.0, Direction::Right).unwrap();
+            assert!(
+                found.value.abs() <= found.error,
+                "{}: {}",
+                expression,
+                found
+            );
+            assert!(found.error < 1e-6, "{}: {}", expression, found);
+        }
+        assert!(limit("x * sin(1 / x)", "x", 0.0, Direction::Right).is_err());
+
+        // still climbing at 2^15, long before it settles at 1
+        let found = limit("x / (x + 1000000)", "x", f64::INFINITY, Direction::Both).unwrap();
+        assert!(
+            (found.value - 1.0).abs() <= found.error.max(1e-9),
+            "{}",
+            found
+        );
+        assert!(found.error < 1e-6, "{}", found);
+        // a divergence is only ever a trend, never certain
+        let found = limit("ln(x)", "x", f64::INFINITY, Direction::Both).unwrap();
+        assert_eq!(found.value, f64::INFINITY);
+        assert!(found.error > 0.0);
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{self, BufRead, Write};
+
+use solver::{AngleMode, Context, Value};
+
+const PROMPT: &str = "> ";
+const USAGE: &str = "usage: solver [--precision N] [--radians | --degrees] [--format plain|sci] [--numeric-bool] [--jsonrpc] [expression]\n       solver test [dir]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Format {
+    #[default]
+    Plain,
+    Scientific,
+}
+
+#[derive(Debug, Default)]
+struct Settings {
+    // no expression means reading expressions from stdin
+    expression: Option<String>,
+    help: bool,
+    jsonrpc: bool,
+    // print predicates as 1 and 0 instead of true and false
+    numeric_bool: bool,
+    precision: Option<usize>,
+    // only set by a flag, so giving the default mode explicitly still counts
+    angle_mode: Option<AngleMode>,
+    format: Format,
+    context: Context,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> anyhow::Result<Settings> {
+    let mut settings = Settings::default();
+    let mut words: Vec<String> = vec![];
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--precision" => {
+                let digits = args
+                    .next()
+                    .and_then(|digits| digits.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow::Error::msg("--precision needs a number of digits"))?;
+                settings.precision = Some(digits);
+            }
+            "--radians" => settings.angle_mode = Some(AngleMode::Radians),
+            "--degrees" => settings.angle_mode = Some(AngleMode::Degrees),
+            "--format" => {
+                settings.format = match args.next().as_deref() {
+                    Some("plain") => Format::Plain,
+                    Some("sci") => Format::Scientific,
+                    _ => return Err(anyhow::Error::msg("--format must be plain or sci")),
+                };
+            }
+            "--numeric-bool" => settings.numeric_bool = true,
+            "--jsonrpc" => settings.jsonrpc = true,
+            "-h" | "--help" => settings.help = true,
+            // a lone `-` is never a flag, and `-5` is a negative number rather than one
+            flag if flag.starts_with("--") => {
+                return Err(anyhow::Error::msg(format!(
+                    "unknown flag {}\n{}",
+                    flag, USAGE
+                )))
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    // unquoted `solver 2 + 3` arrives as several words
+    if !words.is_empty() {
+        settings.expression = Some(words.join(" "));
+    }
+    if let Some(angle_mode) = settings.angle_mode {
+        settings.context.options.angle_mode = angle_mode;
+    }
+    // requests carry their own expressions, and answers are json values rather than formatted text
+    let customised = settings.expression.is_some()
+        || settings.precision.is_some()
+        || settings.format != Format::Plain
+        || settings.numeric_bool
+        || settings.angle_mode.is_some();
+    if settings.jsonrpc && customised {
+        return Err(anyhow::Error::msg(format!(
+            "--jsonrpc takes no expression or output flags\n{}",
+            USAGE
+        )));
+    }
+    Ok(settings)
+}
+
+fn format_value(value: f64, settings: &Settings) -> String {
+    match (settings.format, settings.precision) {
+        (Format::Plain, None) => format!("{}", value),
+        (Format::Plain, Some(digits)) => format!("{:.*}", digits, value),
+        (Format::Scientific, None) => format!("{:e}", value),
+        (Format::Scientific, Some(digits)) => format!("{:.*e}", digits, value),
+    }
+}
+
+fn format_result(value: Value, settings: &Settings) -> String {
+    match value {
+        Value::Bool(boolean) if !settings.numeric_bool => boolean.to_string(),
+        value => format_value(value.into(), settings),
+    }
+}
+
+fn repl<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    settings: &mut Settings,
+) -> io::Result<()> {
+    let mut line = String::new();
+
+    loop {
+        write!(output, "{}", PROMPT)?;
+        output.flush()?;
+
+        line.clear();
+        // end of input (ctrl-d or a closed pipe) ends the session cleanly
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(());
+        }
+
+        let expression = line.trim();
+        match expression {
+            "" => continue,
+            "exit" | "quit" => return Ok(()),
+            _ => {}
+        }
+        if let Some(statement) = expression.strip_prefix("assume ") {
+            match settings.context.assume(statement) {
+                Ok(()) => writeln!(output, "assuming {}", statement.trim())?,
+                Err(error) => writeln!(output, "error: {}", error)?,
+            }
+            continue;
+        }
+        // uses the assumptions so far, and says which ones it needed
+        if let Some(expression) = expression.strip_prefix("simplify ") {
+            match solver::rewrite::simplify_with(expression, &settings.context) {
+                Ok((expr, relied)) if relied.is_empty() => writeln!(output, "{}", expr)?,
+                Ok((expr, relied)) => {
+                    let relied: Vec<String> = relied
+                        .iter()
+                        .map(|assumption| assumption.to_string())
+                        .collect();
+                    writeln!(output, "{}  (assuming {})", expr, relied.join(", "))?
+                }
+                Err(error) => writeln!(output, "error: {}", error)?,
+            }
+            continue;
+        }
+        // assignments persist for the rest of the session
+        match solver::execute_value(expression, &mut settings.context) {
+            Ok(value) => writeln!(output, "{}", format_result(value, settings))?,
+            Err(error) => writeln!(output, "error: {}", error)?,
+        }
+    }
+}
+
+// `solver test [dir]` runs every `.calc` file in dir
+fn run_tests(dir: &str) -> anyhow::Result<()> {
+    let report = solver::testing::run_calc_dir(dir)?;
+    for failure in &report.failures {
+        println!("{}", failure);
+    }
+    println!("{} passed, {} failed", report.passed, report.failures.len());
+    if report.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::Error::msg("some checks failed"))
+    }
+}
+
+// the directory for `solver test`, none when the first word is anything else
+fn test_dir(args: &[String]) -> Option<anyhow::Result<&str>> {
+    match args {
+        [command, rest @ ..] if command == "test" => Some(match rest {
+            [] => Ok("."),
+            [dir] => Ok(dir),
+            _ => Err(anyhow::Error::msg(format!(
+                "solver test takes at most one directory\n{}",
+                USAGE
+            ))),
+        }),
+        _ => None,
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(dir) = test_dir(&args) {
+        return run_tests(dir?);
+    }
+    let mut settings = parse_args(args.into_iter())?;
+
+    if settings.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if settings.jsonrpc {
+        return serve_jsonrpc();
+    }
+    match settings.expression.clone() {
+        Some(expression) => {
+            let value = solver::execute_value(&expression, &mut settings.context)?;
+            println!("{}", format_result(value, &settings));
+        }
+        None => {
+            let stdin = io::stdin();
+            repl(stdin.lock(), &mut io::stdout(), &mut settings)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "jsonrpc")]
+fn serve_jsonrpc() -> anyhow::Result<()> {
+    let stdin = io::stdin();
+    solver::jsonrpc::serve(stdin.lock(), &mut io::stdout())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "jsonrpc"))]
+fn serve_jsonrpc() -> anyhow::Result<()> {
+    Err(anyhow::Error::msg(
+        "solver was built without the jsonrpc feature",
+    ))
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> anyhow::Result<Settings> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    fn session(input: &str) -> String {
+        let mut output = vec![];
+        repl(input.as_bytes(), &mut output, &mut Settings::default()).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_command() {
+        let words =
+            |line: &str| -> Vec<String> { line.split_whitespace().map(String::from).collect() };
+        assert_eq!(test_dir(&words("test")).unwrap().unwrap(), ".");
+        assert_eq!(test_dir(&words("test checks")).unwrap().unwrap(), "checks");
+        let error = test_dir(&words("test foo bar")).unwrap().unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("solver test takes at most one directory"));
+        assert!(test_dir(&words("2 + test")).is_none());
+        assert!(test_dir(&[]).is_none());
+    }
+    #[test]
+    fn repl_works() {
+        assert_eq!(session("1 + 2\n2^10\n"), "> 3\n> 1024\n> \n");
+    }
+    #[test]
+    fn repl_keeps_going_after_errors() {
+        let output = session("5 +\n\n3!\nquit\n4\n");
+        assert_eq!(
+            output,
+            "> error: unexpected end of expression\n  | 5 +\n  |     ^\n> > 6\n> "
+        );
+    }
+    #[test]
+    fn repl_keeps_assignments() {
+        let output = session("r = 2\nv = r^3\nv + r\n");
+        assert_eq!(output, "> 2\n> 8\n> 10\n> \n");
+    }
+    #[test]
+    fn repl_simplifies() {
+        let output =
+            session("simplify sqrt(x^2) + 0\nassume x >= 0\nsimplify sqrt(x^2) + 0\nassume x\n");
+        assert_eq!(
+            output,
+            "> abs(x)\n> assuming x >= 0\n> x  (assuming x >= 0)\n> error: an assumption compares a variable with 0, like `x > 0`, or reads `n is integer` or `x is finite`\n> \n"
+        );
+    }
+    #[test]
+    fn parse_args_works() {
+        let settings = args("--precision 3 --degrees --format sci 2^10 + 5").unwrap();
+        assert_eq!(settings.expression.as_deref(), Some("2^10 + 5"));
+        assert_eq!(settings.precision, Some(3));
+        assert_eq!(settings.format, Format::Scientific);
+        assert_eq!(settings.context.options.angle_mode, AngleMode::Degrees);
+
+        assert_eq!(args("").unwrap().expression, None);
+        assert_eq!(
+            args("-5 * 2").unwrap().expression.as_deref(),
+            Some("-5 * 2")
+        );
+        assert!(args("--precision").is_err());
+        assert!(args("--format hex 1").is_err());
+        assert!(args("--verbose 1").is_err());
+        assert!(args("--help").unwrap().help);
+        assert!(args("-h 1 +").unwrap().help);
+    }
+    #[test]
+    fn parse_args_jsonrpc() {
+        assert!(args("--jsonrpc").unwrap().jsonrpc);
+        assert!(args("--jsonrpc 1 + 1").is_err());
+        assert!(args("--jsonrpc --precision 3").is_err());
+        assert!(args("--degrees --jsonrpc").is_err());
+        assert!(args("--radians --jsonrpc").is_err());
+        assert!(args("--jsonrpc --format sci").is_err());
+        assert!(args("--jsonrpc --numeric-bool").is_err());
+    }
+    #[test]
+    fn format_value_works() {
+        let mut settings = Settings::default();
+        assert_eq!(format_value(0.1 + 0.2, &settings), "0.30000000000000004");
+        settings.precision = Some(2);
+        assert_eq!(format_value(0.1 + 0.2, &settings), "0.30");
+        settings.format = Format::Scientific;
+        assert_eq!(format_value(1234.5, &settings), "1.23e3");
+        settings.precision = None;
+        assert_eq!(format_value(1234.5, &settings), "1.2345e3");
+
+        assert_eq!(format_result(Value::Bool(false), &settings), "false");
+        let settings = args("--numeric-bool").unwrap();
+        assert_eq!(format_result(Value::Bool(true), &settings), "1");
+        assert_eq!(format_result(Value::Number(2.5), &settings), "2.5");
+    }
+}
@@ -0,0 +1,396 @@
+// matrices are rows of f64, a[i][j] being row i and column j
+type Matrix = Vec<Vec<f64>>;
+
+// pivots this far below the largest entry count as zero
+const EPSILON: f64 = 1e-12;
+// shifted qr steps allowed per eigenvalue before giving up
+const ITERATIONS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lu {
+    pub lower: Matrix,
+    pub upper: Matrix,
+    // row i of the factored matrix is row permutation[i] of the input, so a[p] = lower * upper
+    pub permutation: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Qr {
+    pub q: Matrix,
+    pub r: Matrix,
+}
+
+fn check_shape(a: &[Vec<f64>]) -> anyhow::Result<(usize, usize)> {
+    let columns = a.first().map_or(0, |row| row.len());
+    if columns == 0 {
+        return Err(anyhow::Error::msg("matrix must not be empty"));
+    }
+    if a.iter().any(|row| row.len() != columns) {
+        return Err(anyhow::Error::msg(
+            "matrix rows must all be the same length",
+        ));
+    }
+    if a.iter().flatten().any(|value| !value.is_finite()) {
+        return Err(anyhow::Error::msg("matrix entries must be finite"));
+    }
+    Ok((a.len(), columns))
+}
+
+fn check_square(a: &[Vec<f64>]) -> anyhow::Result<usize> {
+    let (rows, columns) = check_shape(a)?;
+    if rows != columns {
+        return Err(anyhow::Error::msg("matrix must be square"));
+    }
+    Ok(rows)
+}
+
+fn identity(size: usize) -> Matrix {
+    (0..size)
+        .map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect()
+}
+
+fn multiply(a: &[Vec<f64>], b: &[Vec<f64>]) -> Matrix {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| {
+                    row.iter()
+                        .zip(b)
+                        .map(|(value, other)| value * other[j])
+                        .sum()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn largest(a: &[Vec<f64>]) -> f64 {
+    a.iter()
+        .flatten()
+        .fold(0.0, |largest, value| value.abs().max(largest))
+}
+
+// doolittle with partial pivoting
+pub fn lu(a: &[Vec<f64>]) -> anyhow::Result<Lu> {
+    let size = check_square(a)?;
+    let tolerance = largest(a) * EPSILON;
+    let mut upper = a.to_vec();
+    let mut lower = vec![vec![0.0; size]; size];
+    let mut permutation: Vec<usize> = (0..size).collect();
+
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|&x, &y| upper[x][column].abs().total_cmp(&upper[y][column].abs()))
+            .unwrap_or(column);
+        if upper[pivot][column].abs() <= tolerance {
+            return Err(anyhow::Error::msg("matrix is singular"));
+        }
+        upper.swap(column, pivot);
+        lower.swap(column, pivot);
+        permutation.swap(column, pivot);
+
+        let (done, below) = upper.split_at_mut(column + 1);
+        let pivot_row = &done[column];
+        for (row, lower_row) in below.iter_mut().zip(&mut lower[column + 1..]) {
+            let factor = row[column] / pivot_row[column];
+            lower_row[column] = factor;
+            for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    for (index, row) in lower.iter_mut().enumerate() {
+        row[index] = 1.0;
+    }
+    Ok(Lu {
+        lower,
+        upper,
+        permutation,
+    })
+}
+
+impl Lu {
+    // forward then back substitution, for one right-hand side
+    fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let size = b.len();
+        let mut y = vec![0.0; size];
+        for row in 0..size {
+            let known: f64 = (0..row)
+                .map(|index| self.lower[row][index] * y[index])
+                .sum();
+            y[row] = b[self.permutation[row]] - known;
+        }
+        let mut x = vec![0.0; size];
+        for row in (0..size).rev() {
+            let known: f64 = (row + 1..size)
+                .map(|index| self.upper[row][index] * x[index])
+                .sum();
+            x[row] = (y[row] - known) / self.upper[row][row];
+        }
+        x
+    }
+}
+
+// solves a * x = b
+pub fn solve(a: &[Vec<f64>], b: &[f64]) -> anyhow::Result<Vec<f64>> {
+    let factors = lu(a)?;
+    if b.len() != a.len() {
+        return Err(anyhow::Error::msg("b must have one value per row of a"));
+    }
+    if b.iter().any(|value| !value.is_finite()) {
+        return Err(anyhow::Error::msg("b must be finite"));
+    }
+    Ok(factors.solve(b))
+}
+
+pub fn inv(a: &[Vec<f64>]) -> anyhow::Result<Matrix> {
+    let factors = lu(a)?;
+    let size = a.len();
+    // column j of the inverse solves a * x = e_j
+    let columns: Vec<Vec<f64>> = identity(size)
+        .iter()
+        .map(|unit| factors.solve(unit))
+        .collect();
+    Ok((0..size)
+        .map(|i| columns.iter().map(|column| column[i]).collect())
+        .collect())
+}
+
+// householder reflections, so q is square and orthogonal and r is the shape of a
+pub fn qr(a: &[Vec<f64>]) -> anyhow::Result<Qr> {
+    let (rows, columns) = check_shape(a)?;
+    let mut r = a.to_vec();
+    let mut q = identity(rows);
+
+    for column in 0..columns.min(rows.saturating_sub(1)) {
+        let norm = (column..rows)
+            .map(|row| r[row][column].powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        // reflecting onto -sign * norm avoids cancelling against the diagonal
+        let alpha = -r[column][column].signum() * norm;
+        let mut v: Vec<f64> = (column..rows).map(|row| r[row][column]).collect();
+        v[0] -= alpha;
+        let length: f64 = v.iter().map(|value| value * value).sum();
+        if length == 0.0 {
+            continue;
+        }
+
+        let dots: Vec<f64> = (0..columns)
+            .map(|j| (column..rows).map(|row| v[row - column] * r[row][j]).sum())
+            .collect();
+        for (row, component) in r[column..].iter_mut().zip(&v) {
+            for (value, dot) in row.iter_mut().zip(&dots) {
+                *value -= 2.0 * dot / length * component;
+            }
+        }
+        for line in q.iter_mut() {
+            let dot: f64 = (column..rows).map(|row| line[row] * v[row - column]).sum();
+            for row in column..rows {
+                line[row] -= 2.0 * dot / length * v[row - column];
+            }
+        }
+    }
+    // below the diagonal is rounding noise once reflected
+    for (i, row) in r.iter_mut().enumerate() {
+        for value in row.iter_mut().take(i) {
+            *value = 0.0;
+        }
+    }
+    Ok(Qr { q, r })
+}
+
+// both eigenvalues of [[a, b], [c, d]], or None when they are complex
+fn pair(a: f64, b: f64, c: f64, d: f64) -> Option<(f64, f64)> {
+    let half_trace = (a + d) / 2.0;
+    let discriminant = ((a - d) / 2.0).powi(2) + b * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some((half_trace + root, half_trace - root))
+}
+
+// real eigenvalues by shifted qr iteration, largest first; complex pairs are an error
+pub fn eigvals(a: &[Vec<f64>]) -> anyhow::Result<Vec<f64>> {
+    let size = check_square(a)?;
+    let tolerance = largest(a).max(f64::MIN_POSITIVE) * EPSILON;
+    let mut matrix = a.to_vec();
+    let mut values = vec![];
+    let mut active = size;
+    let mut steps = 0;
+    let small = |matrix: &Matrix, row: usize, columns: usize| {
+        matrix[row][..columns]
+            .iter()
+            .all(|value| value.abs() <= tolerance)
+    };
+
+    while active > 0 {
+        let last = active - 1;
+        if active == 1 || small(&matrix, last, last) {
+            values.push(matrix[last][last]);
+            active -= 1;
+            continue;
+        }
+        let block = (
+            matrix[last - 1][last - 1],
+            matrix[last - 1][last],
+            matrix[last][last - 1],
+            matrix[last][last],
+        );
+        if active == 2 || small(&matrix, last - 1, last - 1) && small(&matrix, last, last - 1) {
+            let (first, second) = pair(block.0, block.1, block.2, block.3)
+                .ok_or_else(|| anyhow::Error::msg("matrix has complex eigenvalues"))?;
+            values.extend([first, second]);
+            active -= 2;
+            continue;
+        }
+
+        steps += 1;
+        if steps > ITERATIONS * size {
+            return Err(anyhow::Error::msg("eigenvalues did not converge"));
+        }
+        // wilkinson shift: the eigenvalue of the trailing block nearest its corner
+        let shift = match pair(block.0, block.1, block.2, block.3) {
+            Some((first, second)) if (first - block.3).abs() < (second - block.3).abs() => first,
+            Some((_, second)) => second,
+            None => block.3,
+        };
+        let mut leading: Matrix = matrix[..active]
+            .iter()
+            .map(|row| row[..active].to_vec())
+            .collect();
+        for (index, row) in leading.iter_mut().enumerate() {
+            row[index] -= shift;
+        }
+        let Qr { q, r } = qr(&leading)?;
+        let mut next = multiply(&r, &q);
+        for (index, row) in next.iter_mut().enumerate() {
+            row[index] += shift;
+        }
+        for (row, updated) in matrix.iter_mut().zip(next) {
+            row[..active].copy_from_slice(&updated);
+        }
+    }
+
+    values.sort_by(|x, y| y.total_cmp(x));
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn close_matrix(a: &[Vec<f64>], b: &[Vec<f64>]) -> bool {
+        a.iter()
+            .flatten()
+            .zip(b.iter().flatten())
+            .all(|(&x, &y)| close(x, y))
+    }
+
+    fn example() -> Matrix {
+        vec![
+            vec![2.0, 1.0, 1.0],
+            vec![4.0, -6.0, 0.0],
+            vec![-2.0, 7.0, 2.0],
+        ]
+    }
+
+    #[test]
+    fn solve_works() {
+        let x = solve(&example(), &[5.0, -2.0, 9.0]).unwrap();
+        assert!(close(x[0], 1.0) && close(x[1], 1.0) && close(x[2], 2.0));
+        // the zero pivot forces a row swap
+        let x = solve(&[vec![0.0, 1.0], vec![1.0, 0.0]], &[3.0, 4.0]).unwrap();
+        assert!(close(x[0], 4.0) && close(x[1], 3.0));
+
+        assert!(solve(&[vec![1.0, 2.0], vec![2.0, 4.0]], &[1.0, 2.0]).is_err());
+        assert!(solve(&example(), &[1.0, 2.0]).is_err());
+        assert!(solve(&[vec![1.0, 2.0]], &[1.0]).is_err());
+        assert!(solve(&[vec![1.0], vec![1.0, 2.0]], &[1.0, 2.0]).is_err());
+        assert!(solve(&[], &[]).is_err());
+        assert!(solve(&[vec![f64::NAN]], &[1.0]).is_err());
+    }
+    #[test]
+    fn inv_works() {
+        let inverse = inv(&example()).unwrap();
+        assert!(close_matrix(&multiply(&example(), &inverse), &identity(3)));
+        let inverse = inv(&[vec![4.0, 7.0], vec![2.0, 6.0]]).unwrap();
+        assert!(close_matrix(&inverse, &[vec![0.6, -0.7], vec![-0.2, 0.4]]));
+        assert!(inv(&[vec![0.0]]).is_err());
+    }
+    #[test]
+    fn lu_works() {
+        let Lu {
+            lower,
+            upper,
+            permutation,
+        } = lu(&example()).unwrap();
+        let permuted: Matrix = permutation
+            .iter()
+            .map(|&row| example()[row].clone())
+            .collect();
+        assert!(close_matrix(&multiply(&lower, &upper), &permuted));
+        for i in 0..3 {
+            assert_eq!(lower[i][i], 1.0);
+            assert!((i + 1..3).all(|j| lower[i][j] == 0.0 && upper[j][i] == 0.0));
+        }
+    }
+    #[test]
+    fn qr_works() {
+        let tall = vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]];
+        for a in [example(), tall] {
+            let Qr { q, r } = qr(&a).unwrap();
+            assert!(close_matrix(&multiply(&q, &r), &a));
+            let transposed: Matrix = (0..q.len())
+                .map(|i| q.iter().map(|row| row[i]).collect())
+                .collect();
+            assert!(close_matrix(&multiply(&transposed, &q), &identity(q.len())));
+            assert!((0..r.len()).all(|i| r[i][..i.min(r[i].len())].iter().all(|&v| v == 0.0)));
+        }
+    }
+    #[test]
+    fn eigvals_works() {
+        let values = eigvals(&[vec![2.0, 0.0], vec![0.0, 3.0]]).unwrap();
+        assert!(close(values[0], 3.0) && close(values[1], 2.0));
+        let symmetric = vec![
+            vec![4.0, 1.0, 2.0],
+            vec![1.0, 3.0, 0.0],
+            vec![2.0, 0.0, 5.0],
+        ];
+        let values = eigvals(&symmetric).unwrap();
+        assert_eq!(values.len(), 3);
+        assert!(close(values.iter().sum(), 12.0));
+        assert!(close(
+            values.iter().product(),
+            4.0 * 15.0 - 1.0 * 5.0 + 2.0 * -6.0
+        ));
+        // equal magnitudes, which unshifted qr never separates
+        let values = eigvals(&[vec![0.0, 1.0], vec![1.0, 0.0]]).unwrap();
+        assert!(close(values[0], 1.0) && close(values[1], -1.0));
+        let values = eigvals(&[
+            vec![1.0, 2.0, 3.0],
+            vec![0.0, 4.0, 5.0],
+            vec![0.0, 0.0, 6.0],
+        ])
+        .unwrap();
+        assert!(close(values[0], 6.0) && close(values[1], 4.0) && close(values[2], 1.0));
+        // a rotation has no real eigenvalues
+        assert!(eigvals(&[vec![0.0, -1.0], vec![1.0, 0.0]]).is_err());
+        assert!(eigvals(&[
+            vec![0.0, -1.0, 0.0],
+            vec![1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 2.0]
+        ])
+        .is_err());
+        assert!(eigvals(&[vec![1.0, 2.0]]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+
+use crate::ast::Expr;
+use crate::lexer::{self, LexOptions, Operator, Token};
+use crate::parser::Parser;
+use crate::rewrite::same;
+use crate::Context;
+
+fn matching_groups(tokens: &[Token]) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    let mut stack = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Left(_) => stack.push(index),
+            Token::Right(_) => {
+                if let Some(left) = stack.pop() {
+                    pairs.push((left, index));
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+fn is_number(token: Option<&Token>) -> bool {
+    matches!(token, Some(Token::Number(_)))
+}
+
+fn is_operand(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        Some(Token::Number(_)) | Some(Token::Identifier(_)) | Some(Token::Constant(_))
+    )
+}
+
+// only groups that can't change how any grammar reads the tokens are removed:
+// the whole expression, a group directly inside another group, and a lone number
+fn redundant_group(tokens: &[Token]) -> Option<(usize, usize)> {
+    let pairs = matching_groups(tokens);
+    pairs.iter().copied().find(|&(left, right)| {
+        let whole = left == 0 && right == tokens.len() - 1;
+        let doubled = pairs.contains(&(left + 1, right - 1));
+        let lone_number = right == left + 2
+            && is_number(tokens.get(left + 1))
+            && !is_operand(left.checked_sub(1).and_then(|before| tokens.get(before)))
+            && !is_operand(tokens.get(right + 1));
+        whole || doubled || lone_number
+    })
+}
+
+// a number as digits and a power of ten, with no zeros at either end of the digits;
+// none for literals too long to write out exactly
+fn digits(text: &str) -> Option<(String, i64)> {
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => 10,
+    };
+    if radix != 10 {
+        let value = u128::from_str_radix(&text[2..], radix).ok()?;
+        return digits(&value.to_string());
+    }
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(index) => (&text[..index], text[index + 1..].parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all = format!("{}{}", whole, fraction);
+    let all = all.trim_start_matches('0');
+    let trimmed = all.trim_end_matches('0');
+    let exponent = exponent - fraction.len() as i64 + (all.len() - trimmed.len()) as i64;
+    Some((
+        String::from(trimmed),
+        if trimmed.is_empty() { 0 } else { exponent },
+    ))
+}
+
+// a number keeps the text it was written with, unless the shortest form of its f64 is no
+// longer and means exactly the same: `.50` becomes `0.5`, but `1e300` and
+// `9007199254740993` stay, since other evaluators read every digit
+fn number(text: &str, value: f64) -> String {
+    let shortest = value.to_string();
+    let exact = digits(text).is_some_and(|text| digits(&shortest) == Some(text));
+    if exact && shortest.len() <= text.len() {
+        shortest
+    } else {
+        String::from(text)
+    }
+}
+
+// whether removing the group would leave an operand right next to another, as `2(3)` would;
+// that still multiplies, but reads like a single number
+fn runs_together(tokens: &[Token], (left, right): (usize, usize)) -> bool {
+    let before = left.checked_sub(1).and_then(|before| tokens.get(before));
+    (is_operand(before) && is_operand(tokens.get(left + 1)))
+        || (is_operand(tokens.get(right - 1)) && is_operand(tokens.get(right + 1)))
+}
+
+// the logical operators print as words, their symbols are shorter and need no spaces
+fn symbol(token: &Token, text: &str) -> String {
+    match token {
+        Token::Number(value) => number(text, *value),
+        Token::Binary(Operator::And) => String::from("&&"),
+        Token::Binary(Operator::Or) => String::from("||"),
+        Token::Unary(Operator::Not) => String::from("!"),
+        _ => token.to_string(),
+    }
+}
+
+// a word operator runs into the names around it
+fn is_word(token: &Token) -> bool {
+    matches!(token, Token::Binary(Operator::Convert | Operator::BitXor))
+}
+
+// whether nothing is needed before `tokens[index]`: the tokens around it, written next to
+// each other, must still lex to themselves. how `-` and `%` lex depends on a couple of tokens
+// either side, so those are written out too, after a stand-in for the one before them
+fn joins(tokens: &[Token], symbols: &[String], spaced: &[bool], index: usize) -> bool {
+    let start = index.saturating_sub(2);
+    let end = (index + 2).min(tokens.len());
+    let context = start > 0 && !lexer::expects_operand(Some(&tokens[start - 1]));
+    let mut text = String::from(if context { "1 " } else { "" });
+    for position in start..end {
+        let space = match position.cmp(&index) {
+            Ordering::Less => spaced[position],
+            Ordering::Equal => false,
+            Ordering::Greater => true,
+        };
+        if position > start && space {
+            text.push(' ');
+        }
+        text += &symbols[position];
+    }
+    lexer::lex(&text).is_ok_and(|lexed| lexed[usize::from(context)..] == tokens[start..end])
+}
+
+// `texts` holds what each token was written as
+fn render(tokens: &[Token], texts: &[String]) -> String {
+    let symbols: Vec<String> = tokens
+        .iter()
+        .zip(texts)
+        .map(|(token, text)| symbol(token, text))
+        .collect();
+    // whether each token has a space before it
+    let mut spaced = vec![false; tokens.len()];
+    for index in 1..tokens.len() {
+        let (previous, token) = (&tokens[index - 1], &tokens[index]);
+        // two operands next to each other still need something between them
+        spaced[index] = (is_operand(Some(previous)) && is_operand(Some(token)))
+            || is_word(previous)
+            || is_word(token)
+            || !joins(tokens, &symbols, &spaced, index);
+    }
+    let mut result = String::new();
+    for (symbol, space) in symbols.iter().zip(spaced) {
+        if space {
+            result.push(' ');
+        }
+        result += symbol;
+    }
+    result
+}
+
+// reads a statement the way evaluating it would, so the minified one can be checked against it
+fn parse(expression: &str) -> anyhow::Result<(Option<String>, Expr)> {
+    let context = Context::new();
+    let (tokens, spans) = crate::tokenize(expression, &context, false)?;
+    Parser::new(&tokens, &context)
+        .parse_statement()
+        .map_err(|error| crate::framed(error, expression, &spans))
+}
+
+pub fn minify(expression: &str) -> anyhow::Result<String> {
+    let (mut tokens, spans) = lexer::lex_spans(expression, &LexOptions::default())?;
+    let chars: Vec<char> = expression.chars().collect();
+    let mut texts: Vec<String> = spans
+        .iter()
+        .map(|span| chars[span.clone()].iter().collect())
+        .collect();
+
+    // any other group goes when the expression parses the same without it
+    let original = parse(expression).ok();
+    let parses_the_same = |tokens: &[Token], texts: &[String]| {
+        original.as_ref().is_some_and(|(target, expr)| {
+            parse(&render(tokens, texts)).is_ok_and(|(minified_target, minified_expr)| {
+                minified_target == *target && same(&minified_expr, expr)
+            })
+        })
+    };
+    let without = |tokens: &[Token], texts: &[String], (left, right): (usize, usize)| {
+        let mut tokens = tokens.to_vec();
+        let mut texts = texts.to_vec();
+        for index in [right, left] {
+            tokens.remove(index);
+            texts.remove(index);
+        }
+        (tokens, texts)
+    };
+    loop {
+        let group = redundant_group(&tokens).or_else(|| {
+            matching_groups(&tokens).into_iter().find(|&group| {
+                let (tokens_without, texts_without) = without(&tokens, &texts, group);
+                !runs_together(&tokens, group) && parses_the_same(&tokens_without, &texts_without)
+            })
+        });
+        let Some(group) = group else {
+            break;
+        };
+        (tokens, texts) = without(&tokens, &texts, group);
+    }
+
+    let minified = render(&tokens, &texts);
+    if lexer::lex(&minified)? != tokens {
+        return Err(anyhow::Error::msg(
+            "minified expression does not lex the same",
+        ));
+    }
+    // removing a group must not change what the expression means
+    if let Ok((target, expr)) = parse(expression) {
+        let same_statement = parse(&minified).is_ok_and(|(minified_target, minified_expr)| {
+            minified_target == target && same(&minified_expr, &expr)
+        });
+        if !same_statement {
+            return Err(anyhow::Error::msg(
+                "minified expression does not parse the same",
+            ));
+        }
+    }
+    Ok(minified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minify_whitespace() {
+        assert_eq!(minify(" 5 + 3 * ( 2 - 1 ) ").unwrap(), "5+3*(2-1)");
+        assert_eq!(minify("5 3").unwrap(), "5 3");
+        assert_eq!(minify("x 2 + rate").unwrap(), "x 2+rate");
+        assert_eq!(minify(".50 + 1.").unwrap(), "0.5+1");
+        assert_eq!(minify("5 km  in  mi").unwrap(), "5 km in mi");
+        assert_eq!(minify("not x and y or 3! > 1").unwrap(), "!x&&y||3!>1");
+        assert_eq!(minify("~x & 0xf0 xor y").unwrap(), "~x&240 xor y");
+        // where two tokens would run together they keep a space
+        assert_eq!(minify("50% - 3").unwrap(), "50%- 3");
+        assert_eq!(minify("3! == 6").unwrap(), "3! ==6");
+        assert_eq!(minify("50% - (3)").unwrap(), "50%- 3");
+        assert_eq!(minify("50 % -(3)").unwrap(), "50 % -3");
+        assert_eq!(minify("2 - -3").unwrap(), "2--3");
+        assert_eq!(minify("x = 1 + 2").unwrap(), "x=1+2");
+    }
+    #[test]
+    fn minify_groups() {
+        assert_eq!(minify("((1 + 2)) * 3").unwrap(), "(1+2)*3");
+        assert_eq!(minify("(2 + (3))").unwrap(), "2+3");
+        assert_eq!(minify("(4)!").unwrap(), "4!");
+        assert_eq!(minify("2 (3)").unwrap(), "2(3)");
+        assert_eq!(minify("(2*3)+1").unwrap(), "2*3+1");
+        assert_eq!(minify("x = (a * b) - (c / d)").unwrap(), "x=a*b-c/d");
+        assert_eq!(
+            minify("-(2^x) + (x > 1 ? 2 : 3)").unwrap(),
+            "-2^x+(x>1?2:3)"
+        );
+        // groups that decide how it parses stay
+        assert_eq!(minify("(1 + 2) * 3 - (4 - 5)").unwrap(), "(1+2)*3-(4-5)");
+        assert_eq!(minify("2^(3^2) + (2^3)^2").unwrap(), "2^3^2+(2^3)^2");
+        assert_eq!(minify("x (y + 1)").unwrap(), "x(y+1)");
+    }
+    #[test]
+    fn minify_numbers() {
+        // every digit is kept, even past what an f64 holds
+        assert_eq!(
+            minify("12345678901234567891 + 1").unwrap(),
+            "12345678901234567891+1"
+        );
+        assert_eq!(
+            minify("9007199254740993 * 1").unwrap(),
+            "9007199254740993*1"
+        );
+        assert_eq!(minify("0x20000000000001").unwrap(), "0x20000000000001");
+        // exponents are never written out
+        assert_eq!(minify("1e300").unwrap(), "1e300");
+        assert_eq!(minify("6.02e23 * 2").unwrap(), "6.02e23*2");
+        assert_eq!(minify("2.5E-3").unwrap(), "0.0025");
+        assert_eq!(minify("1.0e2").unwrap(), "100");
+        assert_eq!(minify("0.000").unwrap(), "0");
+    }
+}
@@ -0,0 +1,285 @@
+use crate::ast::Expr;
+use crate::lexer::{self, Group, Operator, Token};
+use crate::parser::Parser;
+use crate::Context;
+
+fn unit_value(word: &str) -> Option<f64> {
+    let value = match word {
+        "zero" => 0,
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "thirteen" => 13,
+        "fourteen" => 14,
+        "fifteen" => 15,
+        "sixteen" => 16,
+        "seventeen" => 17,
+        "eighteen" => 18,
+        "nineteen" => 19,
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "seventy" => 70,
+        "eighty" => 80,
+        "ninety" => 90,
+        _ => return None,
+    };
+    Some(value as f64)
+}
+
+fn scale_value(word: &str) -> Option<f64> {
+    match word {
+        "thousand" => Some(1e3),
+        "million" => Some(1e6),
+        "billion" => Some(1e9),
+        "trillion" => Some(1e12),
+        _ => None,
+    }
+}
+
+fn is_number_word(word: &str) -> bool {
+    unit_value(word).is_some() || scale_value(word).is_some() || word == "hundred"
+}
+
+// reads "two hundred thirty four thousand five point one two" starting at `index`
+fn parse_number_words(words: &[&str], index: &mut usize) -> anyhow::Result<Option<f64>> {
+    let (mut total, mut group) = (0.0, 0.0);
+    let start = *index;
+    // the unit word just read, since only "twenty four" may put two in a row
+    let mut previous: Option<(&str, f64)> = None;
+
+    while let Some(&word) = words.get(*index) {
+        if let Some(value) = unit_value(word) {
+            if let Some((last, last_value)) = previous {
+                if last_value < 20.0 || !(1.0..10.0).contains(&value) {
+                    return Err(anyhow::Error::msg(format!(
+                        "{} cannot follow {} in a number",
+                        word, last
+                    )));
+                }
+            }
+            group += value;
+            previous = Some((word, value));
+        } else if word == "hundred" {
+            group = group.max(1.0) * 100.0;
+            previous = None;
+        } else if let Some(scale) = scale_value(word) {
+            total += group.max(1.0) * scale;
+            group = 0.0;
+            previous = None;
+        } else {
+            break;
+        }
+        *index += 1;
+    }
+    if *index == start {
+        return Ok(None);
+    }
+    let mut number = total + group;
+
+    if words.get(*index) == Some(&"point") {
+        *index += 1;
+        let mut digits = String::new();
+        while let Some(digit) = words.get(*index).and_then(|word| unit_value(word)) {
+            if digit >= 10.0 {
+                break;
+            }
+            digits += &digit.to_string();
+            *index += 1;
+        }
+        number += format!("0.{}", digits).parse::<f64>().unwrap_or_default();
+    }
+
+    Ok(Some(number))
+}
+
+fn starts_with(words: &[&str], index: usize, phrase: &[&str]) -> bool {
+    words[index..].starts_with(phrase)
+}
+
+pub fn lex_natural(input: &str) -> anyhow::Result<Vec<Token>> {
+    let lowered = input.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|char: char| char.is_whitespace() || char == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut result: Vec<Token> = vec![];
+    let mut open_groups = 0;
+    let mut index = 0;
+
+    // multi-word phrases first, so "divided by" isn't read as "divided" then "by"
+    let phrases: [(&[&str], &[Token]); 8] = [
+        (&["divided", "by"], &[Token::Binary(Operator::Divide)]),
+        (&["multiplied", "by"], &[Token::Binary(Operator::Multiply)]),
+        (
+            &["to", "the", "power", "of"],
+            &[Token::Binary(Operator::Power)],
+        ),
+        (&["raised", "to"], &[Token::Binary(Operator::Power)]),
+        // the postfix percent takes the whole operand before it, a quantity included
+        (
+            &["percent", "of"],
+            &[
+                Token::Unary(Operator::Percent),
+                Token::Binary(Operator::Multiply),
+            ],
+        ),
+        (&["percent"], &[Token::Unary(Operator::Percent)]),
+        (&["the", "quantity"], &[Token::Left(Group::Parenthesis)]),
+        (&["end", "quantity"], &[Token::Right(Group::Parenthesis)]),
+    ];
+
+    'words: while index < words.len() {
+        for (phrase, tokens) in phrases.iter() {
+            if starts_with(&words, index, phrase) {
+                if phrase[0] == "percent" && result.is_empty() {
+                    return Err(anyhow::Error::msg("percent without a number"));
+                }
+                match phrase[0] {
+                    "the" => open_groups += 1,
+                    "end" => open_groups -= 1,
+                    _ => {}
+                }
+                result.extend(tokens.iter().cloned());
+                index += phrase.len();
+                continue 'words;
+            }
+        }
+
+        let word = words[index];
+        if is_number_word(word) {
+            let number = parse_number_words(&words, &mut index)?.unwrap_or_default();
+            result.push(Token::Number(number));
+            continue;
+        }
+        if let Ok(number) = word.parse::<f64>() {
+            result.push(Token::Number(number));
+            index += 1;
+            continue;
+        }
+
+        match word {
+            "plus" | "add" => result.push(Token::Binary(Operator::Plus)),
+            // with nothing to subtract from, "minus five" is negative five
+            "minus" if lexer::expects_operand(result.last()) => {
+                result.push(Token::Unary(Operator::Negative))
+            }
+            "minus" | "subtract" => result.push(Token::Binary(Operator::Minus)),
+            "times" => result.push(Token::Binary(Operator::Multiply)),
+            "over" => result.push(Token::Binary(Operator::Divide)),
+            "modulo" | "mod" => result.push(Token::Binary(Operator::Remainder)),
+            "factorial" => result.push(Token::Unary(Operator::Factorial)),
+            "negative" => result.push(Token::Unary(Operator::Negative)),
+            "squared" => {
+                result.push(Token::Binary(Operator::Power));
+                result.push(Token::Number(2.0));
+            }
+            "cubed" => {
+                result.push(Token::Binary(Operator::Power));
+                result.push(Token::Number(3.0));
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "unknown word in expression: {}",
+                    word
+                )))
+            }
+        }
+        index += 1;
+    }
+
+    // spoken input rarely says "end quantity" at the very end
+    for _ in 0..open_groups {
+        result.push(Token::Right(Group::Parenthesis));
+    }
+
+    Ok(result)
+}
+
+pub fn parse_natural(input: &str) -> anyhow::Result<Expr> {
+    Parser::new(&lex_natural(input)?, &Context::new()).parse()
+}
+
+pub fn evaluate_natural(input: &str) -> anyhow::Result<f64> {
+    parse_natural(input)?.evaluate(&Context::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_natural_works() {
+        let equal_to = vec![
+            Token::Number(2.0),
+            Token::Binary(Operator::Plus),
+            Token::Number(3.0),
+            Token::Binary(Operator::Multiply),
+            Token::Number(4.0),
+        ];
+        assert_eq!(lex_natural("two plus three times four").unwrap(), equal_to);
+    }
+    #[test]
+    fn lex_natural_numbers() {
+        let result = lex_natural("two hundred thirty four thousand five point two five").unwrap();
+        assert_eq!(result, vec![Token::Number(234_005.25)]);
+    }
+    #[test]
+    fn lex_natural_repeated_numbers() {
+        assert!(lex_natural("five five").is_err());
+        assert!(lex_natural("twenty twenty").is_err());
+        assert!(lex_natural("twelve three").is_err());
+        assert!(lex_natural("twenty four five").is_err());
+        let result = lex_natural("ninety nine thousand twenty").unwrap();
+        assert_eq!(result, vec![Token::Number(99_020.0)]);
+    }
+    #[test]
+    fn lex_natural_percent() {
+        let equal_to = vec![
+            Token::Number(20.0),
+            Token::Unary(Operator::Percent),
+            Token::Binary(Operator::Multiply),
+            Token::Number(150.0),
+        ];
+        assert_eq!(lex_natural("20 percent of 150").unwrap(), equal_to);
+        let expr = parse_natural("the quantity two plus three end quantity percent").unwrap();
+        assert_eq!(expr.to_string(), "(2 + 3)%");
+        assert!(lex_natural("percent of 150").is_err());
+    }
+    #[test]
+    fn lex_natural_quantity() {
+        let equal_to = vec![
+            Token::Number(2.0),
+            Token::Binary(Operator::Plus),
+            Token::Left(Group::Parenthesis),
+            Token::Number(3.0),
+            Token::Binary(Operator::Multiply),
+            Token::Number(4.0),
+            Token::Right(Group::Parenthesis),
+        ];
+        let result = lex_natural("two plus the quantity three times four").unwrap();
+        assert_eq!(result, equal_to);
+        assert!(lex_natural("two plus banana").is_err());
+    }
+    #[test]
+    fn evaluate_natural_works() {
+        assert_eq!(evaluate_natural("two plus three times four").unwrap(), 14.0);
+        assert_eq!(evaluate_natural("minus five").unwrap(), -5.0);
+        assert_eq!(evaluate_natural("two times minus three").unwrap(), -6.0);
+        assert_eq!(evaluate_natural("ten minus minus two").unwrap(), 12.0);
+        assert_eq!(evaluate_natural("six minus four").unwrap(), 2.0);
+        assert!(evaluate_natural("two plus").is_err());
+    }
+}
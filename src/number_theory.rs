@@ -0,0 +1,171 @@
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128 * b as u128) % modulus as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, modulus);
+        }
+        base = mul_mod(base, base, modulus);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+pub fn isprime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for prime in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        if n.is_multiple_of(prime) {
+            return n == prime;
+        }
+    }
+
+    // miller-rabin with these bases is deterministic for every u64
+    let (mut d, mut shifts) = (n - 1, 0);
+    while d.is_multiple_of(2) {
+        d /= 2;
+        shifts += 1;
+    }
+    'bases: for base in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        let mut x = pow_mod(base, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..shifts {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'bases;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+pub fn nextprime(n: u64) -> Option<u64> {
+    let mut candidate = n.checked_add(1)?;
+    while !isprime(candidate) {
+        candidate = candidate.checked_add(1)?;
+    }
+    Some(candidate)
+}
+
+// pollard's rho, only called on odd composites
+fn find_divisor(n: u64) -> u64 {
+    for offset in 1.. {
+        let step = |x: u64| ((mul_mod(x, x, n) as u128 + offset) % n as u128) as u64;
+        let (mut slow, mut fast, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            slow = step(slow);
+            fast = step(step(fast));
+            divisor = gcd(slow.abs_diff(fast), n);
+        }
+        if divisor != n {
+            return divisor;
+        }
+    }
+    unreachable!()
+}
+
+pub fn factorint(n: u64) -> Vec<u64> {
+    let mut factors = vec![];
+    let mut pending = vec![];
+
+    let mut n = n;
+    for prime in [2, 3, 5, 7, 11, 13] {
+        while n.is_multiple_of(prime) && n > 1 {
+            factors.push(prime);
+            n /= prime;
+        }
+    }
+    if n > 1 {
+        pending.push(n);
+    }
+
+    while let Some(value) = pending.pop() {
+        if isprime(value) {
+            factors.push(value);
+        } else {
+            let divisor = find_divisor(value);
+            pending.push(divisor);
+            pending.push(value / divisor);
+        }
+    }
+
+    factors.sort();
+    factors
+}
+
+pub fn format_factors(factors: &[u64]) -> String {
+    let mut parts: Vec<String> = vec![];
+    let mut index = 0;
+    while index < factors.len() {
+        let count = factors[index..]
+            .iter()
+            .take_while(|&&factor| factor == factors[index])
+            .count();
+        parts.push(match count {
+            1 => factors[index].to_string(),
+            _ => format!("{}^{}", factors[index], count),
+        });
+        index += count;
+    }
+    parts.join(" * ")
+}
+
+pub fn totient(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    let mut factors = factorint(n);
+    factors.dedup();
+    factors
+        .iter()
+        .fold(n, |result, prime| result / prime * (prime - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isprime_works() {
+        let primes: Vec<u64> = (0..30).filter(|&n| isprime(n)).collect();
+        assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert!(isprime(18_446_744_073_709_551_557));
+        assert!(!isprime(3_215_031_751));
+    }
+    #[test]
+    fn nextprime_works() {
+        assert_eq!(nextprime(0), Some(2));
+        assert_eq!(nextprime(13), Some(17));
+        assert_eq!(nextprime(u64::MAX), None);
+    }
+    #[test]
+    fn factorint_works() {
+        assert_eq!(factorint(360), vec![2, 2, 2, 3, 3, 5]);
+        assert_eq!(factorint(1), Vec::<u64>::new());
+        assert_eq!(factorint(600_851_475_143), vec![71, 839, 1471, 6857]);
+        assert_eq!(factorint(999_999_000_001 * 3), vec![3, 999_999_000_001]);
+        assert_eq!(format_factors(&factorint(360)), "2^3 * 3^2 * 5");
+    }
+    #[test]
+    fn totient_works() {
+        assert_eq!(totient(1), 1);
+        assert_eq!(totient(9), 6);
+        assert_eq!(totient(36), 12);
+        assert_eq!(totient(97), 96);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::{Operator, Token};
+use crate::{Associativity, Context};
+
+// binding powers, spaced out so registered operators can slot in between
+const BINARY_OPERATORS: [(Operator, u8, Associativity); 19] = [
+    (Operator::Convert, 1, Associativity::Left),
+    (Operator::Or, 3, Associativity::Left),
+    (Operator::And, 4, Associativity::Left),
+    (Operator::Approx, 5, Associativity::Left),
+    (Operator::Less, 5, Associativity::Left),
+    (Operator::LessEqual, 5, Associativity::Left),
+    (Operator::Greater, 5, Associativity::Left),
+    (Operator::GreaterEqual, 5, Associativity::Left),
+    (Operator::Equal, 5, Associativity::Left),
+    (Operator::NotEqual, 5, Associativity::Left),
+    (Operator::BitOr, 6, Associativity::Left),
+    (Operator::BitXor, 7, Associativity::Left),
+    (Operator::BitAnd, 8, Associativity::Left),
+    (Operator::Plus, 10, Associativity::Left),
+    (Operator::Minus, 10, Associativity::Left),
+    (Operator::Multiply, 20, Associativity::Left),
+    (Operator::Divide, 20, Associativity::Left),
+    (Operator::Remainder, 20, Associativity::Left),
+    (Operator::Power, 40, Associativity::Right),
+];
+// looser than `^`, so -2^2 is -4, but still allowed in an exponent: 2^-3; `~` binds the same
+pub(crate) const NEGATION: u8 = 30;
+// `not` and `!` take a whole comparison, so `not x > 3` is not (x > 3)
+pub(crate) const NOT: u8 = 5;
+// `!` and `%` as a percent sign
+pub(crate) const POSTFIX: u8 = 50;
+// right associative, so `a ? b : c ? d : e` chooses between b and the second conditional;
+// it is just another way to write `if(condition, then, otherwise)`
+pub(crate) const CONDITIONAL: u8 = 2;
+// how many levels an expression's tree may have, operators in a row included, so that
+// parsing and every walk over the tree after it stay well inside the stack
+pub(crate) const MAX_DEPTH: usize = 200;
+
+fn conditional(condition: Expr, then: Expr, otherwise: Expr, token: usize) -> Expr {
+    Expr::Conditional {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+        token,
+    }
+}
+
+pub(crate) fn builtin_precedence(operator: &Operator) -> Option<(u8, Associativity)> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|(candidate, _, _)| candidate == operator)
+        .map(|(_, precedence, associativity)| (*precedence, *associativity))
+}
+
+// a tree and how many levels deep it goes
+type Parsed = (Expr, usize);
+
+pub(crate) struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    // how many parse_expr calls are open
+    depth: usize,
+    context: &'a Context,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(tokens: &'a [Token], context: &'a Context) -> Parser<'a> {
+        Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+            context,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, kind: ErrorKind, token: Option<usize>) -> anyhow::Error {
+        Error::new(kind, token).into()
+    }
+
+    // the token just consumed by `next`
+    fn last(&self) -> usize {
+        self.position - 1
+    }
+
+    // the whole token stream as one expression
+    pub(crate) fn parse(&mut self) -> anyhow::Result<Expr> {
+        let (expr, _) = self.parse_expr(0)?;
+        if self.peek().is_some() {
+            return Err(self.error(ErrorKind::UnexpectedToken, Some(self.position)));
+        }
+        Ok(expr)
+    }
+
+    // `name = expr` stores into the context, anything else is a bare expression
+    pub(crate) fn parse_statement(&mut self) -> anyhow::Result<(Option<String>, Expr)> {
+        let target = match self.tokens {
+            [Token::Identifier(name), Token::Assign, ..] => {
+                self.position += 2;
+                Some(name.clone())
+            }
+            _ => None,
+        };
+        Ok((target, self.parse()?))
+    }
+
+    // pratt parsing: only operators binding at least as tightly as `minimum` are taken;
+    // it is wider than a precedence so a left associative operator at 255 still has a next level
+    fn parse_expr(&mut self, minimum: u16) -> anyhow::Result<Parsed> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+        self.depth += 1;
+        let parsed = self
+            .parse_prefix()
+            .and_then(|left| self.parse_infix(left, minimum));
+        self.depth -= 1;
+        parsed
+    }
+
+    fn too_deep(&self) -> anyhow::Error {
+        let token = self.position.min(self.tokens.len().saturating_sub(1));
+        self.error(ErrorKind::TooDeep, Some(token))
+    }
+
+    // the height of a node over children this high, an error once it reaches MAX_DEPTH
+    fn over(&self, children: &[usize]) -> anyhow::Result<usize> {
+        let height = children.iter().max().unwrap_or(&0) + 1;
+        if height >= MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+        Ok(height)
+    }
+
+    // the operators after an operand that is already parsed; each one wraps the tree so far,
+    // so a long run of them nests as deeply as parentheses would
+    fn parse_infix(
+        &mut self,
+        (mut left, mut height): Parsed,
+        minimum: u16,
+    ) -> anyhow::Result<Parsed> {
+        loop {
+            match self.peek() {
+                Some(Token::Unary(operator @ (Operator::Factorial | Operator::Percent)))
+                    if u16::from(POSTFIX) >= minimum =>
+                {
+                    self.next();
+                    height = self.over(&[height])?;
+                    left = Expr::Unary {
+                        operator: operator.clone(),
+                        operand: Box::new(left),
+                        token: self.last(),
+                    };
+                }
+                Some(Token::Question) if u16::from(CONDITIONAL) >= minimum => {
+                    self.next();
+                    let token = self.last();
+                    let (then, then_height) = self.parse_expr(0)?;
+                    match self.next() {
+                        Some(Token::Colon) => {}
+                        Some(_) => {
+                            return Err(self.error(ErrorKind::UnexpectedToken, Some(self.last())))
+                        }
+                        None => return Err(self.error(ErrorKind::UnexpectedEnd, None)),
+                    }
+                    let (otherwise, otherwise_height) = self.parse_expr(CONDITIONAL.into())?;
+                    height = self.over(&[height, then_height, otherwise_height])?;
+                    left = conditional(left, then, otherwise, token);
+                }
+                Some(Token::Binary(operator)) => {
+                    let Some((precedence, associativity)) = self.binary_precedence(operator) else {
+                        break;
+                    };
+                    if u16::from(precedence) < minimum {
+                        break;
+                    }
+                    self.next();
+                    let token = self.last();
+                    let next = match associativity {
+                        Associativity::Left => u16::from(precedence) + 1,
+                        Associativity::Right => precedence.into(),
+                    };
+                    let (right, right_height) = self.parse_expr(next)?;
+                    // the percent form below adds two levels
+                    let percent = matches!(operator, Operator::Plus | Operator::Minus)
+                        && matches!(
+                            right,
+                            Expr::Unary {
+                                operator: Operator::Percent,
+                                ..
+                            }
+                        );
+                    let extra = if percent { 2 } else { 0 };
+                    height = self.over(&[height + extra, right_height + extra])?;
+                    left = match (operator, right) {
+                        // `200 + 10%` adds ten percent of 200, so it is read as 200 * (100 + 10) / 100,
+                        // which stays exact where 200 * 1.1 would not
+                        (
+                            Operator::Plus | Operator::Minus,
+                            Expr::Unary {
+                                operator: Operator::Percent,
+                                operand,
+                                ..
+                            },
+                        ) => Expr::Binary {
+                            operator: Operator::Divide,
+                            left: Box::new(Expr::Binary {
+                                operator: Operator::Multiply,
+                                left: Box::new(left),
+                                right: Box::new(Expr::Binary {
+                                    operator: operator.clone(),
+                                    left: Box::new(Expr::Number(100.0)),
+                                    right: operand,
+                                    token,
+                                }),
+                                token,
+                            }),
+                            right: Box::new(Expr::Number(100.0)),
+                            token,
+                        },
+                        (_, right) => Expr::Binary {
+                            operator: operator.clone(),
+                            left: Box::new(left),
+                            right: Box::new(right),
+                            token,
+                        },
+                    };
+                }
+                _ => break,
+            }
+        }
+        Ok((left, height))
+    }
+
+    fn binary_precedence(&self, operator: &Operator) -> Option<(u8, Associativity)> {
+        if let Operator::Custom(symbol) = operator {
+            let registered = self.context.binary_operator(symbol)?;
+            return Some((registered.precedence, registered.associativity));
+        }
+        builtin_precedence(operator)
+    }
+
+    fn parse_prefix(&mut self) -> anyhow::Result<Parsed> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok((Expr::Number(*number), 1)),
+            Some(Token::Constant(constant)) => Ok((Expr::Constant(constant.clone()), 1)),
+            Some(Token::Identifier(name))
+                if name == "piecewise"
+                    && matches!(self.peek(), Some(Token::Left(_)))
+                    && self.context.function(name).is_none() =>
+            {
+                self.parse_piecewise()
+            }
+            // unless the context registers its own if
+            Some(Token::Identifier(name))
+                if name == "if"
+                    && matches!(self.peek(), Some(Token::Left(_)))
+                    && self.context.function(name).is_none() =>
+            {
+                let token = self.last();
+                let (arguments, heights): (Vec<Expr>, Vec<usize>) =
+                    self.parse_arguments()?.into_iter().unzip();
+                match <[Expr; 3]>::try_from(arguments) {
+                    Ok([condition, then, otherwise]) => Ok((
+                        conditional(condition, then, otherwise, token),
+                        self.over(&heights)?,
+                    )),
+                    Err(arguments) => {
+                        let kind = ErrorKind::WrongArgumentCount {
+                            name: name.clone(),
+                            expected: 3,
+                            found: arguments.len(),
+                        };
+                        Err(self.error(kind, Some(token)))
+                    }
+                }
+            }
+            // a name directly followed by a group is a call, otherwise a variable
+            Some(Token::Identifier(name)) if matches!(self.peek(), Some(Token::Left(_))) => {
+                let token = self.last();
+                let (arguments, heights): (Vec<Expr>, Vec<usize>) =
+                    self.parse_arguments()?.into_iter().unzip();
+                let call = Expr::Call {
+                    name: name.clone(),
+                    arguments,
+                    token,
+                };
+                Ok((call, self.over(&heights)?))
+            }
+            Some(Token::Identifier(name)) => Ok((
+                Expr::Variable {
+                    name: name.clone(),
+                    token: self.last(),
+                },
+                1,
+            )),
+            Some(Token::Unary(
+                operator @ (Operator::Negative
+                | Operator::Not
+                | Operator::BitNot
+                | Operator::Custom(_)),
+            )) => {
+                let token = self.last();
+                let precedence = match operator {
+                    Operator::Custom(symbol) => match self.context.unary_operator(symbol) {
+                        Some(registered) => registered.precedence,
+                        None => return Err(self.error(ErrorKind::UnexpectedToken, Some(token))),
+                    },
+                    Operator::Not => NOT,
+                    _ => NEGATION,
+                };
+                let (operand, height) = self.parse_expr(precedence.into())?;
+                let unary = Expr::Unary {
+                    operator: operator.clone(),
+                    operand: Box::new(operand),
+                    token,
+                };
+                Ok((unary, self.over(&[height])?))
+            }
+            Some(Token::Left(_)) => {
+                let parsed = self.parse_expr(0)?;
+                match self.next() {
+                    Some(Token::Right(_)) => Ok(parsed),
+                    Some(_) => Err(self.error(ErrorKind::UnclosedGroup, Some(self.last()))),
+                    None => Err(self.error(ErrorKind::UnclosedGroup, None)),
+                }
+            }
+            Some(_) => Err(self.error(ErrorKind::UnexpectedToken, Some(self.last()))),
+            None => Err(self.error(ErrorKind::UnexpectedEnd, None)),
+        }
+    }
+
+    // `piecewise((c1, v1), (c2, v2), default)` is `c1 ? v1 : c2 ? v2 : default`,
+    // so only the first true condition's value is evaluated
+    fn parse_piecewise(&mut self) -> anyhow::Result<Parsed> {
+        let token = self.last();
+        let mut pieces = vec![];
+        self.next();
+        let (default, default_height) = loop {
+            match self.parse_piece()? {
+                (Some(condition), value) => {
+                    pieces.push((condition, value));
+                    match self.next() {
+                        Some(Token::Comma) => continue,
+                        // the default is missing
+                        Some(Token::Right(_)) => {
+                            return Err(self.error(ErrorKind::UnexpectedToken, Some(self.last())))
+                        }
+                        Some(_) => {
+                            return Err(self.error(ErrorKind::UnclosedGroup, Some(self.last())))
+                        }
+                        None => return Err(self.error(ErrorKind::UnclosedGroup, None)),
+                    }
+                }
+                (None, default) => match self.next() {
+                    Some(Token::Right(_)) => break default,
+                    // a plain value can only come last
+                    Some(Token::Comma) => {
+                        return Err(self.error(ErrorKind::UnexpectedToken, Some(self.last())))
+                    }
+                    Some(_) => return Err(self.error(ErrorKind::UnclosedGroup, Some(self.last()))),
+                    None => return Err(self.error(ErrorKind::UnclosedGroup, None)),
+                },
+            }
+        };
+        if pieces.is_empty() {
+            let kind = ErrorKind::TooFewArguments {
+                name: String::from("piecewise"),
+                minimum: 2,
+                found: 1,
+            };
+            return Err(self.error(kind, Some(token)));
+        }
+        // each piece nests the ones after it
+        let mut height = default_height;
+        let mut expr = default;
+        for ((condition, condition_height), (then, then_height)) in pieces.into_iter().rev() {
+            height = self.over(&[height, condition_height, then_height])?;
+            expr = conditional(condition, then, expr, token);
+        }
+        Ok((expr, height))
+    }
+
+    // a `(condition, value)` pair, or no condition for the plain default; read in one pass,
+    // since going back over a failed pair would parse nested defaults again at every level
+    fn parse_piece(&mut self) -> anyhow::Result<(Option<Parsed>, Parsed)> {
+        if !matches!(self.peek(), Some(Token::Left(_))) {
+            return Ok((None, self.parse_expr(0)?));
+        }
+        self.next();
+        let first = self.parse_expr(0)?;
+        match self.next() {
+            Some(Token::Comma) => {
+                let value = self.parse_expr(0)?;
+                match self.next() {
+                    Some(Token::Right(_)) => Ok((Some(first), value)),
+                    Some(_) => Err(self.error(ErrorKind::UnclosedGroup, Some(self.last()))),
+                    None => Err(self.error(ErrorKind::UnclosedGroup, None)),
+                }
+            }
+            // just a group, which the default goes on from, like `(x + 1) * 2`
+            Some(Token::Right(_)) => Ok((None, self.parse_infix(first, 0)?)),
+            Some(_) => Err(self.error(ErrorKind::UnclosedGroup, Some(self.last()))),
+            None => Err(self.error(ErrorKind::UnclosedGroup, None)),
+        }
+    }
+
+    // a parenthesized, comma separated list, possibly empty
+    fn parse_arguments(&mut self) -> anyhow::Result<Vec<Parsed>> {
+        let mut arguments = vec![];
+        self.next();
+        if let Some(Token::Right(_)) = self.peek() {
+            self.next();
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.parse_expr(0)?);
+            match self.next() {
+                Some(Token::Comma) => {}
+                Some(Token::Right(_)) => return Ok(arguments),
+                Some(_) => return Err(self.error(ErrorKind::UnclosedGroup, Some(self.last()))),
+                None => return Err(self.error(ErrorKind::UnclosedGroup, None)),
+            }
+        }
+    }
+}
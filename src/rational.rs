@@ -0,0 +1,134 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rational {
+    pub numerator: i64,
+    pub denominator: i64,
+}
+
+impl Rational {
+    pub fn value(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+pub fn to_continued_fraction(x: f64, terms: usize) -> Vec<i64> {
+    let mut result = vec![];
+    let mut remainder = x;
+    // running convergent, used to tell when the expansion already reproduces x
+    let (mut h, mut k) = ((0.0, 1.0), (1.0, 0.0));
+
+    for _ in 0..terms {
+        if !remainder.is_finite() || remainder.abs() >= i64::MAX as f64 {
+            break;
+        }
+        let whole = remainder.floor();
+        result.push(whole as i64);
+
+        h = (h.1, whole * h.1 + h.0);
+        k = (k.1, whole * k.1 + k.0);
+        let fraction = remainder - whole;
+        // past this point further terms only describe float noise
+        if fraction == 0.0 || (h.1 / k.1 - x).abs() <= f64::EPSILON * x.abs().max(1.0) {
+            // [.., a, 1] is the same number as [.., a + 1], keep the canonical form
+            if result.len() > 1 && result.last() == Some(&1) {
+                result.pop();
+                *result.last_mut().unwrap() += 1;
+            }
+            break;
+        }
+        remainder = 1.0 / fraction;
+    }
+    result
+}
+
+pub fn best_rational(x: f64, max_den: i64) -> anyhow::Result<Rational> {
+    if !x.is_finite() {
+        return Err(anyhow::Error::msg("cannot approximate a non-finite value"));
+    }
+    // 2^63 itself already saturates to i64::MAX
+    if x.abs() >= i64::MAX as f64 {
+        return Err(anyhow::Error::msg(
+            "value is too large for a 64-bit numerator",
+        ));
+    }
+    if max_den < 1 {
+        return Err(anyhow::Error::msg("max denominator must be at least 1"));
+    }
+
+    // convergents h/k of the continued fraction, (previous, current)
+    let (mut h, mut k) = ((1i64, x.floor() as i64), (0i64, 1i64));
+    for term in to_continued_fraction(x, 64).into_iter().skip(1) {
+        let next_k = term.saturating_mul(k.1).saturating_add(k.0);
+        if next_k > max_den {
+            // the best semiconvergent that still fits may beat the last convergent
+            let steps = (max_den - k.0) / k.1;
+            let semi = Rational {
+                numerator: h.0 + steps * h.1,
+                denominator: k.0 + steps * k.1,
+            };
+            let last = Rational {
+                numerator: h.1,
+                denominator: k.1,
+            };
+            if steps > 0 && (semi.value() - x).abs() < (last.value() - x).abs() {
+                return Ok(semi);
+            }
+            return Ok(last);
+        }
+        h = (h.1, term.saturating_mul(h.1).saturating_add(h.0));
+        k = (k.1, next_k);
+    }
+
+    Ok(Rational {
+        numerator: h.1,
+        denominator: k.1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continued_fraction_works() {
+        assert_eq!(to_continued_fraction(3.245, 10), vec![3, 4, 12, 4]);
+        assert_eq!(
+            to_continued_fraction(std::f64::consts::PI, 4),
+            vec![3, 7, 15, 1]
+        );
+        assert_eq!(to_continued_fraction(-0.5, 5), vec![-1, 2]);
+    }
+    #[test]
+    fn best_rational_works() {
+        let pi = best_rational(3.14159292, 1000).unwrap();
+        assert_eq!(pi.to_string(), "355/113");
+        assert_eq!(
+            best_rational(std::f64::consts::PI, 100)
+                .unwrap()
+                .to_string(),
+            "311/99"
+        );
+        assert_eq!(best_rational(0.75, 100).unwrap().to_string(), "3/4");
+        assert_eq!(best_rational(2.0, 10).unwrap().to_string(), "2");
+        assert!(best_rational(f64::NAN, 10).is_err());
+        assert!(best_rational(f64::INFINITY, 10).is_err());
+        assert!(best_rational(1e19, 10).is_err());
+        assert!(best_rational(-9.3e18, 10).is_err());
+        assert!(best_rational(i64::MAX as f64, 10).is_err());
+        assert_eq!(
+            best_rational(9e18, 10).unwrap().to_string(),
+            "9000000000000000000"
+        );
+    }
+}
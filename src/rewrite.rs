@@ -0,0 +1,732 @@
+use std::collections::HashMap;
+
+use crate::assume::{self, Assumption};
+use crate::ast::Expr;
+use crate::lexer::Operator;
+use crate::Context;
+
+// a rule that rewrites its own output would never settle, so passes stop here
+const MAX_PASSES: usize = 64;
+
+// identities over the reals
+const DEFAULT_RULES: [(&str, &str); 21] = [
+    ("x + 0", "x"),
+    ("0 + x", "x"),
+    ("x - 0", "x"),
+    ("0 - x", "-x"),
+    ("x + -y", "x - y"),
+    ("x - -y", "x + y"),
+    ("--x", "x"),
+    ("-(x - y)", "y - x"),
+    ("x * 1", "x"),
+    ("1 * x", "x"),
+    ("x / 1", "x"),
+    ("x ^ 1", "x"),
+    ("x + x", "2 * x"),
+    ("a * x + x", "(a + 1) * x"),
+    ("a * x + b * x", "(a + b) * x"),
+    ("a * x - b * x", "(a - b) * x"),
+    ("x * x", "x ^ 2"),
+    ("ln(exp(x))", "x"),
+    ("sqrt(x ^ 2)", "abs(x)"),
+    ("abs(-x)", "abs(x)"),
+    ("abs(abs(x))", "abs(x)"),
+];
+
+// only true under their conditions, so they apply when the context's assumptions show those;
+// tried ahead of the unconditional rules, which handle the general case; the ones dropping
+// an operand need it finite, or they would turn an error, NaN or infinity into a number
+const CONDITIONAL_RULES: [(&str, &str, &[&str]); 14] = [
+    ("x - x", "0", &["x is finite"]),
+    ("x * 0", "0", &["x is finite"]),
+    ("0 * x", "0", &["x is finite"]),
+    ("x ^ 0", "1", &["x is finite"]),
+    ("1 ^ x", "1", &["x is finite"]),
+    ("sqrt(x ^ 2)", "x", &["x >= 0"]),
+    ("sqrt(x) ^ 2", "x", &["x >= 0"]),
+    ("abs(x)", "x", &["x >= 0"]),
+    ("abs(x)", "-x", &["x <= 0"]),
+    ("exp(ln(x))", "x", &["x > 0"]),
+    ("x / x", "1", &["x is finite", "x != 0"]),
+    // 0^-1 * 0 and infinity^-1 * infinity are NaN, not 0^0 or infinity^0
+    ("x ^ a * x", "x ^ (a + 1)", &["x is finite", "x != 0"]),
+    ("x ^ a * x ^ b", "x ^ (a + b)", &["x is finite", "x != 0"]),
+    ("(x ^ a) ^ b", "x ^ (a * b)", &["x > 0"]),
+];
+
+// these hold in degrees as well as radians; sums are matched as written, hence both orders
+const TRIGONOMETRIC_RULES: [(&str, &str); 11] = [
+    ("1 - sin(x) ^ 2", "cos(x) ^ 2"),
+    ("1 - cos(x) ^ 2", "sin(x) ^ 2"),
+    ("sin(x) / cos(x)", "tan(x)"),
+    ("2 * sin(x) * cos(x)", "sin(2 * x)"),
+    ("cos(x) ^ 2 - sin(x) ^ 2", "cos(2 * x)"),
+    ("sin(-x)", "-sin(x)"),
+    ("cos(-x)", "cos(x)"),
+    ("tan(-x)", "-tan(x)"),
+    ("sin(0)", "0"),
+    ("cos(0)", "1"),
+    ("tan(0)", "0"),
+];
+
+// these drop x, which like the conditional rules above must then be finite:
+// sin(1 / 0)^2 + cos(1 / 0)^2 is an error and sin(inf)^2 + cos(inf)^2 is NaN
+const CONDITIONAL_TRIGONOMETRIC_RULES: [(&str, &str, &[&str]); 4] = [
+    ("sin(x) ^ 2 + cos(x) ^ 2", "1", &["x is finite"]),
+    ("cos(x) ^ 2 + sin(x) ^ 2", "1", &["x is finite"]),
+    ("a + sin(x) ^ 2 + cos(x) ^ 2", "a + 1", &["x is finite"]),
+    ("a + cos(x) ^ 2 + sin(x) ^ 2", "a + 1", &["x is finite"]),
+];
+
+// groups of rules a rewriter can be built from; the default rewriter has all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulePack {
+    // the identities over the reals, and the ones that need assumptions
+    Algebraic,
+    Trigonometric,
+}
+
+// every variable in the pattern stands for any subexpression, the same one wherever it repeats
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pattern: Expr,
+    replacement: Expr,
+    // assumptions about what the pattern variables matched
+    conditions: Vec<Assumption>,
+}
+
+fn variables<'a>(expr: &'a Expr, names: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Number(_) | Expr::Constant(_) => {}
+        Expr::Variable { name, .. } => names.push(name),
+        Expr::Unary { operand, .. } => variables(operand, names),
+        Expr::Binary { left, right, .. } => {
+            variables(left, names);
+            variables(right, names);
+        }
+        Expr::Call { arguments, .. } => {
+            for argument in arguments {
+                variables(argument, names);
+            }
+        }
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            variables(condition, names);
+            variables(then, names);
+            variables(otherwise, names);
+        }
+    }
+}
+
+impl Rule {
+    pub fn new(pattern: &str, replacement: &str) -> anyhow::Result<Rule> {
+        Rule::with_conditions(pattern, replacement, &[])
+    }
+
+    // `sqrt(x^2)` to `x` given `x >= 0`: each condition is an assumption about a pattern variable
+    pub fn with_conditions(
+        pattern: &str,
+        replacement: &str,
+        conditions: &[&str],
+    ) -> anyhow::Result<Rule> {
+        // folded like the expressions they are matched against, so `(-1)^n` has a literal -1
+        let pattern = Rewriter::new().rewrite(&crate::parse(pattern)?);
+        let replacement = crate::parse(replacement)?;
+        let conditions = conditions
+            .iter()
+            .map(|condition| Assumption::parse(condition))
+            .collect::<anyhow::Result<Vec<Assumption>>>()?;
+        let (mut bound, mut used) = (vec![], vec![]);
+        variables(&pattern, &mut bound);
+        variables(&replacement, &mut used);
+        used.extend(conditions.iter().map(|condition| condition.name.as_str()));
+        if let Some(name) = used.iter().find(|name| !bound.contains(name)) {
+            return Err(anyhow::Error::msg(format!(
+                "{} is in the replacement or a condition but not in the pattern",
+                name
+            )));
+        }
+        Ok(Rule {
+            pattern,
+            replacement,
+            conditions,
+        })
+    }
+
+    // the assumptions that meet every condition, none when one of them can't be shown
+    fn applies(&self, bindings: &HashMap<String, &Expr>, context: &Context) -> Option<Vec<usize>> {
+        let mut used = vec![];
+        for condition in &self.conditions {
+            used.extend(assume::prove(
+                bindings[&condition.name],
+                condition.property,
+                context,
+            )?);
+        }
+        Some(used)
+    }
+}
+
+// the same tree, wherever it was parsed from
+pub(crate) fn same(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Number(a), Expr::Number(b)) => a == b,
+        (Expr::Constant(a), Expr::Constant(b)) => a == b,
+        (Expr::Variable { name: a, .. }, Expr::Variable { name: b, .. }) => a == b,
+        (
+            Expr::Unary {
+                operator: a,
+                operand: a_operand,
+                ..
+            },
+            Expr::Unary {
+                operator: b,
+                operand: b_operand,
+                ..
+            },
+        ) => a == b && same(a_operand, b_operand),
+        (
+            Expr::Binary {
+                operator: a,
+                left: a_left,
+                right: a_right,
+                ..
+            },
+            Expr::Binary {
+                operator: b,
+                left: b_left,
+                right: b_right,
+                ..
+            },
+        ) => a == b && same(a_left, b_left) && same(a_right, b_right),
+        (
+            Expr::Call {
+                name: a,
+                arguments: a_arguments,
+                ..
+            },
+            Expr::Call {
+                name: b,
+                arguments: b_arguments,
+                ..
+            },
+        ) => {
+            a == b
+                && a_arguments.len() == b_arguments.len()
+                && a_arguments.iter().zip(b_arguments).all(|(a, b)| same(a, b))
+        }
+        (
+            Expr::Conditional {
+                condition: a_condition,
+                then: a_then,
+                otherwise: a_otherwise,
+                ..
+            },
+            Expr::Conditional {
+                condition: b_condition,
+                then: b_then,
+                otherwise: b_otherwise,
+                ..
+            },
+        ) => {
+            same(a_condition, b_condition) && same(a_then, b_then) && same(a_otherwise, b_otherwise)
+        }
+        _ => false,
+    }
+}
+
+fn match_pattern<'a>(
+    pattern: &Expr,
+    expr: &'a Expr,
+    bindings: &mut HashMap<String, &'a Expr>,
+) -> bool {
+    match (pattern, expr) {
+        (Expr::Variable { name, .. }, _) => match bindings.get(name) {
+            Some(bound) => same(bound, expr),
+            None => {
+                bindings.insert(name.clone(), expr);
+                true
+            }
+        },
+        (
+            Expr::Unary {
+                operator: a,
+                operand: pattern,
+                ..
+            },
+            Expr::Unary {
+                operator: b,
+                operand: expr,
+                ..
+            },
+        ) => a == b && match_pattern(pattern, expr, bindings),
+        (
+            Expr::Binary {
+                operator: a,
+                left: pattern_left,
+                right: pattern_right,
+                ..
+            },
+            Expr::Binary {
+                operator: b,
+                left,
+                right,
+                ..
+            },
+        ) => {
+            a == b
+                && match_pattern(pattern_left, left, bindings)
+                && match_pattern(pattern_right, right, bindings)
+        }
+        (
+            Expr::Call {
+                name: a,
+                arguments: patterns,
+                ..
+            },
+            Expr::Call {
+                name: b, arguments, ..
+            },
+        ) => {
+            a == b
+                && patterns.len() == arguments.len()
+                && patterns
+                    .iter()
+                    .zip(arguments)
+                    .all(|(pattern, argument)| match_pattern(pattern, argument, bindings))
+        }
+        (
+            Expr::Conditional {
+                condition: pattern_condition,
+                then: pattern_then,
+                otherwise: pattern_otherwise,
+                ..
+            },
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                ..
+            },
+        ) => {
+            match_pattern(pattern_condition, condition, bindings)
+                && match_pattern(pattern_then, then, bindings)
+                && match_pattern(pattern_otherwise, otherwise, bindings)
+        }
+        _ => same(pattern, expr),
+    }
+}
+
+// nodes the replacement adds take the token of the node they replace, so errors still point somewhere sensible
+fn instantiate(replacement: &Expr, bindings: &HashMap<String, &Expr>, token: usize) -> Expr {
+    let instantiate = |expr: &Expr| Box::new(instantiate(expr, bindings, token));
+    match replacement {
+        Expr::Variable { name, .. } => bindings[name].clone(),
+        Expr::Unary {
+            operator, operand, ..
+        } => Expr::Unary {
+            operator: operator.clone(),
+            operand: instantiate(operand),
+            token,
+        },
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => Expr::Binary {
+            operator: operator.clone(),
+            left: instantiate(left),
+            right: instantiate(right),
+            token,
+        },
+        Expr::Call {
+            name, arguments, ..
+        } => Expr::Call {
+            name: name.clone(),
+            arguments: arguments
+                .iter()
+                .map(|argument| *instantiate(argument))
+                .collect(),
+            token,
+        },
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => Expr::Conditional {
+            condition: instantiate(condition),
+            then: instantiate(then),
+            otherwise: instantiate(otherwise),
+            token,
+        },
+        _ => replacement.clone(),
+    }
+}
+
+fn token_of(expr: &Expr) -> usize {
+    match expr {
+        Expr::Variable { token, .. }
+        | Expr::Unary { token, .. }
+        | Expr::Binary { token, .. }
+        | Expr::Call { token, .. }
+        | Expr::Conditional { token, .. } => *token,
+        Expr::Number(_) | Expr::Constant(_) => 0,
+    }
+}
+
+// arithmetic on two numbers becomes its result, unless that is an error or not finite;
+// comparisons are left alone so predicates stay predicates
+fn fold(expr: Expr) -> Expr {
+    let foldable = match &expr {
+        Expr::Unary {
+            operator: Operator::Negative,
+            operand,
+            ..
+        } => matches!(**operand, Expr::Number(_)),
+        Expr::Binary {
+            operator:
+                Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Remainder
+                | Operator::Power,
+            left,
+            right,
+            ..
+        } => matches!((&**left, &**right), (Expr::Number(_), Expr::Number(_))),
+        _ => false,
+    };
+    if !foldable {
+        return expr;
+    }
+    match expr.evaluate(&Context::new()) {
+        Ok(value) if value.is_finite() => Expr::Number(value),
+        _ => expr,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rewriter {
+    rules: Vec<Rule>,
+}
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        Rewriter::with_packs(&[RulePack::Algebraic, RulePack::Trigonometric])
+    }
+}
+
+impl Rewriter {
+    // no rules at all, numbers are still folded
+    pub fn new() -> Rewriter {
+        Rewriter { rules: vec![] }
+    }
+
+    pub fn with_packs(packs: &[RulePack]) -> Rewriter {
+        let mut rewriter = Rewriter::new();
+        for pack in packs {
+            rewriter.add_pack(*pack);
+        }
+        rewriter
+    }
+
+    // tried after the rules already added; conditional rules go ahead of the general case they refine
+    pub fn add_pack(&mut self, pack: RulePack) {
+        let rules = |rules: &[(&str, &str)]| {
+            rules
+                .iter()
+                .map(|(pattern, replacement)| Rule::new(pattern, replacement).unwrap())
+                .collect::<Vec<Rule>>()
+        };
+        let conditional = |rules: &[(&str, &str, &[&str])]| {
+            rules
+                .iter()
+                .map(|(pattern, replacement, conditions)| {
+                    Rule::with_conditions(pattern, replacement, conditions).unwrap()
+                })
+                .collect::<Vec<Rule>>()
+        };
+        match pack {
+            RulePack::Algebraic => {
+                self.rules.extend(conditional(&CONDITIONAL_RULES));
+                self.rules.extend(rules(&DEFAULT_RULES));
+            }
+            RulePack::Trigonometric => {
+                self.rules
+                    .extend(conditional(&CONDITIONAL_TRIGONOMETRIC_RULES));
+                self.rules.extend(rules(&TRIGONOMETRIC_RULES));
+            }
+        }
+    }
+
+    // tried after the ones already added
+    pub fn add_rule(&mut self, pattern: &str, replacement: &str) -> anyhow::Result<()> {
+        self.rules.push(Rule::new(pattern, replacement)?);
+        Ok(())
+    }
+
+    pub fn add_conditional_rule(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        conditions: &[&str],
+    ) -> anyhow::Result<()> {
+        self.rules
+            .push(Rule::with_conditions(pattern, replacement, conditions)?);
+        Ok(())
+    }
+
+    // children first, then the first rule that matches the node itself;
+    // `relied` collects the assumptions behind every conditional rule applied
+    fn pass(&self, expr: &Expr, context: &Context, relied: &mut Vec<usize>) -> Expr {
+        let mut pass = |expr: &Expr| Box::new(self.pass(expr, context, relied));
+        let expr = fold(match expr {
+            Expr::Unary {
+                operator,
+                operand,
+                token,
+            } => Expr::Unary {
+                operator: operator.clone(),
+                operand: pass(operand),
+                token: *token,
+            },
+            Expr::Binary {
+                operator,
+                left,
+                right,
+                token,
+            } => Expr::Binary {
+                operator: operator.clone(),
+                left: pass(left),
+                right: pass(right),
+                token: *token,
+            },
+            Expr::Call {
+                name,
+                arguments,
+                token,
+            } => Expr::Call {
+                name: name.clone(),
+                arguments: arguments.iter().map(|argument| *pass(argument)).collect(),
+                token: *token,
+            },
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+                token,
+            } => Expr::Conditional {
+                condition: pass(condition),
+                then: pass(then),
+                otherwise: pass(otherwise),
+                token: *token,
+            },
+            _ => expr.clone(),
+        });
+        for rule in &self.rules {
+            let mut bindings = HashMap::new();
+            if !match_pattern(&rule.pattern, &expr, &mut bindings) {
+                continue;
+            }
+            if let Some(used) = rule.applies(&bindings, context) {
+                relied.extend(used);
+                return instantiate(&rule.replacement, &bindings, token_of(&expr));
+            }
+        }
+        expr
+    }
+
+    // until no rule applies anywhere; with no assumptions, conditional rules never apply
+    pub fn rewrite(&self, expr: &Expr) -> Expr {
+        self.rewrite_with(expr, &Context::new()).0
+    }
+
+    // also the context's assumptions the result relied on, in the order they were made
+    pub fn rewrite_with(&self, expr: &Expr, context: &Context) -> (Expr, Vec<Assumption>) {
+        let mut expr = expr.clone();
+        let mut relied = vec![];
+        for _ in 0..MAX_PASSES {
+            let next = self.pass(&expr, context, &mut relied);
+            if same(&next, &expr) {
+                break;
+            }
+            expr = next;
+        }
+        relied.sort_unstable();
+        relied.dedup();
+        let relied = relied
+            .into_iter()
+            .map(|index| context.assumptions()[index].clone())
+            .collect();
+        (expr, relied)
+    }
+}
+
+pub fn simplify(expression: &str) -> anyhow::Result<Expr> {
+    Ok(simplify_with(expression, &Context::new())?.0)
+}
+
+pub fn simplify_with(
+    expression: &str,
+    context: &Context,
+) -> anyhow::Result<(Expr, Vec<Assumption>)> {
+    let expr = crate::parse_with(expression, context)?;
+    Ok(Rewriter::default().rewrite_with(&expr, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simplified(expression: &str) -> String {
+        simplify(expression).unwrap().to_string()
+    }
+
+    #[test]
+    fn simplify_works() {
+        assert_eq!(simplified("2x + 3x"), "5 * x");
+        assert_eq!(simplified("a*y + b*y"), "(a + b) * y");
+        assert_eq!(simplified("(x + 0) * 1 - -y"), "x + y");
+        assert_eq!(simplified("x * x * x"), "x^2 * x");
+        assert_eq!(simplified("x^-1 * x"), "x^(-1) * x");
+        assert_eq!(
+            simplified("0 * sin(t) + ln(exp(t + t))"),
+            "0 * sin(t) + 2 * t"
+        );
+        // dropping an operand that might fail or overflow would hide that it does
+        assert_eq!(simplified("(1 / 0) * 0 + (x - x)"), "1 / 0 * 0 + (x - x)");
+        assert_eq!(simplified("y^0 + 1^y + 3 * 0"), "y^0 + 1^y");
+        assert_eq!(simplified("sqrt((1 - x)^2) + 2^10"), "abs(1 - x) + 1024");
+        assert_eq!(simplified("1 / 0 + 2 * 3"), "1 / 0 + 6");
+        assert_eq!(simplified("1 < 2"), "1 < 2");
+        for rule in DEFAULT_RULES {
+            assert!(Rule::new(rule.0, rule.1).is_ok());
+        }
+        for rule in CONDITIONAL_RULES {
+            assert!(Rule::with_conditions(rule.0, rule.1, rule.2).is_ok());
+        }
+    }
+    #[test]
+    fn trigonometric_rules() {
+        let mut context = Context::new();
+        context.assume("x is finite").unwrap();
+        context.assume("t is finite").unwrap();
+        let simplified = |expression| simplify_with(expression, &context).unwrap().0.to_string();
+        assert_eq!(simplified("sin(x)^2 + cos(x)^2 + 1"), "2");
+        assert_eq!(simplified("1 + cos(t)^2 + sin(t)^2"), "2");
+        assert_eq!(simplified("2 sin(a) cos(a)"), "sin(2 * a)");
+        assert_eq!(simplified("cos(3x)^2 - sin(3x)^2"), "cos(2 * (3 * x))");
+        assert_eq!(simplified("sin(x) / cos(x) + sin(-x)"), "tan(x) - sin(x)");
+        assert_eq!(simplified("1 - cos(y)^2 + cos(-0)"), "sin(y)^2 + 1");
+        // the identity drops x, so without knowing it is finite it stays
+        assert_eq!(simplified("sin(y)^2 + cos(y)^2"), "sin(y)^2 + cos(y)^2");
+        assert_eq!(
+            simplified("sin(1 / 0)^2 + cos(1 / 0)^2"),
+            "sin(1 / 0)^2 + cos(1 / 0)^2"
+        );
+        assert_eq!(
+            simplified("sin(1e400)^2 + cos(1e400)^2"),
+            "sin(inf)^2 + cos(inf)^2"
+        );
+
+        let algebraic = Rewriter::with_packs(&[RulePack::Algebraic]);
+        let expr = crate::parse("sin(x)^2 + cos(x)^2 + y * 1").unwrap();
+        assert_eq!(
+            algebraic.rewrite_with(&expr, &context).0.to_string(),
+            "sin(x)^2 + cos(x)^2 + y"
+        );
+        let mut trigonometric = Rewriter::new();
+        trigonometric.add_pack(RulePack::Trigonometric);
+        assert_eq!(
+            trigonometric.rewrite_with(&expr, &context).0.to_string(),
+            "1 + y * 1"
+        );
+        for rule in CONDITIONAL_TRIGONOMETRIC_RULES {
+            assert!(Rule::with_conditions(rule.0, rule.1, rule.2).is_ok());
+        }
+    }
+    #[test]
+    fn simplify_with_assumptions() {
+        let mut context = Context::new();
+        context.assume("x > 0").unwrap();
+        context.assume("y <= 0").unwrap();
+        context.assume("n is integer").unwrap();
+        context.assume("f is finite").unwrap();
+        context.assume("x is finite").unwrap();
+        let simplified = |expression| {
+            let (expr, relied) = simplify_with(expression, &context).unwrap();
+            let relied: Vec<String> = relied
+                .iter()
+                .map(|assumption| assumption.to_string())
+                .collect();
+            (expr.to_string(), relied.join(", "))
+        };
+        assert_eq!(
+            simplified("sqrt(x^2)"),
+            (String::from("x"), String::from("x > 0"))
+        );
+        assert_eq!(
+            simplified("sqrt(z^2)"),
+            (String::from("abs(z)"), String::new())
+        );
+        assert_eq!(
+            simplified("abs(y - x) + (y^2)^0.5"),
+            (
+                String::from("x - y + (y^2)^0.5"),
+                String::from("x > 0, y <= 0")
+            )
+        );
+        assert_eq!(
+            simplified("x / x"),
+            (String::from("1"), String::from("x > 0, x is finite"))
+        );
+        assert_eq!(simplified("z / z"), (String::from("z / z"), String::new()));
+        assert_eq!(
+            simplified("x * x * x^-1"),
+            (String::from("x"), String::from("x > 0, x is finite"))
+        );
+        assert_eq!(simplified("f / f"), (String::from("f / f"), String::new()));
+        assert_eq!(
+            simplified("abs(y^2 + y)"),
+            (String::from("abs(y^2 + y)"), String::new())
+        );
+        assert_eq!(
+            simplified("f * 0 + (f - f) + f^0 + (n - n)"),
+            (String::from("1"), String::from("n is integer, f is finite"))
+        );
+
+        let mut rewriter = Rewriter::new();
+        rewriter
+            .add_conditional_rule("(-1)^(2m)", "1", &["m is integer"])
+            .unwrap();
+        let expr = crate::parse("(-1)^(2n) + (-1)^(2z)").unwrap();
+        let (expr, relied) = rewriter.rewrite_with(&expr, &context);
+        assert_eq!(expr.to_string(), "1 + (-1)^(2 * z)");
+        assert_eq!(relied, vec![context.assumptions()[2].clone()]);
+        assert!(rewriter.add_conditional_rule("x", "x", &["y > 0"]).is_err());
+        assert!(rewriter.add_conditional_rule("x", "x", &["x > 1"]).is_err());
+    }
+    #[test]
+    fn custom_rules() {
+        let mut rewriter = Rewriter::new();
+        rewriter.add_rule("sin(x)^2 + cos(x)^2", "1").unwrap();
+        rewriter.add_rule("log(10^x)", "x").unwrap();
+        let expr = crate::parse("sin(2t)^2 + cos(2t)^2 + log(10^(y + 0))").unwrap();
+        assert_eq!(rewriter.rewrite(&expr).to_string(), "1 + (y + 0)");
+        assert!(Rewriter::new().add_rule("x + y", "z").is_err());
+        assert!(Rewriter::new().add_rule("x +", "x").is_err());
+
+        // never settles, but stops
+        let mut rewriter = Rewriter::new();
+        rewriter.add_rule("a + b", "b + a").unwrap();
+        let expr = crate::parse("1 + x").unwrap();
+        assert_eq!(rewriter.rewrite(&expr).to_string(), "1 + x");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::lexer::{self, Operator, Token};
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+fn hundreds_words(number: u64) -> Vec<&'static str> {
+    let mut words = vec![];
+    let (hundreds, rest) = (number / 100, number % 100);
+    if hundreds > 0 {
+        words.push(ONES[hundreds as usize]);
+        words.push("hundred");
+    }
+    if rest >= 20 {
+        words.push(TENS[(rest / 10) as usize]);
+        if rest % 10 > 0 {
+            words.push(ONES[(rest % 10) as usize]);
+        }
+    } else if rest > 0 || hundreds == 0 {
+        words.push(ONES[rest as usize]);
+    }
+    words
+}
+
+fn integer_words(mut number: u64) -> String {
+    if number == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut groups = vec![];
+    let mut scale = 0;
+    while number > 0 {
+        let group = number % 1000;
+        if group > 0 {
+            let mut words = hundreds_words(group);
+            if scale > 0 {
+                words.push(SCALES[scale]);
+            }
+            groups.push(words.join(" "));
+        }
+        number /= 1000;
+        scale += 1;
+    }
+    groups.reverse();
+    groups.join(" ")
+}
+
+fn number_words(number: f64) -> String {
+    let written = number.to_string();
+    // anything past u64 is read out as written rather than guessing at scale names
+    let (integer, fraction) = written.split_once('.').unwrap_or((&written, ""));
+    let mut words = match integer.parse::<u64>() {
+        Ok(integer) => integer_words(integer),
+        Err(_) => return written,
+    };
+
+    if !fraction.is_empty() {
+        words += " point";
+        for digit in fraction.chars().filter_map(|char| char.to_digit(10)) {
+            words += " ";
+            words += ONES[digit as usize];
+        }
+    }
+    words
+}
+
+fn operator_words(operator: &Operator) -> &str {
+    match operator {
+        Operator::Plus => "plus",
+        Operator::Minus => "minus",
+        Operator::Multiply => "times",
+        Operator::Divide => "divided by",
+        Operator::Remainder => "modulo",
+        Operator::Power => "to the power of",
+        Operator::Factorial => "factorial",
+        Operator::Percent => "percent",
+        Operator::Negative => "negative",
+        Operator::Approx => "is approximately",
+        Operator::Less => "is less than",
+        Operator::LessEqual => "is at most",
+        Operator::Greater => "is greater than",
+        Operator::GreaterEqual => "is at least",
+        Operator::Equal => "equals",
+        Operator::NotEqual => "does not equal",
+        Operator::And => "and",
+        Operator::Or => "or",
+        Operator::Not => "not",
+        Operator::BitAnd => "bitwise and",
+        Operator::BitOr => "bitwise or",
+        Operator::BitXor => "xor",
+        Operator::BitNot => "bitwise not",
+        Operator::Convert => "in",
+        Operator::Custom(symbol) => symbol,
+    }
+}
+
+pub fn to_speech(expression: &str) -> anyhow::Result<String> {
+    let tokens = lexer::lex(expression)?;
+    let mut words: Vec<String> = vec![];
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Number(number) => words.push(number_words(*number)),
+            Token::Identifier(name) => words.push(name.clone()),
+            Token::Constant(_) => words.push(token.to_string()),
+            Token::Unary(operator) | Token::Binary(operator) => {
+                words.push(operator_words(operator).to_string())
+            }
+            Token::Left(_) => words.push("the quantity".to_string()),
+            // a trailing close needs no marker, anywhere else it tells the listener the group is over
+            Token::Right(_) if index + 1 < tokens.len() => words.push("end quantity".to_string()),
+            Token::Right(_) => {}
+            Token::Assign => words.push("equals".to_string()),
+            Token::Comma => words.push("comma".to_string()),
+            Token::Question => words.push("then".to_string()),
+            Token::Colon => words.push("otherwise".to_string()),
+        }
+    }
+
+    Ok(words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_words_works() {
+        assert_eq!(number_words(0.0), "zero");
+        assert_eq!(number_words(42.0), "forty two");
+        assert_eq!(number_words(1005.0), "one thousand five");
+        assert_eq!(
+            number_words(2_300_115.0),
+            "two million three hundred thousand one hundred fifteen"
+        );
+        assert_eq!(number_words(2.75), "two point seven five");
+    }
+    #[test]
+    fn to_speech_works() {
+        assert_eq!(
+            to_speech("2 + (3 * 4)").unwrap(),
+            "two plus the quantity three times four"
+        );
+        assert_eq!(
+            to_speech("(2 + 3) * 4").unwrap(),
+            "the quantity two plus three end quantity times four"
+        );
+        assert_eq!(
+            to_speech("5! / 2").unwrap(),
+            "five factorial divided by two"
+        );
+    }
+}
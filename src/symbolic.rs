@@ -0,0 +1,835 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, One, Signed, ToPrimitive, Zero};
+
+use crate::ast::Expr;
+use crate::error::ErrorKind;
+use crate::exact::{self, MAX_DIGITS, MAX_EXPONENT};
+use crate::lexer::{Constant, Operator, Token};
+use crate::Context;
+
+// radicands are reduced by trial division up to here, a larger repeated prime stays under the root
+const MAX_TRIAL_FACTOR: u64 = 100_000;
+// products of sums grow quickly, (pi + 1)^10000 would never finish
+const MAX_TERMS: usize = 1000;
+// digits computed past the ones asked for, to absorb rounding along the way
+const GUARD_DIGITS: u64 = 10;
+// the most digits approx() gives, more would take seconds
+const MAX_APPROX_DIGITS: u64 = 10_000;
+
+fn gcd(mut a: BigInt, mut b: BigInt) -> BigInt {
+    while !b.is_zero() {
+        let remainder = &a % &b;
+        a = b;
+        b = remainder;
+    }
+    a.abs()
+}
+
+// n as outside^2 * inside
+fn square_free(mut n: BigInt) -> (BigInt, BigInt) {
+    let mut outside = BigInt::one();
+    for factor in 2..=MAX_TRIAL_FACTOR {
+        let factor = BigInt::from(factor);
+        let square = &factor * &factor;
+        if square > n {
+            break;
+        }
+        while (&n % &square).is_zero() {
+            n /= &square;
+            outside *= &factor;
+        }
+    }
+    // what is left may still be the square of a large prime
+    let root = n.sqrt();
+    if &root * &root == n {
+        return (outside * root, BigInt::one());
+    }
+    (outside, n)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Fraction {
+    numerator: BigInt,
+    // positive, and sharing no factor with the numerator
+    denominator: BigInt,
+}
+
+impl Fraction {
+    fn new(numerator: BigInt, denominator: BigInt) -> Fraction {
+        let mut divisor = gcd(numerator.clone(), denominator.clone());
+        if denominator.is_negative() {
+            divisor = -divisor;
+        }
+        Fraction {
+            numerator: numerator / &divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    fn integer(value: impl Into<BigInt>) -> Fraction {
+        Fraction {
+            numerator: value.into(),
+            denominator: BigInt::one(),
+        }
+    }
+
+    // `1e-99999999999` is a fine decimal, but not as a fraction of whole numbers
+    fn from_decimal(value: &BigDecimal) -> anyhow::Result<Fraction> {
+        let (digits, scale) = value.as_bigint_and_exponent();
+        if scale.unsigned_abs() > MAX_DIGITS {
+            return Err(exact::unsupported("a number this large or this small"));
+        }
+        let ten = BigInt::from(10);
+        Ok(if scale >= 0 {
+            Fraction::new(digits, ten.pow(scale as u32))
+        } else {
+            Fraction::integer(digits * ten.pow(scale.unsigned_abs() as u32))
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    fn whole(&self, token: usize) -> anyhow::Result<BigInt> {
+        if !self.denominator.is_one() {
+            return Err(exact::error(ErrorKind::NotAnInteger, token));
+        }
+        Ok(self.numerator.clone())
+    }
+
+    fn add(&self, other: &Fraction) -> Fraction {
+        Fraction::new(
+            &self.numerator * &other.denominator + &other.numerator * &self.denominator,
+            &self.denominator * &other.denominator,
+        )
+    }
+
+    fn multiply(&self, other: &Fraction) -> Fraction {
+        Fraction::new(
+            &self.numerator * &other.numerator,
+            &self.denominator * &other.denominator,
+        )
+    }
+
+    fn abs(&self) -> Fraction {
+        Fraction {
+            numerator: self.numerator.abs(),
+            denominator: self.denominator.clone(),
+        }
+    }
+
+    // the exact decimal, when the denominator has no factors but 2 and 5
+    fn terminating(&self) -> Option<BigDecimal> {
+        let mut rest = self.denominator.clone();
+        let mut places = 0;
+        for factor in [2, 5] {
+            let factor = BigInt::from(factor);
+            let mut count = 0;
+            while (&rest % &factor).is_zero() {
+                rest /= &factor;
+                count += 1;
+            }
+            places = places.max(count);
+        }
+        if !rest.is_one() {
+            return None;
+        }
+        let scaled = &self.numerator * BigInt::from(10).pow(places) / &self.denominator;
+        Some(BigDecimal::new(scaled, places as i64).normalized())
+    }
+
+    fn approx(&self, precision: &bigdecimal::Context) -> BigDecimal {
+        BigDecimal::new(self.numerator.clone(), 0)
+            * BigDecimal::new(self.denominator.clone(), 0).inverse_with_context(precision)
+    }
+}
+
+// the denominators are positive, so cross multiplying keeps the order
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Fraction) -> Option<Ordering> {
+        (&self.numerator * &other.denominator).partial_cmp(&(&other.numerator * &self.denominator))
+    }
+}
+
+// pi^pi * e^e * sqrt(root), with a square-free root
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Monomial {
+    pi: i64,
+    e: i64,
+    root: BigInt,
+}
+
+impl Monomial {
+    fn one() -> Monomial {
+        Monomial {
+            pi: 0,
+            e: 0,
+            root: BigInt::one(),
+        }
+    }
+
+    fn is_one(&self) -> bool {
+        self.pi == 0 && self.e == 0 && self.root.is_one()
+    }
+
+    // the rational term first, then by powers of pi, of e and the root
+    fn order(&self, other: &Monomial) -> Ordering {
+        (!self.is_one(), self.pi, self.e, &self.root).cmp(&(
+            !other.is_one(),
+            other.pi,
+            other.e,
+            &other.root,
+        ))
+    }
+
+    // the product, and the whole factor that comes out of the roots: sqrt(6) * sqrt(10) is 2 * sqrt(15)
+    fn multiply(&self, other: &Monomial) -> (Monomial, BigInt) {
+        let common = gcd(self.root.clone(), other.root.clone());
+        let root = (&self.root / &common) * (&other.root / &common);
+        let monomial = Monomial {
+            pi: self.pi + other.pi,
+            e: self.e + other.e,
+            root,
+        };
+        (monomial, common)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    coefficient: Fraction,
+    monomial: Monomial,
+}
+
+// an exact value, the sum of rational multiples of powers of pi and e and of square roots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbolic {
+    // in display order, with distinct monomials and no zero coefficients, so zero has no terms
+    terms: Vec<Term>,
+}
+
+impl Symbolic {
+    fn from_terms(mut terms: Vec<Term>) -> Symbolic {
+        terms.sort_by(|a, b| a.monomial.order(&b.monomial));
+        let mut merged: Vec<Term> = vec![];
+        for term in terms {
+            match merged.last_mut() {
+                Some(last) if last.monomial == term.monomial => {
+                    last.coefficient = last.coefficient.add(&term.coefficient)
+                }
+                _ => merged.push(term),
+            }
+        }
+        merged.retain(|term| !term.coefficient.is_zero());
+        Symbolic { terms: merged }
+    }
+
+    fn term(coefficient: Fraction, monomial: Monomial) -> Symbolic {
+        Symbolic::from_terms(vec![Term {
+            coefficient,
+            monomial,
+        }])
+    }
+
+    fn rational(value: Fraction) -> Symbolic {
+        Symbolic::term(value, Monomial::one())
+    }
+
+    fn constant(pi: i64, e: i64) -> Symbolic {
+        let monomial = Monomial {
+            pi,
+            e,
+            root: BigInt::one(),
+        };
+        Symbolic::term(Fraction::integer(1), monomial)
+    }
+
+    fn fraction(&self) -> Option<Fraction> {
+        match &self.terms[..] {
+            [] => Some(Fraction::integer(0)),
+            [term] if term.monomial.is_one() => Some(term.coefficient.clone()),
+            _ => None,
+        }
+    }
+
+    fn add(&self, other: &Symbolic) -> Symbolic {
+        Symbolic::from_terms(self.terms.iter().chain(&other.terms).cloned().collect())
+    }
+
+    fn negate(&self) -> Symbolic {
+        let terms = self
+            .terms
+            .iter()
+            .map(|term| Term {
+                coefficient: Fraction {
+                    numerator: -&term.coefficient.numerator,
+                    denominator: term.coefficient.denominator.clone(),
+                },
+                monomial: term.monomial.clone(),
+            })
+            .collect();
+        Symbolic { terms }
+    }
+
+    fn multiply(&self, other: &Symbolic) -> anyhow::Result<Symbolic> {
+        let mut terms = Vec::with_capacity(self.terms.len() * other.terms.len());
+        for a in &self.terms {
+            for b in &other.terms {
+                let (monomial, factor) = a.monomial.multiply(&b.monomial);
+                let coefficient = a
+                    .coefficient
+                    .multiply(&b.coefficient)
+                    .multiply(&Fraction::integer(factor));
+                terms.push(Term {
+                    coefficient,
+                    monomial,
+                });
+            }
+        }
+        let product = Symbolic::from_terms(terms);
+        if product.terms.len() > MAX_TERMS {
+            return Err(exact::unsupported("a result with this many terms"));
+        }
+        Ok(product)
+    }
+
+    fn reciprocal(&self, token: usize) -> anyhow::Result<Symbolic> {
+        let term = match &self.terms[..] {
+            [] => return Err(exact::error(ErrorKind::DivisionByZero, token)),
+            [term] => term,
+            _ => {
+                return Err(exact::unsupported(
+                    "dividing by a sum with irrational terms",
+                ))
+            }
+        };
+        // 1 / sqrt(r) is sqrt(r) / r
+        let coefficient = Fraction::new(
+            term.coefficient.denominator.clone(),
+            &term.coefficient.numerator * &term.monomial.root,
+        );
+        let monomial = Monomial {
+            pi: -term.monomial.pi,
+            e: -term.monomial.e,
+            root: term.monomial.root.clone(),
+        };
+        Ok(Symbolic::term(coefficient, monomial))
+    }
+
+    // whole and half-integer exponents, the latter through the square root
+    fn power(&self, exponent: &Fraction, token: usize) -> anyhow::Result<Symbolic> {
+        if !exponent.denominator.is_one() && exponent.denominator != BigInt::from(2) {
+            return Err(exact::unsupported(
+                "an exponent other than a whole or half number",
+            ));
+        }
+        let twice = exponent.multiply(&Fraction::integer(2));
+        let twice = match twice.numerator.to_i64() {
+            Some(twice) if twice.unsigned_abs() <= 2 * MAX_EXPONENT as u64 => twice,
+            _ => return Err(exact::unsupported("a very large exponent")),
+        };
+        let (base, exponent) = if twice % 2 == 0 {
+            (self.clone(), twice / 2)
+        } else {
+            (self.sqrt()?, twice)
+        };
+        // like exact powers, the exponent alone can't bound the size of `(9^999)^999`
+        if base.digits().saturating_mul(exponent.unsigned_abs()) > MAX_DIGITS {
+            return Err(exact::unsupported("a power this large"));
+        }
+        let mut square = if exponent < 0 {
+            base.reciprocal(token)?
+        } else {
+            base
+        };
+        let mut result = Symbolic::rational(Fraction::integer(1));
+        let mut remaining = exponent.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.multiply(&square)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                square = square.multiply(&square)?;
+            }
+        }
+        Ok(result)
+    }
+
+    // about as many decimal digits as the longest number in any term
+    fn digits(&self) -> u64 {
+        let bits = self
+            .terms
+            .iter()
+            .map(|term| {
+                let Fraction {
+                    numerator,
+                    denominator,
+                } = &term.coefficient;
+                numerator.bits() + denominator.bits() + term.monomial.root.bits()
+            })
+            .max()
+            .unwrap_or(0);
+        bits * 3 / 10 + 1
+    }
+
+    // only of a single term with even powers of pi and e
+    fn sqrt(&self) -> anyhow::Result<Symbolic> {
+        let term = match &self.terms[..] {
+            [] => return Ok(self.clone()),
+            [term]
+                if term.monomial.root.is_one()
+                    && term.monomial.pi % 2 == 0
+                    && term.monomial.e % 2 == 0 =>
+            {
+                term
+            }
+            _ => return Err(exact::unsupported("this square root")),
+        };
+        if term.coefficient.numerator.is_negative() {
+            return Err(exact::unsupported("the square root of a negative number"));
+        }
+        // sqrt(p / q) is sqrt(p * q) / q
+        let (outside, root) =
+            square_free(&term.coefficient.numerator * &term.coefficient.denominator);
+        let monomial = Monomial {
+            pi: term.monomial.pi / 2,
+            e: term.monomial.e / 2,
+            root,
+        };
+        Ok(Symbolic::term(
+            Fraction::new(outside, term.coefficient.denominator.clone()),
+            monomial,
+        ))
+    }
+
+    fn abs(&self) -> anyhow::Result<Symbolic> {
+        // pi, e and roots are positive, so a single term has the sign of its coefficient
+        match &self.terms[..] {
+            [term] if term.coefficient.numerator.is_negative() => Ok(self.negate()),
+            [] | [_] => Ok(self.clone()),
+            _ => Err(exact::unsupported("abs() of a sum with irrational terms")),
+        }
+    }
+
+    // the value to `digits` significant digits; terms that nearly cancel can cost some of them
+    pub fn approx(&self, digits: u64) -> BigDecimal {
+        let digits = digits.clamp(1, MAX_APPROX_DIGITS);
+        let working = digits + GUARD_DIGITS;
+        let precision = bigdecimal::Context::default()
+            .with_prec(working)
+            .unwrap_or_default();
+        let uses = |exponent: fn(&Monomial) -> i64| {
+            self.terms.iter().any(|term| exponent(&term.monomial) != 0)
+        };
+        let pi = uses(|monomial| monomial.pi).then(|| pi(working));
+        let e = uses(|monomial| monomial.e).then(|| e(working));
+
+        let mut sum = BigDecimal::zero();
+        for term in &self.terms {
+            let mut value = term.coefficient.approx(&precision);
+            for (constant, exponent) in [(&pi, term.monomial.pi), (&e, term.monomial.e)] {
+                if let (Some(constant), true) = (constant, exponent != 0) {
+                    value *= constant.powi_with_context(exponent, &precision);
+                }
+            }
+            if !term.monomial.root.is_one() {
+                let scaled = &term.monomial.root * BigInt::from(10).pow(2 * working as u32);
+                value *= BigDecimal::new(scaled.sqrt(), working as i64);
+            }
+            sum += value.with_prec(working);
+        }
+        sum.with_prec(digits).normalized()
+    }
+}
+
+// arctan(1 / x), scaled by `scale`
+fn arctan_inverse(x: u64, scale: &BigInt) -> BigInt {
+    let square = BigInt::from(x * x);
+    let mut power = scale / x;
+    let mut sum = BigInt::zero();
+    let mut k = 0u64;
+    while !power.is_zero() {
+        let term = &power / (2 * k + 1);
+        if k.is_multiple_of(2) {
+            sum += term;
+        } else {
+            sum -= term;
+        }
+        power /= &square;
+        k += 1;
+    }
+    sum
+}
+
+// by machin's formula, to `places` decimal places
+fn pi(places: u64) -> BigDecimal {
+    let scale = BigInt::from(10).pow(places as u32);
+    let pi = arctan_inverse(5, &scale) * 16 - arctan_inverse(239, &scale) * 4;
+    BigDecimal::new(pi, places as i64)
+}
+
+// the sum of 1 / k!, to `places` decimal places
+fn e(places: u64) -> BigDecimal {
+    let mut term = BigInt::from(10).pow(places as u32);
+    let mut sum = BigInt::zero();
+    let mut k = 1u64;
+    while !term.is_zero() {
+        sum += &term;
+        term /= k;
+        k += 1;
+    }
+    BigDecimal::new(sum, places as i64)
+}
+
+fn write_term(f: &mut fmt::Formatter, coefficient: &Fraction, monomial: &Monomial) -> fmt::Result {
+    if monomial.is_one() {
+        return match coefficient.terminating() {
+            Some(decimal) => write!(f, "{}", decimal),
+            None => write!(f, "{} / {}", coefficient.numerator, coefficient.denominator),
+        };
+    }
+    let mut above = vec![];
+    let mut below = vec![];
+    if !coefficient.numerator.is_one() {
+        above.push(coefficient.numerator.to_string());
+    }
+    if !coefficient.denominator.is_one() {
+        below.push(coefficient.denominator.to_string());
+    }
+    for (name, exponent) in [("pi", monomial.pi), ("e", monomial.e)] {
+        let power = match exponent.unsigned_abs() {
+            1 => name.to_string(),
+            magnitude => format!("{}^{}", name, magnitude),
+        };
+        match exponent.cmp(&0) {
+            Ordering::Greater => above.push(power),
+            Ordering::Less => below.push(power),
+            Ordering::Equal => {}
+        }
+    }
+    if !monomial.root.is_one() {
+        above.push(format!("sqrt({})", monomial.root));
+    }
+    if above.is_empty() {
+        above.push(String::from("1"));
+    }
+    write!(f, "{}", above.join(" * "))?;
+    match &below[..] {
+        [] => Ok(()),
+        [single] => write!(f, " / {}", single),
+        _ => write!(f, " / ({})", below.join(" * ")),
+    }
+}
+
+// in the expression syntax, so results can be parsed back
+impl fmt::Display for Symbolic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (index, term) in self.terms.iter().enumerate() {
+            let negative = term.coefficient.numerator.is_negative();
+            match (index, negative) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            write_term(f, &term.coefficient.abs(), &term.monomial)?;
+        }
+        Ok(())
+    }
+}
+
+fn number(value: f64) -> anyhow::Result<Symbolic> {
+    Ok(Symbolic::rational(Fraction::from_decimal(
+        &exact::decimal(value)?,
+    )?))
+}
+
+fn boolean(holds: bool) -> Symbolic {
+    Symbolic::rational(Fraction::integer(holds as u8))
+}
+
+// comparisons, logic and bitwise operators only take rationals: whether a sum of
+// powers of pi and e is zero is not something exact arithmetic can decide
+fn rational(value: &Symbolic) -> anyhow::Result<Fraction> {
+    value
+        .fraction()
+        .ok_or_else(|| exact::unsupported("comparing or testing an irrational value"))
+}
+
+fn operator_name(operator: &Operator) -> String {
+    Token::Binary(operator.clone()).to_string()
+}
+
+fn evaluate_expr(expr: &Expr, context: &Context) -> anyhow::Result<Symbolic> {
+    match expr {
+        Expr::Number(value) => number(*value),
+        Expr::Constant(Constant::Pi) => Ok(Symbolic::constant(1, 0)),
+        Expr::Constant(Constant::Tau) => Symbolic::constant(1, 0).multiply(&number(2.0)?),
+        Expr::Constant(Constant::E) => Ok(Symbolic::constant(0, 1)),
+        Expr::Variable { name, .. } if name.starts_with('#') => Ok(Symbolic::rational(
+            Fraction::from_decimal(&exact::literal(name)?)?,
+        )),
+        Expr::Variable { name, token } => match context.get(name) {
+            Some(value) => number(value),
+            None => Err(exact::error(
+                ErrorKind::UnknownVariable(name.clone()),
+                *token,
+            )),
+        },
+        Expr::Unary {
+            operator: Operator::Negative,
+            operand,
+            ..
+        } => Ok(evaluate_expr(operand, context)?.negate()),
+        Expr::Unary {
+            operator: Operator::Percent,
+            operand,
+            ..
+        } => evaluate_expr(operand, context)?.multiply(&number(0.01)?),
+        Expr::Unary {
+            operator: Operator::Factorial,
+            operand,
+            token,
+        } => match evaluate_expr(operand, context)?.fraction() {
+            Some(value) if value.denominator.is_one() => {
+                let value = BigDecimal::new(value.numerator, 0);
+                Ok(Symbolic::rational(Fraction::from_decimal(
+                    &exact::factorial(&value, *token)?,
+                )?))
+            }
+            _ => Err(exact::error(ErrorKind::InvalidFactorial, *token)),
+        },
+        Expr::Binary {
+            operator:
+                operator @ (Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Remainder
+                | Operator::Power),
+            left,
+            right,
+            token,
+        } => {
+            let left = evaluate_expr(left, context)?;
+            let right = evaluate_expr(right, context)?;
+            match operator {
+                Operator::Plus => Ok(left.add(&right)),
+                Operator::Minus => Ok(left.add(&right.negate())),
+                Operator::Multiply => left.multiply(&right),
+                Operator::Divide => left.multiply(&right.reciprocal(*token)?),
+                Operator::Power => match right.fraction() {
+                    Some(exponent) => left.power(&exponent, *token),
+                    None => Err(exact::unsupported("an irrational exponent")),
+                },
+                // truncated like f64's %
+                _ => match (left.fraction(), right.fraction()) {
+                    (Some(_), Some(divisor)) if divisor.is_zero() => {
+                        Err(exact::error(ErrorKind::DivisionByZero, *token))
+                    }
+                    (Some(dividend), Some(divisor)) => {
+                        let quotient = (&dividend.numerator * &divisor.denominator)
+                            / (&dividend.denominator * &divisor.numerator);
+                        let whole = divisor.multiply(&Fraction::integer(-quotient));
+                        Ok(Symbolic::rational(dividend.add(&whole)))
+                    }
+                    _ => Err(exact::unsupported("% of irrational values")),
+                },
+            }
+        }
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } => {
+            let builtin = matches!(name.as_str(), "sqrt" | "abs" | "exp");
+            if !builtin || context.function(name).is_some() {
+                return Err(exact::unsupported(&format!("{}()", name)));
+            }
+            let [argument] = &arguments[..] else {
+                let kind = ErrorKind::WrongArgumentCount {
+                    name: name.clone(),
+                    expected: 1,
+                    found: arguments.len(),
+                };
+                return Err(exact::error(kind, *token));
+            };
+            let argument = evaluate_expr(argument, context)?;
+            match name.as_str() {
+                "sqrt" => argument.sqrt(),
+                "abs" => argument.abs(),
+                _ => match argument.fraction() {
+                    Some(exponent) => Symbolic::constant(0, 1).power(&exponent, *token),
+                    None => Err(exact::unsupported("exp() of an irrational value")),
+                },
+            }
+        }
+        Expr::Unary {
+            operator: Operator::Not,
+            operand,
+            ..
+        } => Ok(boolean(
+            rational(&evaluate_expr(operand, context)?)?.is_zero(),
+        )),
+        Expr::Unary {
+            operator: Operator::BitNot,
+            operand,
+            token,
+        } => {
+            let operand = rational(&evaluate_expr(operand, context)?)?.whole(*token)?;
+            Ok(Symbolic::rational(Fraction::integer(!operand)))
+        }
+        Expr::Unary { operator, .. } => Err(exact::unsupported(&format!(
+            "the {} operator",
+            operator_name(operator)
+        ))),
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            token,
+        } => {
+            let left = rational(&evaluate_expr(left, context)?)?;
+            match operator {
+                Operator::And if left.is_zero() => return Ok(boolean(false)),
+                Operator::Or if !left.is_zero() => return Ok(boolean(true)),
+                _ => {}
+            }
+            let right = rational(&evaluate_expr(right, context)?)?;
+            match operator {
+                Operator::And | Operator::Or => Ok(boolean(!right.is_zero())),
+                Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                    let (left, right) = (left.whole(*token)?, right.whole(*token)?);
+                    let bits = operator.bitwise(left, right).unwrap_or_default();
+                    Ok(Symbolic::rational(Fraction::integer(bits)))
+                }
+                comparison if comparison.is_comparison() => Ok(boolean(
+                    comparison.compare(&left, &right).unwrap_or_default(),
+                )),
+                _ => Err(exact::unsupported(&format!(
+                    "the {} operator",
+                    operator_name(operator)
+                ))),
+            }
+        }
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            if rational(&evaluate_expr(condition, context)?)?.is_zero() {
+                return evaluate_expr(otherwise, context);
+            }
+            evaluate_expr(then, context)
+        }
+    }
+}
+
+pub fn evaluate_symbolic(expression: &str) -> anyhow::Result<Symbolic> {
+    evaluate_symbolic_with(expression, &Context::new())
+}
+
+// exact arithmetic that keeps pi, e and square roots as they are, `pi / 4` or `sqrt(2) / 2`,
+// and fractions that don't terminate as fractions; approx() gives as many digits as asked for
+pub fn evaluate_symbolic_with(expression: &str, context: &Context) -> anyhow::Result<Symbolic> {
+    let (expr, spans) = crate::parse_literals(expression, context)?;
+    evaluate_expr(&expr, context).map_err(|error| crate::framed(error, expression, &spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn symbolic(expression: &str) -> String {
+        evaluate_symbolic(expression).unwrap().to_string()
+    }
+
+    fn approx(expression: &str, digits: u64) -> String {
+        evaluate_symbolic(expression)
+            .unwrap()
+            .approx(digits)
+            .to_string()
+    }
+
+    #[test]
+    fn evaluate_symbolic_works() {
+        assert_eq!(symbolic("pi / 4"), "pi / 4");
+        assert_eq!(symbolic("1 / sqrt(2)"), "sqrt(2) / 2");
+        assert_eq!(symbolic("e^2"), "e^2");
+        assert_eq!(symbolic("sqrt(8) * sqrt(6)"), "4 * sqrt(3)");
+        assert_eq!(symbolic("(1 + sqrt(2))^2"), "3 + 2 * sqrt(2)");
+        assert_eq!(symbolic("tau / 8 - pi / 4"), "0");
+        assert_eq!(symbolic("2 / (3pi)"), "2 / (3 * pi)");
+        assert_eq!(symbolic("1 / 3 + 0.5"), "5 / 6");
+        assert_eq!(symbolic("0.1 + 0.2"), "0.3");
+        assert_eq!(symbolic("2^100"), "1267650600228229401496703205376");
+        assert_eq!(symbolic("sqrt(0.5) - exp(1) + 3!"), "6 + sqrt(2) / 2 - e");
+        assert_eq!(symbolic("sqrt(4pi^2) * 2^-0.5"), "pi * sqrt(2)");
+        assert_eq!(symbolic("abs(-pi) * (7.5 % 2)"), "3 * pi / 2");
+        assert_eq!(symbolic("-e / pi^2"), "-e / pi^2");
+
+        let mut context = Context::new();
+        context.set("x", 2.0);
+        let result = evaluate_symbolic_with("x > 0 ? -pi : pi", &context).unwrap();
+        assert_eq!(result.to_string(), "-pi");
+        // conditions are decided on the same exact values
+        assert_eq!(symbolic("1/3 * 3 == 1"), "1");
+        assert_eq!(symbolic("1/3 * 3 == 1 ? pi : e"), "pi");
+        assert_eq!(symbolic("2/3 < 0.6667 and not 1/7 * 7 != 1"), "1");
+        assert_eq!(symbolic("sqrt(2)^2 >= 2 or 1 / 0"), "1");
+        assert_eq!(symbolic("~6 & 0xff xor 1"), "248");
+    }
+    #[test]
+    fn approx_works() {
+        assert_eq!(approx("pi", 30), "3.14159265358979323846264338328");
+        assert_eq!(approx("e", 20), "2.7182818284590452354");
+        assert_eq!(approx("sqrt(2) / 2", 10), "0.7071067812");
+        assert_eq!(approx("e^2 - pi", 6), "4.24746");
+        assert_eq!(approx("1 / 3", 5), "0.33333");
+        assert_eq!(approx("pi - pi", 5), "0");
+        let third = evaluate_symbolic("1 / 3").unwrap().approx(u64::MAX);
+        assert_eq!(third.digits(), MAX_APPROX_DIGITS);
+        let root = evaluate_symbolic("pi + e + sqrt(2)").unwrap();
+        assert_eq!(root.approx(u64::MAX).digits(), MAX_APPROX_DIGITS);
+    }
+    #[test]
+    fn evaluate_symbolic_errors() {
+        assert!(evaluate_symbolic("sin(1)").is_err());
+        assert!(evaluate_symbolic("1 / (1 + sqrt(2))").is_err());
+        assert!(evaluate_symbolic("2^pi").is_err());
+        assert!(evaluate_symbolic("2^(1/3)").is_err());
+        assert!(evaluate_symbolic("sqrt(-1)").is_err());
+        assert!(evaluate_symbolic("sqrt(pi)").is_err());
+        assert!(evaluate_symbolic("pi > 3").is_err());
+        assert!(evaluate_symbolic("sqrt(2) ? 1 : 0").is_err());
+        assert!(evaluate_symbolic("not e").is_err());
+        let kind = |expression| {
+            evaluate_symbolic(expression)
+                .unwrap_err()
+                .downcast_ref::<Error>()
+                .map(|error| error.kind.clone())
+        };
+        assert!(evaluate_symbolic("(9^999)^999").is_err());
+        assert!(evaluate_symbolic("((1 + sqrt(2))^999)^999").is_err());
+        assert!(evaluate_symbolic("1e-99999999999").is_err());
+        assert_eq!(symbolic("(2^100)^100").len(), 3011);
+        assert_eq!(kind("pi!"), Some(ErrorKind::InvalidFactorial));
+        assert_eq!(kind("1/2 | 1"), Some(ErrorKind::NotAnInteger));
+        assert_eq!(kind("1 / (pi - pi)"), Some(ErrorKind::DivisionByZero));
+        let error = evaluate_symbolic("1 / (pi - pi)").unwrap_err().to_string();
+        assert_eq!(
+            error.lines().take(3).collect::<Vec<_>>(),
+            ["division by zero", "  | 1 / (pi - pi)", "  |   ^"]
+        );
+    }
+}
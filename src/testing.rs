@@ -0,0 +1,281 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::{lexer, Context};
+
+// every `<name>.txt` in the corpus is an input, its result is stored next to it as `<name>.snap`
+const INPUT_EXTENSION: &str = "txt";
+const SNAPSHOT_EXTENSION: &str = "snap";
+const CALC_EXTENSION: &str = "calc";
+
+fn render(input: &str, context: &Context) -> String {
+    let options = lexer::LexOptions {
+        symbols: &context.operator_symbols(),
+        max_literal_length: context.options.max_literal_length,
+        conversions: false,
+    };
+    let tokens = match lexer::lex_with(input, &options) {
+        Ok(tokens) => format!("tokens: {:?}\n", tokens),
+        Err(error) => return format!("error: {}\n", error),
+    };
+    match crate::evaluate_with(input, context) {
+        Ok(value) => format!("{}value: {}\n", tokens, value),
+        Err(error) => format!("{}error: {}\n", tokens, error),
+    }
+}
+
+fn files_with_extension(dir: &Path, wanted: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut inputs = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == wanted)
+        {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+// what to do about an input that has no snapshot yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record {
+    // a missing snapshot is a failure, and the corpus is left as it is
+    Never,
+    // a missing snapshot is written from the current result, so new entries only need the input file
+    Missing,
+}
+
+pub fn replay<P: AsRef<Path>>(corpus_dir: P) -> anyhow::Result<()> {
+    replay_with(corpus_dir, &Context::new(), Record::Never)
+}
+
+// every input is lexed and evaluated with the context's variables, functions and operators
+pub fn replay_with<P: AsRef<Path>>(
+    corpus_dir: P,
+    context: &Context,
+    record: Record,
+) -> anyhow::Result<()> {
+    let mut failures = vec![];
+
+    for input_path in files_with_extension(corpus_dir.as_ref(), INPUT_EXTENSION)? {
+        let input = fs::read_to_string(&input_path)?;
+        let snapshot_path = input_path.with_extension(SNAPSHOT_EXTENSION);
+
+        // a panic leaves nothing half updated, the context is only read
+        let rendered =
+            match panic::catch_unwind(AssertUnwindSafe(|| render(input.trim_end(), context))) {
+                Ok(rendered) => rendered,
+                Err(_) => {
+                    failures.push(format!("{}: panicked", input_path.display()));
+                    continue;
+                }
+            };
+
+        if !snapshot_path.exists() {
+            match record {
+                Record::Never => failures.push(format!(
+                    "{}: no snapshot, got {:?}",
+                    input_path.display(),
+                    rendered.trim_end()
+                )),
+                Record::Missing => fs::write(&snapshot_path, &rendered)?,
+            }
+            continue;
+        }
+
+        let snapshot = fs::read_to_string(&snapshot_path)?;
+        if snapshot != rendered {
+            failures.push(format!(
+                "{}: expected {:?}, got {:?}",
+                input_path.display(),
+                snapshot.trim_end(),
+                rendered.trim_end()
+            ));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::Error::msg(failures.join("\n")))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CalcReport {
+    pub passed: usize,
+    // `file:line: message` for every failed check
+    pub failures: Vec<String>,
+}
+
+fn close(value: f64, expected: f64) -> bool {
+    value == expected || (value - expected).abs() <= 1e-9 * expected.abs().max(1.0)
+}
+
+// checks a single `.calc` line, none when it is a plain statement with nothing to compare
+fn check_line(line: &str, context: &mut Context) -> Option<Result<(), String>> {
+    let (statement, expected) = match line.split_once("# =>") {
+        Some((statement, expected)) => (statement.trim(), Some(expected.trim())),
+        None => (line.split('#').next().unwrap_or_default().trim(), None),
+    };
+    if statement.is_empty() {
+        return None;
+    }
+
+    // `assert x` passes when x evaluates to anything but zero
+    if let Some(condition) = statement.strip_prefix("assert ") {
+        return Some(match crate::execute(condition, context) {
+            Ok(value) if value != 0.0 => Ok(()),
+            Ok(_) => Err(format!("assertion failed: {}", condition.trim())),
+            Err(error) => Err(format!("error: {}", error)),
+        });
+    }
+
+    let result = crate::execute(statement, context);
+    let expected = expected?;
+    Some(match (result, expected.strip_prefix("error")) {
+        (Err(error), Some(message)) => {
+            let message = message.trim_start_matches(':').trim();
+            if error.to_string().starts_with(message) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected error {:?}, got {:?}",
+                    message,
+                    error.to_string()
+                ))
+            }
+        }
+        (Ok(value), Some(_)) => Err(format!("expected an error, got {}", value)),
+        (Err(error), None) => Err(format!("error: {}", error)),
+        (Ok(value), None) => match crate::evaluate(expected) {
+            Ok(wanted) if close(value, wanted) => Ok(()),
+            Ok(wanted) => Err(format!("expected {}, got {}", wanted, value)),
+            Err(error) => Err(format!("bad expectation {:?}: {}", expected, error)),
+        },
+    })
+}
+
+// each line runs against one context for the whole file, so assignments carry over
+pub fn run_calc(source: &str) -> Vec<(usize, Result<(), String>)> {
+    let mut context = Context::new();
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| Some((index + 1, check_line(line, &mut context)?)))
+        .collect()
+}
+
+pub fn run_calc_dir<P: AsRef<Path>>(dir: P) -> anyhow::Result<CalcReport> {
+    let mut report = CalcReport::default();
+
+    for path in files_with_extension(dir.as_ref(), CALC_EXTENSION)? {
+        let source = fs::read_to_string(&path)?;
+        for (line, outcome) in run_calc(&source) {
+            match outcome {
+                Ok(()) => report.passed += 1,
+                Err(message) => {
+                    report
+                        .failures
+                        .push(format!("{}:{}: {}", path.display(), line, message))
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("solver-corpus-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replay_records_then_passes() {
+        let dir = corpus("records");
+        fs::write(dir.join("sum.txt"), "(53+110)\n").unwrap();
+        fs::write(dir.join("bad.txt"), "5 $ 3").unwrap();
+
+        // nothing is written unless asked for, and a missing snapshot fails
+        let error = replay(&dir).unwrap_err().to_string();
+        assert!(error.contains("sum.txt: no snapshot"));
+        assert!(!dir.join("sum.snap").exists());
+
+        replay_with(&dir, &Context::new(), Record::Missing).unwrap();
+        assert!(dir.join("sum.snap").exists());
+        assert!(dir.join("bad.snap").exists());
+        replay(&dir).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn replay_detects_changes() {
+        let dir = corpus("changes");
+        fs::write(dir.join("number.txt"), "5.3").unwrap();
+        fs::write(
+            dir.join("number.snap"),
+            "tokens: [Number(5.4)]\nvalue: 5.4\n",
+        )
+        .unwrap();
+
+        let error = replay(&dir).unwrap_err().to_string();
+        assert!(error.contains("number.txt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn replay_with_context() {
+        let dir = corpus("context");
+        fs::write(dir.join("rate.txt"), "rate * 2").unwrap();
+        fs::write(
+            dir.join("rate.snap"),
+            "tokens: [Identifier(\"rate\"), Binary(Multiply), Number(2.0)]\nvalue: 1\n",
+        )
+        .unwrap();
+
+        let mut context = Context::new();
+        context.set("rate", 0.5);
+        replay_with(&dir, &context, Record::Never).unwrap();
+        assert!(replay(&dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn run_calc_works() {
+        let source = "# rates\nrate = 0.5 # => 1/2\nrate * 4 # => 2\n\nassert rate\n1 / 0 # => error: division by zero\n3 # => 4\nassert rate - 0.5\n";
+        let outcomes = run_calc(source);
+        let lines: Vec<usize> = outcomes.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![2, 3, 5, 6, 7, 8]);
+        assert!(outcomes[..4].iter().all(|(_, outcome)| outcome.is_ok()));
+        assert_eq!(outcomes[4].1, Err(String::from("expected 4, got 3")));
+        assert_eq!(
+            outcomes[5].1,
+            Err(String::from("assertion failed: rate - 0.5"))
+        );
+    }
+    #[test]
+    fn run_calc_dir_works() {
+        let dir = corpus("calc");
+        fs::write(dir.join("good.calc"), "2 + 2 # => 4\n").unwrap();
+        fs::write(dir.join("bad.calc"), "x # => 1\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "x # => 1\n").unwrap();
+
+        let report = run_calc_dir(&dir).unwrap();
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].contains("bad.calc:1: error: unknown variable x"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,606 @@
+use std::fmt;
+
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::Operator;
+use crate::parser::Parser;
+use crate::Context;
+
+const BASE_UNITS: [&str; 6] = ["m", "kg", "s", "A", "K", "mol"];
+
+// exponents of the base units, in the order of BASE_UNITS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension(pub [i8; 6]);
+
+impl Dimension {
+    pub fn is_dimensionless(&self) -> bool {
+        self.0 == [0; 6]
+    }
+
+    // `sign` is 1 when multiplying and -1 when dividing
+    fn combine(self, other: Dimension, sign: i8) -> Option<Dimension> {
+        let mut exponents = self.0;
+        for (exponent, other) in exponents.iter_mut().zip(other.0) {
+            *exponent = exponent.checked_add(other.checked_mul(sign)?)?;
+        }
+        Some(Dimension(exponents))
+    }
+
+    // only whole powers of units come out, so m^2 has a square root and m does not
+    fn scale(self, power: f64) -> Option<Dimension> {
+        // any power of a plain number is one, even an infinite or NaN power
+        if self.is_dimensionless() {
+            return Some(self);
+        }
+        let mut exponents = self.0;
+        for exponent in exponents.iter_mut() {
+            let scaled = *exponent as f64 * power;
+            if scaled.fract() != 0.0 || scaled.abs() > i8::MAX as f64 {
+                return None;
+            }
+            *exponent = scaled as i8;
+        }
+        Some(Dimension(exponents))
+    }
+
+    fn describe(&self) -> String {
+        if self.is_dimensionless() {
+            String::from("a plain number")
+        } else {
+            format!("a quantity in {}", self)
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let power = |unit: &str, exponent: i8| match exponent {
+            1 => unit.to_string(),
+            _ => format!("{}^{}", unit, exponent),
+        };
+        let mut numerator = vec![];
+        let mut denominator = vec![];
+        for (unit, exponent) in BASE_UNITS.iter().zip(self.0) {
+            if exponent > 0 {
+                numerator.push(power(unit, exponent));
+            } else if exponent < 0 {
+                denominator.push(power(unit, -exponent));
+            }
+        }
+
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        match denominator.len() {
+            0 => Ok(()),
+            1 => write!(f, "/{}", denominator[0]),
+            _ => write!(f, "/({})", denominator.join("*")),
+        }
+    }
+}
+
+// a value in base units, so 30 cm is stored as 0.3 with a dimension of m
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub dimension: Dimension,
+}
+
+impl Quantity {
+    fn plain(value: f64) -> Quantity {
+        Quantity {
+            value,
+            dimension: Dimension::default(),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dimension.is_dimensionless() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} {}", self.value, self.dimension)
+        }
+    }
+}
+
+const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0]);
+const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0]);
+const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0]);
+const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1]);
+const VOLUME: Dimension = Dimension([3, 0, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0]);
+const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0]);
+const ENERGY: Dimension = Dimension([2, 1, -2, 0, 0, 0]);
+const POWER: Dimension = Dimension([2, 1, -3, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension([-1, 1, -2, 0, 0, 0]);
+pub(crate) const MOLAR_MASS: Dimension = Dimension([0, 1, 0, 0, 0, -1]);
+
+// name, size in base units and dimension; `in` is left out because it reads as a keyword
+const UNITS: [(&str, f64, Dimension); 50] = [
+    ("m", 1.0, LENGTH),
+    ("mm", 0.001, LENGTH),
+    ("cm", 0.01, LENGTH),
+    ("km", 1000.0, LENGTH),
+    ("inch", 0.0254, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("yd", 0.9144, LENGTH),
+    ("mi", 1609.344, LENGTH),
+    ("metre", 1.0, LENGTH),
+    ("meter", 1.0, LENGTH),
+    ("foot", 0.3048, LENGTH),
+    ("feet", 0.3048, LENGTH),
+    ("yard", 0.9144, LENGTH),
+    ("mile", 1609.344, LENGTH),
+    ("mg", 1e-6, MASS),
+    ("g", 0.001, MASS),
+    ("kg", 1.0, MASS),
+    ("oz", 0.028349523125, MASS),
+    ("lb", 0.45359237, MASS),
+    ("gram", 0.001, MASS),
+    ("pound", 0.45359237, MASS),
+    ("ms", 0.001, TIME),
+    ("s", 1.0, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3600.0, TIME),
+    ("day", 86400.0, TIME),
+    ("second", 1.0, TIME),
+    ("minute", 60.0, TIME),
+    ("hour", 3600.0, TIME),
+    ("mA", 0.001, CURRENT),
+    ("A", 1.0, CURRENT),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("mL", 1e-6, VOLUME),
+    ("L", 0.001, VOLUME),
+    ("litre", 0.001, VOLUME),
+    ("liter", 0.001, VOLUME),
+    ("Hz", 1.0, FREQUENCY),
+    ("kHz", 1000.0, FREQUENCY),
+    ("N", 1.0, FORCE),
+    ("kN", 1000.0, FORCE),
+    ("J", 1.0, ENERGY),
+    ("kJ", 1000.0, ENERGY),
+    ("cal", 4.184, ENERGY),
+    ("kcal", 4184.0, ENERGY),
+    ("W", 1.0, POWER),
+    ("kW", 1000.0, POWER),
+    ("Pa", 1.0, PRESSURE),
+    ("kPa", 1000.0, PRESSURE),
+    ("bar", 100_000.0, PRESSURE),
+];
+
+// scale and offset to kelvin; alone they are a difference in degrees, only a conversion reads `72 F` as a temperature
+const TEMPERATURES: [(&str, f64, f64); 2] =
+    [("C", 1.0, 273.15), ("F", 5.0 / 9.0, 459.67 * 5.0 / 9.0)];
+
+fn temperature(name: &str) -> Option<(f64, f64)> {
+    TEMPERATURES
+        .iter()
+        .find(|(unit, _, _)| *unit == name)
+        .map(|&(_, scale, offset)| (scale, offset))
+}
+
+pub fn unit(name: &str) -> Option<Quantity> {
+    let find = |name: &str| UNITS.iter().find(|(unit, _, _)| *unit == name);
+    // plurals of the long names, but short symbols like `ms` are left alone
+    let found = find(name).or_else(|| match name.strip_suffix('s') {
+        Some(singular) if singular.len() > 2 => find(singular),
+        _ => None,
+    });
+    if let Some(&(_, value, dimension)) = found {
+        return Some(Quantity { value, dimension });
+    }
+    temperature(name).map(|(scale, _)| Quantity {
+        value: scale,
+        dimension: TEMPERATURE,
+    })
+}
+
+fn mismatch(expected: Dimension, found: Dimension, token: usize) -> anyhow::Error {
+    let kind = ErrorKind::UnitMismatch {
+        expected: expected.describe(),
+        found: found.describe(),
+    };
+    Error::new(kind, Some(token)).into()
+}
+
+fn same(left: &Quantity, right: &Quantity, token: usize) -> anyhow::Result<Dimension> {
+    if left.dimension != right.dimension {
+        return Err(mismatch(left.dimension, right.dimension, token));
+    }
+    Ok(left.dimension)
+}
+
+fn plain(quantities: &[Quantity], token: usize) -> anyhow::Result<Dimension> {
+    match quantities
+        .iter()
+        .find(|quantity| !quantity.dimension.is_dimensionless())
+    {
+        Some(quantity) => Err(mismatch(Dimension::default(), quantity.dimension, token)),
+        None => Ok(Dimension::default()),
+    }
+}
+
+fn overflow(token: usize) -> anyhow::Error {
+    Error::new(ErrorKind::Overflow, Some(token)).into()
+}
+
+// the numbers come from the plain evaluator on the same node, so every numeric rule and error stays the same
+fn numeric(expr: &Expr, operands: &[Quantity], context: &Context) -> anyhow::Result<f64> {
+    let number = |index: usize| Box::new(Expr::Number(operands[index].value));
+    let expr = match expr {
+        Expr::Unary {
+            operator, token, ..
+        } => Expr::Unary {
+            operator: operator.clone(),
+            operand: number(0),
+            token: *token,
+        },
+        Expr::Binary {
+            operator, token, ..
+        } => Expr::Binary {
+            operator: operator.clone(),
+            left: number(0),
+            right: number(1),
+            token: *token,
+        },
+        Expr::Call { name, token, .. } => Expr::Call {
+            name: name.clone(),
+            arguments: operands
+                .iter()
+                .map(|operand| Expr::Number(operand.value))
+                .collect(),
+            token: *token,
+        },
+        _ => expr.clone(),
+    };
+    expr.evaluate(context)
+}
+
+fn evaluate_call(
+    expr: &Expr,
+    name: &str,
+    arguments: &[Quantity],
+    token: usize,
+    context: &Context,
+) -> anyhow::Result<Quantity> {
+    let builtin = context.function(name).is_none();
+    let dimension = match (name, arguments) {
+        ("abs" | "max" | "min", [first, rest @ ..]) if builtin => {
+            for argument in rest {
+                same(first, argument, token)?;
+            }
+            first.dimension
+        }
+        ("sqrt", [argument]) if builtin => argument
+            .dimension
+            .scale(0.5)
+            .ok_or_else(|| mismatch(Dimension::default(), argument.dimension, token))?,
+        ("approx", [left, right, rest @ ..]) if builtin => {
+            same(left, right, token)?;
+            plain(rest, token)?
+        }
+        _ => plain(arguments, token)?,
+    };
+    let value = numeric(expr, arguments, context)?;
+    Ok(Quantity { value, dimension })
+}
+
+// an affine temperature unit the context does not shadow
+fn temperature_unit(expr: &Expr, context: &Context) -> Option<(f64, f64)> {
+    match expr {
+        Expr::Variable { name, .. } if context.get(name).is_none() => temperature(name),
+        _ => None,
+    }
+}
+
+// the result is a plain number, counted in the target unit
+fn convert(
+    value: &Expr,
+    target: &Expr,
+    token: usize,
+    context: &Context,
+) -> anyhow::Result<Quantity> {
+    let source = match value {
+        Expr::Binary {
+            operator: Operator::Multiply,
+            left,
+            right,
+            ..
+        } if temperature_unit(right, context).is_some() => {
+            let (scale, offset) = temperature_unit(right, context).unwrap();
+            let amount = evaluate_expr(left, context)?;
+            plain(&[amount], token)?;
+            Quantity {
+                value: amount.value * scale + offset,
+                dimension: TEMPERATURE,
+            }
+        }
+        _ => evaluate_expr(value, context)?,
+    };
+
+    if let Some((scale, offset)) = temperature_unit(target, context) {
+        if source.dimension != TEMPERATURE {
+            return Err(mismatch(TEMPERATURE, source.dimension, token));
+        }
+        return Ok(Quantity::plain((source.value - offset) / scale));
+    }
+    let target = evaluate_expr(target, context)?;
+    same(&target, &source, token)?;
+    if target.value == 0.0 {
+        return Err(Error::new(ErrorKind::DivisionByZero, Some(token)).into());
+    }
+    Ok(Quantity::plain(source.value / target.value))
+}
+
+fn evaluate_expr(expr: &Expr, context: &Context) -> anyhow::Result<Quantity> {
+    match expr {
+        Expr::Number(number) => Ok(Quantity::plain(*number)),
+        Expr::Constant(constant) => Ok(Quantity::plain(constant.value())),
+        // variables on the context shadow units of the same name
+        Expr::Variable { name, token } => match context.get(name) {
+            Some(value) => Ok(Quantity::plain(value)),
+            None => unit(name).ok_or_else(|| {
+                Error::new(ErrorKind::UnknownVariable(name.clone()), Some(*token)).into()
+            }),
+        },
+        Expr::Unary {
+            operator,
+            operand,
+            token,
+        } => {
+            let operand = evaluate_expr(operand, context)?;
+            let dimension = match operator {
+                Operator::Negative | Operator::Percent => operand.dimension,
+                _ => plain(&[operand], *token)?,
+            };
+            let value = numeric(expr, &[operand], context)?;
+            Ok(Quantity { value, dimension })
+        }
+        Expr::Binary {
+            operator: Operator::Convert,
+            left,
+            right,
+            token,
+        } => convert(left, right, *token, context),
+        // the right side is skipped wherever the plain evaluator skips it
+        Expr::Binary {
+            operator: operator @ (Operator::And | Operator::Or),
+            left,
+            right,
+            token,
+        } => {
+            let left = evaluate_expr(left, context)?;
+            let right = match (operator, left.value != 0.0) {
+                (Operator::And, false) | (Operator::Or, true) => Quantity::plain(0.0),
+                _ => evaluate_expr(right, context)?,
+            };
+            plain(&[left, right], *token)?;
+            Ok(Quantity::plain(numeric(expr, &[left, right], context)?))
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            token,
+        } => {
+            let left = evaluate_expr(left, context)?;
+            let right = evaluate_expr(right, context)?;
+            let dimension = match operator {
+                Operator::Plus | Operator::Minus | Operator::Remainder => {
+                    same(&left, &right, *token)?
+                }
+                comparison if *comparison == Operator::Approx || comparison.is_comparison() => {
+                    same(&left, &right, *token)?;
+                    Dimension::default()
+                }
+                Operator::Multiply => left
+                    .dimension
+                    .combine(right.dimension, 1)
+                    .ok_or_else(|| overflow(*token))?,
+                Operator::Divide => left
+                    .dimension
+                    .combine(right.dimension, -1)
+                    .ok_or_else(|| overflow(*token))?,
+                Operator::Power => {
+                    plain(&[right], *token)?;
+                    left.dimension
+                        .scale(right.value)
+                        .ok_or_else(|| mismatch(Dimension::default(), left.dimension, *token))?
+                }
+                _ => plain(&[left, right], *token)?,
+            };
+            let value = numeric(expr, &[left, right], context)?;
+            Ok(Quantity { value, dimension })
+        }
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } if name == "convert" && context.function(name).is_none() => match arguments.as_slice() {
+            [value, target] => convert(value, target, *token, context),
+            _ => {
+                let kind = ErrorKind::WrongArgumentCount {
+                    name: name.clone(),
+                    expected: 2,
+                    found: arguments.len(),
+                };
+                Err(Error::new(kind, Some(*token)).into())
+            }
+        },
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate_expr(argument, context))
+                .collect::<anyhow::Result<Vec<Quantity>>>()?;
+            evaluate_call(expr, name, &arguments, *token, context)
+        }
+        // the branches may differ in dimension, only the one taken counts
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            token,
+        } => {
+            let condition = evaluate_expr(condition, context)?;
+            plain(&[condition], *token)?;
+            let branch = if condition.value != 0.0 {
+                then
+            } else {
+                otherwise
+            };
+            evaluate_expr(branch, context)
+        }
+    }
+}
+
+pub fn evaluate_quantity(expression: &str) -> anyhow::Result<Quantity> {
+    evaluate_quantity_with(expression, &Context::new())
+}
+
+// like `evaluate_with`, but names in the unit table are quantities, and + - % ~= check that dimensions agree;
+// `value in unit`, `value to unit` and `convert(value, unit)` give the value counted in that unit
+pub fn evaluate_quantity_with(expression: &str, context: &Context) -> anyhow::Result<Quantity> {
+    let (tokens, spans) = crate::tokenize(expression, context, true)?;
+    Parser::new(&tokens, context)
+        .parse()
+        .and_then(|expr| evaluate_expr(&expr, context))
+        .map_err(|error| crate::framed(error, expression, &spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(expression: &str) -> (f64, String) {
+        let quantity = evaluate_quantity(expression).unwrap();
+        // rounded so unit factors like 0.01 compare cleanly
+        let value = (quantity.value * 1e9).round() / 1e9;
+        (value, quantity.dimension.to_string())
+    }
+
+    fn kind_of(expression: &str) -> ErrorKind {
+        let error = evaluate_quantity(expression).unwrap_err();
+        error.downcast_ref::<Error>().unwrap().kind.clone()
+    }
+
+    #[test]
+    fn evaluate_quantity_works() {
+        assert_eq!(quantity("5 m + 30 cm"), (5.3, String::from("m")));
+        assert_eq!(
+            quantity("10 kg * 9.8 m/s^2"),
+            (98.0, String::from("m*kg/s^2"))
+        );
+        assert_eq!(quantity("2 km / (4 h)"), (0.138888889, String::from("m/s")));
+        assert_eq!(
+            quantity("sqrt(9 m^2) - abs(-1 ft)"),
+            (2.6952, String::from("m"))
+        );
+        assert_eq!(quantity("1 N / (1 kg m/s^2)"), (1.0, String::from("1")));
+        assert_eq!(quantity("1 / (2 s A)"), (0.5, String::from("1/(s*A)")));
+        assert_eq!(
+            quantity("2 m > 1 ft ? 3 s : 4 kg"),
+            (3.0, String::from("s"))
+        );
+        assert_eq!(
+            quantity("max(1 mi, 1 km) ~= 1609.344 m"),
+            (1.0, String::from("1"))
+        );
+        assert_eq!(quantity("3! + ln(e)"), (7.0, String::from("1")));
+        assert_eq!(quantity("1 km > 999 m"), (1.0, String::from("1")));
+        assert_eq!(evaluate_quantity("2^1e400").unwrap().value, f64::INFINITY);
+        assert!(evaluate_quantity("2^sqrt(-1)").unwrap().value.is_nan());
+
+        let mut context = Context::new();
+        context.set("m", 4.0);
+        let shadowed = evaluate_quantity_with("2 m", &context).unwrap();
+        assert_eq!(shadowed.to_string(), "8");
+        assert_eq!(
+            evaluate_quantity("1.5 kW").unwrap().to_string(),
+            "1500 m^2*kg/s^3"
+        );
+    }
+    #[test]
+    fn convert_works() {
+        assert_eq!(quantity("5 km in miles"), (3.106855961, String::from("1")));
+        assert_eq!(quantity("90 km/h to m/s"), (25.0, String::from("1")));
+        assert_eq!(quantity("1 mL in cm^3"), (1.0, String::from("1")));
+        assert_eq!(
+            quantity("convert(72 F, C)"),
+            (22.222222222, String::from("1"))
+        );
+        assert_eq!(quantity("-40 C in F"), (-40.0, String::from("1")));
+        assert_eq!(quantity("0 C in K"), (273.15, String::from("1")));
+        // without a conversion a degree is a difference
+        assert_eq!(quantity("10 C + 5 K"), (15.0, String::from("K")));
+
+        assert!(matches!(
+            kind_of("5 km in kg"),
+            ErrorKind::UnitMismatch { .. }
+        ));
+        assert!(matches!(
+            kind_of("5 km in F"),
+            ErrorKind::UnitMismatch { .. }
+        ));
+        assert!(matches!(
+            kind_of("convert(1 m)"),
+            ErrorKind::WrongArgumentCount { .. }
+        ));
+        assert_eq!(kind_of("5 m in (0 m)"), ErrorKind::DivisionByZero);
+        assert_eq!(
+            crate::evaluate("5 in 2")
+                .unwrap_err()
+                .downcast_ref::<Error>()
+                .unwrap()
+                .kind,
+            ErrorKind::UnexpectedToken
+        );
+    }
+    #[test]
+    fn evaluate_quantity_errors() {
+        assert_eq!(
+            kind_of("5 m + 2 s"),
+            ErrorKind::UnitMismatch {
+                expected: String::from("a quantity in m"),
+                found: String::from("a quantity in s"),
+            }
+        );
+        assert_eq!(
+            kind_of("sin(2 m)"),
+            ErrorKind::UnitMismatch {
+                expected: String::from("a plain number"),
+                found: String::from("a quantity in m"),
+            }
+        );
+        assert!(matches!(
+            kind_of("sqrt(2 m)"),
+            ErrorKind::UnitMismatch { .. }
+        ));
+        assert!(matches!(kind_of("2^(1 s)"), ErrorKind::UnitMismatch { .. }));
+        assert_eq!(kind_of("1 m / (0 s)"), ErrorKind::DivisionByZero);
+        assert_eq!(
+            kind_of("2 parsec"),
+            ErrorKind::UnknownVariable(String::from("parsec"))
+        );
+        // evaluation errors point into the expression like parse errors do
+        let error = evaluate_quantity("5 m in s").unwrap_err().to_string();
+        assert_eq!(
+            error.lines().take(3).collect::<Vec<_>>(),
+            [
+                "expected a quantity in s, found a quantity in m",
+                "  | 5 m in s",
+                "  |     ^^",
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+pub fn encode_for_url(expression: &str) -> String {
+    let mut result = String::new();
+    for byte in expression.bytes() {
+        if is_unreserved(byte) {
+            result.push(byte as char);
+        } else {
+            // spaces go out as %20, never `+`, so a plus sign can't be mistaken for one
+            result += &format!("%{:02X}", byte);
+        }
+    }
+    result
+}
+
+pub fn decode_from_url(encoded: &str) -> anyhow::Result<String> {
+    let mut bytes = vec![];
+    let mut iterator = encoded.bytes();
+
+    while let Some(byte) = iterator.next() {
+        match byte {
+            b'%' => {
+                let high = iterator
+                    .next()
+                    .and_then(|digit| (digit as char).to_digit(16));
+                let low = iterator
+                    .next()
+                    .and_then(|digit| (digit as char).to_digit(16));
+                match (high, low) {
+                    (Some(high), Some(low)) => bytes.push((high * 16 + low) as u8),
+                    _ => return Err(anyhow::Error::msg("malformed percent escape in url")),
+                }
+            }
+            // a literal `+` in a formula link is far more likely to be addition than a space
+            _ => bytes.push(byte),
+        }
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_works() {
+        assert_eq!(
+            encode_for_url("(2 + 3)^2 % 4"),
+            "%282%20%2B%203%29%5E2%20%25%204"
+        );
+        assert_eq!(encode_for_url("5.3"), "5.3");
+    }
+    #[test]
+    fn decode_works() {
+        assert_eq!(
+            decode_from_url("%282%20%2B%203%29%5E2").unwrap(),
+            "(2 + 3)^2"
+        );
+        assert_eq!(decode_from_url("1+2").unwrap(), "1+2");
+        assert!(decode_from_url("50%").is_err());
+        assert!(decode_from_url("%zz").is_err());
+    }
+    #[test]
+    fn round_trip() {
+        let expression = "{[1 / 3] * 2}! - 10 % 3";
+        assert_eq!(
+            decode_from_url(&encode_for_url(expression)).unwrap(),
+            expression
+        );
+    }
+}
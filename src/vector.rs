@@ -0,0 +1,209 @@
+use crate::ast::Expr;
+use crate::Context;
+
+// the element is bound to `t`, so "2 * t + 1" is applied to each value in turn
+const ELEMENT: &str = "t";
+// reduce also binds the running result
+const ACCUMULATOR: &str = "acc";
+// ranges are allocated up front, so a tiny step can't ask for all of memory
+const MAX_LENGTH: usize = 10_000_000;
+
+// evaluates the parsed expression once per value, with the element bound
+fn each(expr: &Expr, values: &[f64], context: &Context) -> anyhow::Result<Vec<f64>> {
+    let mut context = context.clone();
+    values
+        .iter()
+        .map(|&value| {
+            context.set(ELEMENT, value);
+            expr.evaluate(&context)
+        })
+        .collect()
+}
+
+pub fn map(expression: &str, values: &[f64]) -> anyhow::Result<Vec<f64>> {
+    map_with(expression, values, &Context::new())
+}
+
+pub fn map_with(expression: &str, values: &[f64], context: &Context) -> anyhow::Result<Vec<f64>> {
+    let expr = crate::parse_with(expression, context)?;
+    each(&expr, values, context)
+}
+
+pub fn filter(predicate: &str, values: &[f64]) -> anyhow::Result<Vec<f64>> {
+    filter_with(predicate, values, &Context::new())
+}
+
+// keeps the values the predicate holds for, any nonzero result counting as true
+pub fn filter_with(predicate: &str, values: &[f64], context: &Context) -> anyhow::Result<Vec<f64>> {
+    let expr = crate::parse_with(predicate, context)?;
+    let results = each(&expr, values, context)?;
+    Ok(values
+        .iter()
+        .zip(results)
+        .filter(|&(_, result)| result != 0.0)
+        .map(|(&value, _)| value)
+        .collect())
+}
+
+pub fn countif(values: &[f64], predicate: &str) -> anyhow::Result<usize> {
+    countif_with(values, predicate, &Context::new())
+}
+
+// spreadsheet-style, with the element bound to t: countif(&scores, "t >= 50")
+pub fn countif_with(values: &[f64], predicate: &str, context: &Context) -> anyhow::Result<usize> {
+    Ok(filter_with(predicate, values, context)?.len())
+}
+
+pub fn sumif(values: &[f64], predicate: &str) -> anyhow::Result<f64> {
+    sumif_with(values, predicate, &Context::new())
+}
+
+pub fn sumif_with(values: &[f64], predicate: &str, context: &Context) -> anyhow::Result<f64> {
+    Ok(filter_with(predicate, values, context)?.iter().sum())
+}
+
+pub fn reduce(expression: &str, values: &[f64], initial: f64) -> anyhow::Result<f64> {
+    reduce_with(expression, values, initial, &Context::new())
+}
+
+// folds the values left to right, "acc + t" sums them
+pub fn reduce_with(
+    expression: &str,
+    values: &[f64],
+    initial: f64,
+    context: &Context,
+) -> anyhow::Result<f64> {
+    let expr = crate::parse_with(expression, context)?;
+    let mut context = context.clone();
+    let mut result = initial;
+    for &value in values {
+        context.set(ACCUMULATOR, result);
+        context.set(ELEMENT, value);
+        result = expr.evaluate(&context)?;
+    }
+    Ok(result)
+}
+
+// start, start + step, ... up to and including end when a step lands on it, so 0..1 step 0.1 ends at 1
+pub fn range(start: f64, end: f64, step: f64) -> anyhow::Result<Vec<f64>> {
+    if !(start.is_finite() && end.is_finite() && step.is_finite()) {
+        return Err(anyhow::Error::msg("range bounds and step must be finite"));
+    }
+    if step == 0.0 || (end - start) * step < 0.0 {
+        return Err(anyhow::Error::msg(
+            "range step must move from start towards end",
+        ));
+    }
+    // the slack keeps 0..1 step 0.1 from losing its end to rounding
+    let steps = ((end - start) / step * (1.0 + 1e-12)).floor();
+    if steps >= MAX_LENGTH as f64 {
+        return Err(anyhow::Error::msg("range is too long"));
+    }
+    Ok((0..=steps as usize)
+        .map(|index| start + step * index as f64)
+        .collect())
+}
+
+// `count` evenly spaced values from start to end, both included
+pub fn linspace(start: f64, end: f64, count: usize) -> anyhow::Result<Vec<f64>> {
+    if !(start.is_finite() && end.is_finite()) {
+        return Err(anyhow::Error::msg("linspace bounds must be finite"));
+    }
+    if count > MAX_LENGTH {
+        return Err(anyhow::Error::msg("linspace is too long"));
+    }
+    Ok(match count {
+        0 => vec![],
+        1 => vec![start],
+        _ => (0..count)
+            .map(|index| {
+                // the last value is end exactly, not end plus rounding
+                if index == count - 1 {
+                    end
+                } else {
+                    start + (end - start) * index as f64 / (count - 1) as f64
+                }
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_works() {
+        assert_eq!(map("2 * t + 1", &[0.0, 1.0, 2.5]).unwrap(), [1.0, 3.0, 6.0]);
+        assert_eq!(map("t^2", &[]).unwrap(), Vec::<f64>::new());
+        // a scalar from the context is broadcast over every element
+        let mut context = Context::new();
+        context.set("k", 10.0);
+        assert_eq!(
+            map_with("k * t", &[1.0, 2.0], &context).unwrap(),
+            [10.0, 20.0]
+        );
+        assert!(map("t +", &[1.0]).is_err());
+        assert!(map("y * t", &[1.0]).is_err());
+    }
+    #[test]
+    fn filter_works() {
+        let values = [3.0, -1.0, 4.0, -1.5, 0.0];
+        assert_eq!(filter("t > 0", &values).unwrap(), [3.0, 4.0]);
+        assert_eq!(filter("t", &values).unwrap(), [3.0, -1.0, 4.0, -1.5]);
+        assert_eq!(filter("t == 7", &values).unwrap(), Vec::<f64>::new());
+    }
+    #[test]
+    fn countif_and_sumif_work() {
+        let values = [12.0, 55.0, 70.0, 49.5, 50.0];
+        assert_eq!(countif(&values, "t >= 50").unwrap(), 3);
+        assert_eq!(sumif(&values, "t >= 50").unwrap(), 175.0);
+        assert_eq!(countif(&values, "t % 2 == 0 and t < 60").unwrap(), 2);
+        assert_eq!(sumif(&values, "t > 100").unwrap(), 0.0);
+        let mut context = Context::new();
+        context.set("limit", 60.0);
+        assert_eq!(countif_with(&values, "t > limit", &context).unwrap(), 1);
+        assert!(countif(&values, "t >").is_err());
+    }
+    #[test]
+    fn reduce_works() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(reduce("acc + t", &values, 0.0).unwrap(), 10.0);
+        assert_eq!(reduce("acc * t", &values, 1.0).unwrap(), 24.0);
+        assert_eq!(
+            reduce("max(acc, t)", &values, f64::NEG_INFINITY).unwrap(),
+            4.0
+        );
+        assert_eq!(reduce("acc + t", &[], 5.0).unwrap(), 5.0);
+    }
+    #[test]
+    fn range_works() {
+        assert_eq!(range(1.0, 5.0, 1.0).unwrap(), [1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(range(5.0, 1.0, -2.0).unwrap(), [5.0, 3.0, 1.0]);
+        assert_eq!(range(0.0, 1.0, 0.3).unwrap().len(), 4);
+        let tenths = range(0.0, 1.0, 0.1).unwrap();
+        assert_eq!(tenths.len(), 11);
+        assert!((tenths[10] - 1.0).abs() < 1e-12);
+        assert_eq!(range(2.0, 2.0, 1.0).unwrap(), [2.0]);
+
+        assert!(range(0.0, 1.0, 0.0).is_err());
+        assert!(range(0.0, 1.0, -0.1).is_err());
+        assert!(range(0.0, f64::INFINITY, 1.0).is_err());
+        assert!(range(0.0, 1.0, f64::NAN).is_err());
+        assert!(range(0.0, 1.0, 1e-12).is_err());
+    }
+    #[test]
+    fn linspace_works() {
+        assert_eq!(linspace(0.0, 1.0, 5).unwrap(), [0.0, 0.25, 0.5, 0.75, 1.0]);
+        let elevenths = linspace(0.0, 1.0, 11).unwrap();
+        assert_eq!((elevenths.len(), elevenths[10]), (11, 1.0));
+        assert_eq!(linspace(3.0, -3.0, 3).unwrap(), [3.0, 0.0, -3.0]);
+        assert_eq!(linspace(4.0, 9.0, 1).unwrap(), [4.0]);
+        assert!(linspace(4.0, 9.0, 0).unwrap().is_empty());
+        assert!(linspace(f64::NAN, 1.0, 3).is_err());
+        assert!(linspace(0.0, 1.0, usize::MAX).is_err());
+        // ranges feed straight into the other helpers
+        let squares = map("t^2", &range(1.0, 3.0, 1.0).unwrap()).unwrap();
+        assert_eq!(squares, [1.0, 4.0, 9.0]);
+    }
+}
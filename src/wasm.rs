@@ -0,0 +1,107 @@
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::{lexer, Context};
+
+// built for the browser with
+// `cargo rustc --lib --release --features wasm --target wasm32-unknown-unknown --crate-type cdylib`
+
+// the plain parts of an error, before they become a js object
+#[derive(Debug, PartialEq)]
+struct Fields {
+    message: String,
+    kind: Option<String>,
+    token: Option<usize>,
+}
+
+fn fields(error: &anyhow::Error) -> Fields {
+    match error.downcast_ref::<Error>() {
+        Some(error) => Fields {
+            message: error.kind.to_string(),
+            kind: Some(format!("{:?}", error.kind)),
+            token: error.token,
+        },
+        None => Fields {
+            message: error.to_string(),
+            kind: None,
+            token: None,
+        },
+    }
+}
+
+// errors reach javascript as `{ message, kind, token }` rather than as a panic
+fn to_js(error: anyhow::Error) -> JsValue {
+    let fields = fields(&error);
+    let object = Object::new();
+    let set = |key: &str, value: JsValue| Reflect::set(&object, &key.into(), &value);
+    let _ = set("message", fields.message.into());
+    let _ = set("kind", fields.kind.map_or(JsValue::NULL, JsValue::from));
+    let _ = set(
+        "token",
+        fields
+            .token
+            .map_or(JsValue::NULL, |token| JsValue::from(token as u32)),
+    );
+    object.into()
+}
+
+#[wasm_bindgen]
+pub fn evaluate(expression: &str) -> Result<f64, JsValue> {
+    crate::evaluate(expression).map_err(to_js)
+}
+
+// `variables` is a plain object of numbers, `{ x: 3 }`
+#[wasm_bindgen(js_name = evaluateWith)]
+pub fn evaluate_with(expression: &str, variables: &Object) -> Result<f64, JsValue> {
+    let mut context = Context::new();
+    for entry in Object::entries(variables).iter() {
+        let entry = Array::from(&entry);
+        let (name, value) = (entry.get(0).as_string(), entry.get(1).as_f64());
+        match (name, value) {
+            (Some(name), Some(value)) => context.set(&name, value),
+            _ => return Err(to_js(anyhow::Error::msg("variables must all be numbers"))),
+        }
+    }
+    crate::evaluate_with(expression, &context).map_err(to_js)
+}
+
+// the tokens the parser will see, as strings
+#[wasm_bindgen]
+pub fn parse(expression: &str) -> Result<Array, JsValue> {
+    let tokens = lexer::lex(expression).map_err(to_js)?;
+    Ok(lexer::imply_multiplication(tokens)
+        .iter()
+        .map(|token| JsValue::from(token.to_string()))
+        .collect())
+}
+
+#[wasm_bindgen]
+pub fn format(expression: &str) -> Result<String, JsValue> {
+    crate::minify::minify(expression).map_err(to_js)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_works() {
+        let error = crate::evaluate("1 / 0").unwrap_err();
+        assert_eq!(
+            fields(&error),
+            Fields {
+                message: String::from("division by zero"),
+                kind: Some(String::from("DivisionByZero")),
+                token: Some(1),
+            }
+        );
+        let error = crate::evaluate("1 $ 2").unwrap_err();
+        assert_eq!(
+            fields(&error).kind,
+            Some(String::from("UnknownCharacter('$')"))
+        );
+        let error = anyhow::Error::msg("not one of ours");
+        assert_eq!(fields(&error).kind, None);
+    }
+}
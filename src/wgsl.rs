@@ -0,0 +1,564 @@
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::Operator;
+use crate::{AngleMode, Context};
+
+// wgsl's pow is undefined for negative bases, the interpreter's powf is not
+const HELPERS: &str = "fn power(a: f32, b: f32) -> f32 {
+    if (a < 0.0 && fract(b) == 0.0) {
+        let magnitude = pow(-a, b);
+        return select(magnitude, -magnitude, fract(b * 0.5) != 0.0);
+    }
+    return pow(a, b);
+}
+
+fn approx(a: f32, b: f32, absolute: f32, relative: f32) -> f32 {
+    let bound = max(absolute, relative * max(abs(a), abs(b)));
+    return select(0.0, 1.0, a == b || abs(a - b) <= bound);
+}
+";
+
+fn unsupported(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("{} has no WGSL translation", what))
+}
+
+fn literal(value: f64) -> anyhow::Result<String> {
+    let value = value as f32;
+    if !value.is_finite() {
+        return Err(unsupported("a number outside the f32 range"));
+    }
+    Ok(format!("{:?}", value))
+}
+
+fn translate_call(
+    name: &str,
+    arguments: &[String],
+    token: usize,
+    context: &Context,
+) -> anyhow::Result<String> {
+    if context.function(name).is_some() {
+        return Err(unsupported(&format!("host function {}()", name)));
+    }
+    let count = |expected: usize| -> anyhow::Result<()> {
+        if arguments.len() == expected {
+            return Ok(());
+        }
+        let kind = ErrorKind::WrongArgumentCount {
+            name: name.to_string(),
+            expected,
+            found: arguments.len(),
+        };
+        Err(Error::new(kind, Some(token)).into())
+    };
+    if matches!(name, "max" | "min" | "avg") && arguments.is_empty() {
+        let kind = ErrorKind::TooFewArguments {
+            name: name.to_string(),
+            minimum: 1,
+            found: 0,
+        };
+        return Err(Error::new(kind, Some(token)).into());
+    }
+
+    match name {
+        "sin" | "cos" | "tan" => {
+            count(1)?;
+            match context.options.angle_mode {
+                AngleMode::Radians => Ok(format!("{}({})", name, arguments[0])),
+                AngleMode::Degrees => Ok(format!("{}(radians({}))", name, arguments[0])),
+            }
+        }
+        "sqrt" | "exp" | "abs" => {
+            count(1)?;
+            Ok(format!("{}({})", name, arguments[0]))
+        }
+        "ln" => {
+            count(1)?;
+            Ok(format!("log({})", arguments[0]))
+        }
+        "log" => {
+            count(1)?;
+            Ok(format!("(log({}) / log(10.0))", arguments[0]))
+        }
+        "approx" => {
+            count(3)?;
+            Ok(format!(
+                "approx({}, {}, {}, {})",
+                arguments[0], arguments[1], arguments[2], arguments[2]
+            ))
+        }
+        // wgsl's max and min take exactly two arguments
+        "max" | "min" => Ok(arguments[1..]
+            .iter()
+            .fold(arguments[0].clone(), |folded, argument| {
+                format!("{}({}, {})", name, folded, argument)
+            })),
+        "avg" => Ok(format!(
+            "(({}) / {:?})",
+            arguments.join(" + "),
+            arguments.len() as f32
+        )),
+        _ => Err(Error::new(ErrorKind::UnknownFunction(name.to_string()), Some(token)).into()),
+    }
+}
+
+fn translate(expr: &Expr, variables: &[&str], context: &Context) -> anyhow::Result<String> {
+    match expr {
+        Expr::Number(number) => literal(*number),
+        Expr::Constant(constant) => literal(constant.value()),
+        Expr::Variable { name, token } => match variables
+            .iter()
+            .position(|variable| variable == name)
+        {
+            Some(index) => Ok(format!("inputs[base + {}u]", index)),
+            None => Err(Error::new(ErrorKind::UnknownVariable(name.clone()), Some(*token)).into()),
+        },
+        Expr::Unary {
+            operator, operand, ..
+        } => {
+            let operand = translate(operand, variables, context)?;
+            match operator {
+                Operator::Negative => Ok(format!("(-{})", operand)),
+                Operator::Percent => Ok(format!("({} / 100.0)", operand)),
+                Operator::Not => Ok(format!("select(0.0, 1.0, {} == 0.0)", operand)),
+                Operator::Factorial => Err(unsupported("factorial")),
+                Operator::BitNot => Err(unsupported("a bitwise operator")),
+                _ => Err(unsupported("a custom operator")),
+            }
+        }
+        Expr::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => {
+            let left = translate(left, variables, context)?;
+            let right = translate(right, variables, context)?;
+            let symbol = match operator {
+                Operator::Plus => "+",
+                Operator::Minus => "-",
+                Operator::Multiply => "*",
+                Operator::Divide => "/",
+                // truncated like f64's %, so the signs agree with the interpreter
+                Operator::Remainder => "%",
+                comparison if comparison.is_comparison() => {
+                    return Ok(format!(
+                        "select(0.0, 1.0, {} {} {})",
+                        left,
+                        crate::lexer::Token::Binary(comparison.clone()),
+                        right
+                    ))
+                }
+                Operator::And | Operator::Or => {
+                    return Ok(format!(
+                        "select(0.0, 1.0, {} != 0.0 {} {} != 0.0)",
+                        left,
+                        if *operator == Operator::And {
+                            "&&"
+                        } else {
+                            "||"
+                        },
+                        right
+                    ))
+                }
+                Operator::Power => return Ok(format!("power({}, {})", left, right)),
+                Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                    return Err(unsupported("a bitwise operator"))
+                }
+                Operator::Approx => {
+                    return Ok(format!(
+                        "approx({}, {}, {}, {})",
+                        left,
+                        right,
+                        literal(context.options.approx_absolute)?,
+                        literal(context.options.approx_relative)?
+                    ))
+                }
+                _ => return Err(unsupported("a custom operator")),
+            };
+            Ok(format!("({} {} {})", left, symbol, right))
+        }
+        Expr::Call {
+            name,
+            arguments,
+            token,
+        } => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| translate(argument, variables, context))
+                .collect::<anyhow::Result<Vec<String>>>()?;
+            translate_call(name, &arguments, *token, context)
+        }
+        // both branches are computed, which is harmless where nothing can fail
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => Ok(format!(
+            "select({}, {}, {} != 0.0)",
+            translate(otherwise, variables, context)?,
+            translate(then, variables, context)?,
+            translate(condition, variables, context)?
+        )),
+    }
+}
+
+// a compute shader evaluating the expression once per row: `inputs` holds the rows back to back,
+// one f32 per variable in the given order, and `outputs` gets one result per row.
+// division by zero gives inf or NaN on the gpu instead of an error, and precision is f32
+pub fn to_wgsl(expression: &str, variables: &[&str], context: &Context) -> anyhow::Result<String> {
+    let expr = crate::parse_with(expression, context)?;
+    let body = translate(&expr, variables, context)?;
+    Ok(format!(
+        "@group(0) @binding(0) var<storage, read> inputs: array<f32>;
+@group(0) @binding(1) var<storage, read_write> outputs: array<f32>;
+
+{}
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+    let row = id.x;
+    if (row >= arrayLength(&outputs)) {{
+        return;
+    }}
+    let base = row * {}u;
+    outputs[row] = {};
+}}
+",
+        HELPERS,
+        variables.len(),
+        body
+    ))
+}
+
+// the same buffer layout evaluated on the cpu, for when no gpu is available;
+// without variables the shader writes one constant everywhere, so that is a single row
+pub fn evaluate_rows(
+    expression: &str,
+    variables: &[&str],
+    inputs: &[f64],
+    context: &Context,
+) -> anyhow::Result<Vec<f64>> {
+    let expr = crate::parse_with(expression, context)?;
+    if variables.is_empty() {
+        if !inputs.is_empty() {
+            return Err(anyhow::Error::msg("inputs must be empty without variables"));
+        }
+        return Ok(vec![expr.evaluate(context)?]);
+    }
+    if !inputs.len().is_multiple_of(variables.len()) {
+        return Err(anyhow::Error::msg(
+            "inputs must hold a whole number of rows, one value per variable",
+        ));
+    }
+    let mut context = context.clone();
+    inputs
+        .chunks(variables.len())
+        .map(|row| {
+            for (name, value) in variables.iter().zip(row) {
+                context.set(name, *value);
+            }
+            expr.evaluate(&context)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(expression: &str, context: &Context) -> String {
+        let shader = to_wgsl(expression, &["x", "y"], context).unwrap();
+        let line = shader
+            .lines()
+            .find(|line| line.trim_start().starts_with("outputs[row] = "))
+            .unwrap();
+        line.trim().to_string()
+    }
+
+    #[test]
+    fn to_wgsl_works() {
+        let context = Context::new();
+        assert_eq!(
+            body("2x^2 - y / 4", &context),
+            "outputs[row] = ((2.0 * power(inputs[base + 0u], 2.0)) - (inputs[base + 1u] / 4.0));"
+        );
+        assert_eq!(
+            body("max(x, y, 1) + log(avg(x, y))", &context),
+            "outputs[row] = (max(max(inputs[base + 0u], inputs[base + 1u]), 1.0) + (log(((inputs[base + 0u] + inputs[base + 1u]) / 2.0)) / log(10.0)));"
+        );
+        assert_eq!(
+            body("x >= y", &context),
+            "outputs[row] = select(0.0, 1.0, inputs[base + 0u] >= inputs[base + 1u]);"
+        );
+        assert_eq!(
+            body("x > 0 ? x : 2", &context),
+            "outputs[row] = select(2.0, inputs[base + 0u], select(0.0, 1.0, inputs[base + 0u] > 0.0) != 0.0);"
+        );
+        assert_eq!(
+            body("not x or y", &context),
+            "outputs[row] = select(0.0, 1.0, select(0.0, 1.0, inputs[base + 0u] == 0.0) != 0.0 || inputs[base + 1u] != 0.0);"
+        );
+        let mut degrees = Context::new();
+        degrees.options.angle_mode = AngleMode::Degrees;
+        assert_eq!(
+            body("sin(x)", &degrees),
+            "outputs[row] = sin(radians(inputs[base + 0u]));"
+        );
+
+        let shader = to_wgsl("x + y", &["x", "y"], &context).unwrap();
+        assert!(shader.contains("let base = row * 2u;"));
+        assert!(shader.contains("fn power(a: f32, b: f32) -> f32"));
+    }
+    #[test]
+    fn to_wgsl_errors() {
+        let context = Context::new();
+        assert!(to_wgsl("3!", &[], &context).is_err());
+        assert!(to_wgsl("1e300", &[], &context).is_err());
+        let error = to_wgsl("x + z", &["x"], &context).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Error>().map(|error| &error.kind),
+            Some(&ErrorKind::UnknownVariable(String::from("z")))
+        );
+        let mut context = Context::new();
+        context.register_fn("double", 1, |args| args[0] * 2.0);
+        assert!(to_wgsl("double(1)", &[], &context).is_err());
+    }
+    #[test]
+    fn evaluate_rows_works() {
+        let context = Context::new();
+        let rows = evaluate_rows("x^y - x", &["x", "y"], &[2.0, 3.0, -2.0, 3.0], &context).unwrap();
+        assert_eq!(rows, vec![6.0, -6.0]);
+        assert!(evaluate_rows("x", &["x", "y"], &[1.0], &context).is_err());
+
+        // a constant translates with no variables, so it evaluates with none too
+        assert!(to_wgsl("2 * pi", &[], &context).is_ok());
+        let rows = evaluate_rows("2 * pi", &[], &[], &context).unwrap();
+        assert_eq!(rows, vec![std::f64::consts::TAU]);
+        assert!(evaluate_rows("2", &[], &[1.0], &context).is_err());
+    }
+    // just enough of wgsl to run a translated body on the cpu in f32: the generated code is
+    // fully parenthesized, so only select's conditions and the helpers need precedence
+    struct Shader<'a> {
+        text: &'a str,
+        row: &'a [f32],
+    }
+
+    impl Shader<'_> {
+        fn eat(&mut self, symbol: &str) -> bool {
+            self.text = self.text.trim_start();
+            match self.text.strip_prefix(symbol) {
+                Some(rest) => {
+                    self.text = rest;
+                    true
+                }
+                None => false,
+            }
+        }
+
+        fn expect(&mut self, symbol: &str) {
+            assert!(self.eat(symbol), "expected {} at {}", symbol, self.text);
+        }
+
+        // a name or a literal, whose exponent may have a sign, as in 1e-7
+        fn word(&mut self) -> &str {
+            self.text = self.text.trim_start();
+            let mut end = 0;
+            for (index, char) in self.text.char_indices() {
+                let signed = matches!(char, '-' | '+')
+                    && self.text[..index].ends_with('e')
+                    && self.text.starts_with(|char: char| char.is_ascii_digit());
+                if !(char.is_ascii_alphanumeric() || char == '_' || char == '.' || signed) {
+                    break;
+                }
+                end = index + char.len_utf8();
+            }
+            let (word, rest) = self.text.split_at(end);
+            self.text = rest;
+            word
+        }
+
+        fn or(&mut self) -> f32 {
+            let mut value = self.and();
+            while self.eat("||") {
+                let right = self.and();
+                value = f32::from(value != 0.0 || right != 0.0);
+            }
+            value
+        }
+
+        fn and(&mut self) -> f32 {
+            let mut value = self.comparison();
+            while self.eat("&&") {
+                let right = self.comparison();
+                value = f32::from(value != 0.0 && right != 0.0);
+            }
+            value
+        }
+
+        fn comparison(&mut self) -> f32 {
+            let left = self.sum();
+            for symbol in ["==", "!=", "<=", ">=", "<", ">"] {
+                if self.eat(symbol) {
+                    let right = self.sum();
+                    let holds = match symbol {
+                        "==" => left == right,
+                        "!=" => left != right,
+                        "<=" => left <= right,
+                        ">=" => left >= right,
+                        "<" => left < right,
+                        _ => left > right,
+                    };
+                    return f32::from(holds);
+                }
+            }
+            left
+        }
+
+        fn sum(&mut self) -> f32 {
+            let mut value = self.product();
+            loop {
+                if self.eat("+") {
+                    value += self.product();
+                } else if self.text.trim_start().starts_with("- ") && self.eat("-") {
+                    value -= self.product();
+                } else {
+                    return value;
+                }
+            }
+        }
+
+        fn product(&mut self) -> f32 {
+            let mut value = self.unary();
+            loop {
+                if self.eat("*") {
+                    value *= self.unary();
+                } else if self.eat("/") {
+                    value /= self.unary();
+                } else if self.eat("%") {
+                    value %= self.unary();
+                } else {
+                    return value;
+                }
+            }
+        }
+
+        fn unary(&mut self) -> f32 {
+            if self.eat("-") {
+                return -self.unary();
+            }
+            if self.eat("(") {
+                let value = self.or();
+                self.expect(")");
+                return value;
+            }
+            let word = self.word().to_string();
+            if word == "inputs" {
+                self.expect("[");
+                self.expect("base");
+                self.expect("+");
+                let index: usize = self.word().trim_end_matches('u').parse().unwrap();
+                self.expect("]");
+                return self.row[index];
+            }
+            if let Ok(number) = word.parse::<f32>() {
+                return number;
+            }
+            self.expect("(");
+            let mut arguments = vec![self.or()];
+            while self.eat(",") {
+                arguments.push(self.or());
+            }
+            self.expect(")");
+            call(&word, &arguments)
+        }
+    }
+
+    // the builtins the translation uses, and the helpers written the way HELPERS has them
+    fn call(name: &str, arguments: &[f32]) -> f32 {
+        let (a, b) = (arguments[0], arguments.get(1).copied().unwrap_or_default());
+        match name {
+            "sin" => a.sin(),
+            "cos" => a.cos(),
+            "tan" => a.tan(),
+            "radians" => a.to_radians(),
+            "sqrt" => a.sqrt(),
+            "exp" => a.exp(),
+            "abs" => a.abs(),
+            "log" => a.ln(),
+            "max" => a.max(b),
+            "min" => a.min(b),
+            "select" if arguments[2] != 0.0 => b,
+            "select" => a,
+            "power" if a < 0.0 && b.fract() == 0.0 => {
+                let magnitude = (-a).powf(b);
+                if (b * 0.5).fract() != 0.0 {
+                    -magnitude
+                } else {
+                    magnitude
+                }
+            }
+            "power" => a.powf(b),
+            "approx" => {
+                let bound = arguments[2].max(arguments[3] * a.abs().max(b.abs()));
+                f32::from(a == b || (a - b).abs() <= bound)
+            }
+            _ => panic!("no such function {}", name),
+        }
+    }
+
+    // runs the translated body rather than the interpreter, so the shader is what gets checked
+    fn run_shader(expression: &str, row: &[f32], context: &Context) -> f32 {
+        let shader = to_wgsl(expression, &["x", "y"], context).unwrap();
+        let line = shader
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("outputs[row] = "))
+            .unwrap();
+        let mut shader = Shader {
+            text: line.strip_suffix(';').unwrap(),
+            row,
+        };
+        let value = shader.or();
+        assert!(shader.text.trim().is_empty(), "left over: {}", shader.text);
+        value
+    }
+
+    #[test]
+    fn to_wgsl_matches_evaluate() {
+        let inputs = [2.0, 3.0, -2.0, 0.5, 0.0, -1.0, 1e3, 7.0, -3.0, 2.0];
+        let mut degrees = Context::new();
+        degrees.options.angle_mode = AngleMode::Degrees;
+        for context in [Context::new(), degrees] {
+            for expression in [
+                "2x^2 - y / 4",
+                "max(x, y, 1) + log(avg(x, y))",
+                "x >= y",
+                "x > 0 ? x : 2",
+                "not x or y",
+                "sin(x) * cos(y) + tan(x / 8)",
+                "x % 3 + abs(y)",
+                "approx(x, y, 1)",
+                "x^y - -x% + sqrt(abs(y)) * exp(-x / 1000)",
+                "min(x, y) ~= x and ln(abs(x) + 1e-7) < 2",
+            ] {
+                for row in inputs.chunks(2) {
+                    let found = run_shader(expression, &[row[0] as f32, row[1] as f32], &context);
+                    let found = f64::from(found);
+                    let mut context = context.clone();
+                    context.set("x", row[0]);
+                    context.set("y", row[1]);
+                    let expected = crate::evaluate_with(expression, &context).unwrap();
+                    // f32 keeps about seven digits
+                    let close = (found - expected).abs() <= 1e-5 * expected.abs().max(1.0);
+                    assert!(
+                        close || found == expected || (found.is_nan() && expected.is_nan()),
+                        "{} at {:?}: {} != {}",
+                        expression,
+                        row,
+                        found,
+                        expected
+                    );
+                }
+            }
+        }
+    }
+}